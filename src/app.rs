@@ -0,0 +1,3099 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the state of the interface: what's listed, selected, marked and queued, and
+// everything that's open on top of the list, along with the background work
+// that fills it in.
+
+use std::error::Error;
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ffi::OsString;
+
+use rand::seq::SliceRandom;
+
+use kira::sound::static_sound::StaticSoundData;
+
+use tui::layout::Rect;
+use tui::widgets::ListState;
+
+use crate::device::{default_device_name, output_device_names};
+use crate::duplicates::find_duplicates;
+use crate::error::SpinupError;
+use crate::event::AppEvent;
+use crate::export::{ExportFormat, ExportOptions, TrimRegion, convert_dest, export_sound, trim_dest};
+use crate::cover::{CoverArt, read_cover_art};
+use crate::fuzzy;
+use crate::keymap;
+use crate::loudness::{Loudness, measure_loudness};
+use crate::theme;
+use crate::tree::DirTree;
+use crate::fsutil::{CollisionChoice, DirWatch, FileOpMessage, FileOpProgress, DURATION_CACHE_SIZE, FileStamp, RECENT_MAX, RecentPlay, RecursiveScan, ScanMessage, ScanOptions, base64_encode, default_open_command, file_stamp, get_directories_in_dir, get_playlists_in_dir, get_supported_files_in_dir, probed_as_audio, run_file_operation, save_bookmarks, save_duration_cache, save_recent, start_recursive_scan};
+use crate::playlist::{is_playlist, write_m3u};
+use crate::ratings::{MAX_RATING, Ratings};
+use crate::player::{AudioTrackInfo, DecodedWaveform, PlayOptions, SoundFileCodecData, SoundFileMetadata, SoundState, compute_waveform, count_duration, probe_duration, probe_file_info, probe_tags};
+use crate::ui::{FINDER_MAX_RESULTS, TREE_LAYOUT_MIN_WIDTH, format_duration};
+
+// these are the supported fileformats from Kira / symphonia
+pub(crate) const SUPPORTED_EXTS: [&str; 10] = ["wav", "ogg", "mp3", "flac", "aif", "aiff", "m4a", "mp4", "aac", "opus"];
+
+// how long the selection has to stay put before preview mode plays it
+pub(crate) const PREVIEW_DEBOUNCE_TIME: std::time::Duration = std::time::Duration::from_millis(250);
+
+// the most directories the back and forward history each remember
+pub(crate) const MAX_DIR_HISTORY: usize = 100;
+
+// the most waveforms kept around before the cache gets emptied
+pub(crate) const WAVEFORM_CACHE_SIZE: usize = 256;
+
+// the most cover art thumbnails kept around before the cache gets emptied
+pub(crate) const COVER_CACHE_SIZE: usize = 256;
+
+// the most probed files kept around before the info cache gets emptied
+pub(crate) const FILE_INFO_CACHE_SIZE: usize = 4096;
+
+// how many decoded sounds are kept ready to play. they're the whole file in memory,
+// so only the last few selected or played are kept.
+pub(crate) const DECODED_CACHE_SIZE: usize = 2;
+
+// the largest count that can be typed before a motion
+pub(crate) const MAX_COUNT: usize = 99999;
+
+// how long the watched directory has to go without changes before it's listed again
+pub(crate) const DIR_WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(500);
+
+// how often a directory that can't be watched is checked for changes made by other programs
+pub(crate) const DIR_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// how many errors the error log keeps
+pub(crate) const MAX_ERROR_LOG: usize = 50;
+
+// whether the info pane is shown, which is remembered for the session
+#[derive(Default, Clone, Copy, PartialEq)]
+pub(crate) enum InfoPaneMode {
+    #[default]
+    Auto, // shown while a file is selected
+    Off,
+    On, // shown for directories too
+}
+
+impl InfoPaneMode {
+    // cycles to the next mode for the toggle key
+    pub(crate) fn next(self) -> Self {
+        match self {
+            InfoPaneMode::Auto => InfoPaneMode::Off,
+            InfoPaneMode::Off => InfoPaneMode::On,
+            InfoPaneMode::On => InfoPaneMode::Auto,
+        }
+    }
+
+    // the name the mode is saved under in the session file
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            InfoPaneMode::Auto => "auto",
+            InfoPaneMode::Off => "off",
+            InfoPaneMode::On => "on",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [InfoPaneMode::Auto, InfoPaneMode::Off, InfoPaneMode::On].into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+// controls what happens when a sound reaches its end on its own
+#[derive(Default, Clone, Copy, PartialEq)]
+pub(crate) enum AdvanceMode {
+    #[default]
+    Off, // playback just stops
+    StopAtEnd, // the next file in the directory plays, stopping after the last one
+    Wrap, // the next file in the directory plays, wrapping around to the first one
+    RepeatOne, // the file that finished plays again
+}
+
+impl AdvanceMode {
+    // cycles to the next mode for the toggle key
+    pub(crate) fn next(self) -> Self {
+        match self {
+            AdvanceMode::Off => AdvanceMode::StopAtEnd,
+            AdvanceMode::StopAtEnd => AdvanceMode::Wrap,
+            AdvanceMode::Wrap => AdvanceMode::RepeatOne,
+            AdvanceMode::RepeatOne => AdvanceMode::Off,
+        }
+    }
+
+    // the name the mode is saved under in the session file
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            AdvanceMode::Off => "off",
+            AdvanceMode::StopAtEnd => "stop_at_end",
+            AdvanceMode::Wrap => "wrap",
+            AdvanceMode::RepeatOne => "repeat_one",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [AdvanceMode::Off, AdvanceMode::StopAtEnd, AdvanceMode::Wrap, AdvanceMode::RepeatOne].into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+// the order the file list is shown in. directories always come before files.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub(crate) enum SortMode {
+    #[default]
+    NameAscending,
+    NameDescending,
+    ModifiedNewest,
+    SizeLargest,
+    DurationLongest, // files that haven't been probed for a duration yet sort last
+    RatingHighest, // files that haven't been rated sort last
+}
+
+impl SortMode {
+    // cycles to the next mode for the sort key
+    pub(crate) fn next(self) -> Self {
+        match self {
+            SortMode::NameAscending => SortMode::NameDescending,
+            SortMode::NameDescending => SortMode::ModifiedNewest,
+            SortMode::ModifiedNewest => SortMode::SizeLargest,
+            SortMode::SizeLargest => SortMode::DurationLongest,
+            SortMode::DurationLongest => SortMode::RatingHighest,
+            SortMode::RatingHighest => SortMode::NameAscending,
+        }
+    }
+
+    // the short name shown in the file list title
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            SortMode::NameAscending => "name↑",
+            SortMode::NameDescending => "name↓",
+            SortMode::ModifiedNewest => "mtime↓",
+            SortMode::SizeLargest => "size↓",
+            SortMode::DurationLongest => "duration↓",
+            SortMode::RatingHighest => "rating↓",
+        }
+    }
+
+    // the name the mode is saved under in the session file
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            SortMode::NameAscending => "name_ascending",
+            SortMode::NameDescending => "name_descending",
+            SortMode::ModifiedNewest => "modified_newest",
+            SortMode::SizeLargest => "size_largest",
+            SortMode::DurationLongest => "duration_longest",
+            SortMode::RatingHighest => "rating_highest",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [SortMode::NameAscending, SortMode::NameDescending, SortMode::ModifiedNewest, SortMode::SizeLargest, SortMode::DurationLongest, SortMode::RatingHighest]
+            .into_iter()
+            .find(|mode| mode.name() == name)
+    }
+
+    // orders two entries for this mode, falling back to the name to break ties
+    pub(crate) fn compare(self, a: &BrowserEntry, b: &BrowserEntry) -> std::cmp::Ordering {
+        let by_name = || a.display_name().to_lowercase().cmp(&b.display_name().to_lowercase());
+        match self {
+            SortMode::NameAscending => by_name(),
+            SortMode::NameDescending => by_name().reverse(),
+            SortMode::ModifiedNewest => b.modified.cmp(&a.modified).then_with(by_name),
+            SortMode::SizeLargest => b.size.cmp(&a.size).then_with(by_name),
+            SortMode::DurationLongest => b.duration.cmp(&a.duration).then_with(by_name),
+            SortMode::RatingHighest => b.rating.cmp(&a.rating).then_with(by_name),
+        }
+    }
+}
+
+// what an entry in the file list is, in the order they're listed
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum EntryKind {
+    ParentDir,
+    Dir,
+    Playlist,
+    File,
+}
+
+// a directory or file in the list along with the metadata it can be sorted by
+pub(crate) struct BrowserEntry {
+    pub(crate) name: OsString, // the file name, or the path below the listed directory in the recursive view
+    pub(crate) path: PathBuf,
+    pub(crate) kind: EntryKind,
+    pub(crate) modified: Option<std::time::SystemTime>,
+    pub(crate) size: u64,
+    pub(crate) duration: Option<std::time::Duration>, // only known once the file has been probed
+    pub(crate) probe_failed: bool, // set when the content of a file with an audio extension didn't probe as audio
+    pub(crate) rating: Option<u8>, // the stars the file was rated, filled in from the ratings once it's listed
+}
+
+impl BrowserEntry {
+    // reads the metadata for the entry at `path`, leaving it empty if that fails
+    pub(crate) fn new(name: OsString, path: PathBuf, kind: EntryKind) -> Self {
+        let metadata = fs::metadata(&path).ok();
+        BrowserEntry {
+            name,
+            path,
+            kind,
+            modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+            size: metadata.map(|m| m.len()).unwrap_or(0),
+            duration: None,
+            probe_failed: false,
+            rating: None,
+        }
+    }
+
+    // the name to show and match against, with anything that isn't valid UTF-8 replaced
+    pub(crate) fn display_name(&self) -> std::borrow::Cow<'_, str> {
+        self.name.to_string_lossy()
+    }
+
+    // playlists aren't sound files either, so they go along with the directories
+    pub(crate) fn is_dir(&self) -> bool {
+        self.kind != EntryKind::File
+    }
+
+    // dotfiles and dot directories are hidden unless the user asks to see them
+    pub(crate) fn is_hidden(&self) -> bool {
+        self.kind != EntryKind::ParentDir && self.display_name().starts_with('.')
+    }
+}
+
+// a probe for the durations of the listed files running on a background thread,
+// which sends what it finds down the event channel
+pub(crate) struct DurationProbe {
+    pub(crate) id: u64, // tells this probe's finish apart from that of one it replaced
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the thread to stop probing early
+    pub(crate) found: std::collections::HashMap<PathBuf, std::time::Duration>, // durations received but not filled in yet
+    pub(crate) done: std::sync::Arc<std::sync::atomic::AtomicUsize>, // how many files the thread has gone through
+    pub(crate) total: usize, // how many files the thread was given
+}
+
+impl Drop for DurationProbe {
+    fn drop(&mut self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// a sound decoded ahead of time so playing it doesn't read the file again
+pub(crate) struct DecodedSound {
+    pub(crate) path: PathBuf,
+    pub(crate) stamp: FileStamp, // the file as it was when it was decoded
+    pub(crate) sound_data: StaticSoundData,
+}
+
+// the waveform of a file being decoded on a background thread
+pub(crate) struct WaveformJob {
+    pub(crate) path: PathBuf,
+    pub(crate) stamp: Option<FileStamp>, // the file as it was when decoding started
+    pub(crate) receiver: std::sync::mpsc::Receiver<Option<DecodedWaveform>>, // None if the file can't be decoded
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the thread to stop decoding early
+}
+
+impl Drop for WaveformJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// counting up the real length of the playing file on a background thread, when it
+// could only be estimated to start with
+pub(crate) struct DurationCount {
+    pub(crate) path: PathBuf,
+    pub(crate) receiver: std::sync::mpsc::Receiver<Option<std::time::Duration>>, // None if the packets couldn't be read
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the thread to stop counting early
+}
+
+impl Drop for DurationCount {
+    fn drop(&mut self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// measuring the loudness of the selected file on a background thread
+pub(crate) struct LoudnessJob {
+    pub(crate) path: PathBuf,
+    pub(crate) stamp: Option<FileStamp>, // the file as it was when measuring started
+    pub(crate) receiver: std::sync::mpsc::Receiver<Result<Loudness, String>>,
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the thread to stop decoding early
+}
+
+impl Drop for LoudnessJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// reading the cover art of the selected file on a background thread
+pub(crate) struct CoverJob {
+    pub(crate) path: PathBuf,
+    pub(crate) receiver: std::sync::mpsc::Receiver<Option<CoverArt>>, // None if the file has no cover art
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the thread its result isn't wanted
+}
+
+impl Drop for CoverJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// a directory that was browsed and the entry that was selected in it
+pub(crate) struct HistoryEntry {
+    pub(crate) dir: PathBuf,
+    pub(crate) selected: Option<(EntryKind, OsString)>, // from `AppState::selected_entry_key`
+}
+
+// the directories browsed before and after the current one, for going back and forward
+#[derive(Default)]
+pub(crate) struct DirHistory {
+    pub(crate) back: Vec<HistoryEntry>,
+    pub(crate) forward: Vec<HistoryEntry>,
+}
+
+impl DirHistory {
+    // records leaving `current` for a new directory, which drops the forward history
+    pub(crate) fn visit(&mut self, current: HistoryEntry) {
+        DirHistory::push(&mut self.back, current);
+        self.forward.clear();
+    }
+
+    pub(crate) fn go_back(&mut self, current: HistoryEntry) -> Option<HistoryEntry> {
+        let entry = self.back.pop()?;
+        DirHistory::push(&mut self.forward, current);
+        Some(entry)
+    }
+
+    pub(crate) fn go_forward(&mut self, current: HistoryEntry) -> Option<HistoryEntry> {
+        let entry = self.forward.pop()?;
+        DirHistory::push(&mut self.back, current);
+        Some(entry)
+    }
+
+    // pushes onto one of the stacks, only keeping the latest of the same directory
+    // twice in a row and dropping the oldest entries past the cap
+    pub(crate) fn push(stack: &mut Vec<HistoryEntry>, entry: HistoryEntry) {
+        if let Some(last) = stack.last_mut() {
+            if last.dir == entry.dir {
+                *last = entry;
+                return;
+            }
+        }
+        stack.push(entry);
+        if stack.len() > MAX_DIR_HISTORY {
+            stack.remove(0);
+        }
+    }
+}
+
+// which list the navigation keys act on
+#[derive(Default, Clone, Copy, PartialEq)]
+pub(crate) enum Focus {
+    #[default]
+    Files,
+    Queue,
+    Tree, // the directory tree, when the terminal is wide enough to show it
+}
+
+// a drag along the progress gauge with the mouse, seeking as it goes
+pub(crate) struct Scrub {
+    pub(crate) area: Rect, // the gauge as it was drawn when the drag started
+    pub(crate) last_seek: std::time::Instant,
+    pub(crate) pending: Option<f64>, // where the drag got to since the last seek, when it was too soon to seek again
+}
+
+// an ordered run of files being played back to back with the play-all key
+pub(crate) struct PlayAllRun {
+    pub(crate) paths: Vec<PathBuf>,
+    pub(crate) next: usize, // index into `paths` of the file to play after the current one
+}
+
+// the questions that can be asked of the user on the bottom line
+pub(crate) enum Prompt {
+    KeepPlayAll, // asked when changing directories during a play-all run
+    Delete { paths: Vec<PathBuf>, permanent: bool }, // asked before deleting files, or moving them to the trash
+    Collision(PathBuf), // asked when a file being copied or moved is already at the destination
+    OverwritePlaylist(PathBuf, Vec<PathBuf>), // asked before saving the files over a playlist that's already there
+    OverwriteTrim(PathBuf, TrimRegion), // asked before trimming a file over a trimmed wav that's already there
+    ConvertFormat(PathBuf), // asked which format to convert a file to
+    OverwriteConvert(PathBuf, ExportFormat), // asked before converting a file over one that's already there
+    QuitWithJobs(usize), // asked before quitting while this many jobs are writing files
+}
+
+impl Prompt {
+    pub(crate) fn message(&self) -> String {
+        match self {
+            Prompt::KeepPlayAll => "Keep playing all files from the previous directory? (y/n)".to_string(),
+            Prompt::Collision(dest) => {
+                let name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                format!("{} is already there: (s)kip, (o)verwrite or (r)ename?", name)
+            },
+            Prompt::OverwritePlaylist(path, _) => {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                format!("{} already exists, overwrite it? (y/n)", name)
+            },
+            Prompt::OverwriteTrim(path, _) => {
+                let dest = trim_dest(path);
+                let name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                format!("{} already exists, overwrite it? (y/n)", name)
+            },
+            Prompt::ConvertFormat(path) => {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                format!("Convert {} to (w)av or (f)lac?", name)
+            },
+            Prompt::OverwriteConvert(path, format) => {
+                let dest = convert_dest(path, *format);
+                let name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                format!("{} already exists, overwrite it? (y/n)", name)
+            },
+            Prompt::QuitWithJobs(jobs) => {
+                let what = if *jobs == 1 { "1 job".to_string() } else { format!("{} jobs", jobs) };
+                format!("{} running, quit anyway? (y/n, or q again)", what)
+            },
+            Prompt::Delete { paths, permanent } => {
+                let what = match paths.as_slice() {
+                    [path] => path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                    _ => format!("{} files", paths.len()),
+                };
+                if *permanent {
+                    format!("Delete {}? (y/n)", what)
+                } else {
+                    format!("Move {} to the trash? (y/n)", what)
+                }
+            },
+        }
+    }
+}
+
+// the kinds of text the user can be asked to type on the bottom line
+#[derive(PartialEq)]
+pub(crate) enum TextInputKind {
+    StartOffset, // a mm:ss time to start playing the selected file from
+    Filter, // a substring that entries in the file list have to contain
+    ChangeDir, // a path to a directory to browse, which can be relative or start with '~'
+    ExportMarked, // a file to write the list of marked or listed files to
+    SavePlaylist, // a playlist file to write the queue or the marked files to
+    TrimRange, // a start and end like '1:05-1:20' of the selected file to trim out to a wav
+    CopyTo, // a directory to copy the selected or marked files to
+    MoveTo, // a directory to move the selected or marked files to
+}
+
+impl TextInputKind {
+    pub(crate) fn label(&self) -> &str {
+        match self {
+            TextInputKind::StartOffset => "Start at (mm:ss)",
+            TextInputKind::Filter => "Filter",
+            TextInputKind::ChangeDir => "Go to dir",
+            TextInputKind::ExportMarked => "Export files to (.txt or .csv)",
+            TextInputKind::SavePlaylist => "Save playlist as",
+            TextInputKind::TrimRange => "Trim from-to (mm:ss-mm:ss)",
+            TextInputKind::CopyTo => "Copy to dir",
+            TextInputKind::MoveTo => "Move to dir",
+        }
+    }
+}
+
+// text being typed on the bottom line, submitted with enter and cancelled with escape
+pub(crate) struct TextInput {
+    pub(crate) kind: TextInputKind,
+    pub(crate) text: String,
+}
+
+impl TextInput {
+    pub(crate) fn new(kind: TextInputKind) -> Self {
+        Self { kind, text: String::new() }
+    }
+}
+
+// a copy or move of files running on a background thread
+pub(crate) struct FileOperation {
+    pub(crate) receiver: std::sync::mpsc::Receiver<FileOpMessage>,
+    pub(crate) answers: std::sync::mpsc::Sender<CollisionChoice>, // what to do about a file in the way
+    pub(crate) dest_dir: PathBuf, // canonical, to compare with the current directory
+    pub(crate) moving: bool,
+    pub(crate) progress: Option<FileOpProgress>,
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the thread to stop and remove the file it was partway through
+}
+
+// a region of a file being trimmed out to a wav, or a file being converted, on a
+// background thread
+pub(crate) struct ExportJob {
+    pub(crate) dest: PathBuf,
+    pub(crate) converting: bool, // a whole file being converted rather than a region trimmed out
+    pub(crate) receiver: std::sync::mpsc::Receiver<Result<std::time::Duration, String>>, // the length written, or why it failed
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the thread to stop and clean up
+    pub(crate) progress: std::sync::Arc<std::sync::atomic::AtomicUsize>, // how far along it is, in percent
+}
+
+impl Drop for ExportJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// an error that was shown on the bottom line, kept so it can be read later
+pub(crate) struct LoggedError {
+    pub(crate) time: chrono::DateTime<chrono::Local>,
+    pub(crate) message: String,
+}
+
+// a ranked entry in the fuzzy finder
+pub(crate) struct FinderResult {
+    pub(crate) entry: usize, // an index into `AppState::entries`
+    pub(crate) name: String, // the name without the directory prefix
+    pub(crate) is_dir: bool,
+    pub(crate) score: i64,
+    pub(crate) positions: Vec<usize>, // the chars of `name` that matched the query
+}
+
+// the state of the fuzzy finder overlay
+#[derive(Default)]
+pub(crate) struct FuzzyFinder {
+    pub(crate) query: String,
+    pub(crate) results: Vec<FinderResult>, // best matches first
+    pub(crate) list_state: tui::widgets::ListState,
+}
+
+impl FuzzyFinder {
+    pub(crate) fn selected_entry(&self) -> Option<usize> {
+        let sel_index = self.list_state.selected()?;
+        self.results.get(sel_index).map(|result| result.entry)
+    }
+
+    // moves the selection through the shown results, stopping at either end
+    pub(crate) fn move_selection(&mut self, delta: isize) {
+        let shown = self.results.len().min(FINDER_MAX_RESULTS);
+        if shown == 0 {
+            return;
+        }
+        let current = self.list_state.selected().unwrap_or(0) as isize;
+        let new_index = (current + delta).clamp(0, shown as isize - 1);
+        self.list_state.select(Some(new_index as usize));
+    }
+}
+
+// the groups of listed files with the same contents, shown in an overlay
+pub(crate) struct DuplicateList {
+    pub(crate) groups: Vec<Vec<PathBuf>>, // each sorted, with at least two files
+    pub(crate) list_state: ListState, // selects a file, counting through the files of every group in order
+    pub(crate) deep: bool, // compared by their decoded sound instead of their bytes
+    pub(crate) scan: Option<DuplicateScan>, // the hashing, while it's still going
+}
+
+impl DuplicateList {
+    // the files of every group in order, along with the index of their group
+    pub(crate) fn rows(&self) -> impl Iterator<Item = (usize, &PathBuf)> {
+        self.groups.iter()
+            .enumerate()
+            .flat_map(|(group, files)| files.iter().map(move |path| (group, path)))
+    }
+
+    pub(crate) fn row_count(&self) -> usize {
+        self.groups.iter().map(|files| files.len()).sum()
+    }
+
+    pub(crate) fn selected_path(&self) -> Option<PathBuf> {
+        let row = self.list_state.selected()?;
+        self.rows().nth(row).map(|(_, path)| path.clone())
+    }
+
+    // takes out files that were deleted, along with the groups that leaves with
+    // only one file
+    pub(crate) fn forget(&mut self, deleted: &[PathBuf]) {
+        for files in &mut self.groups {
+            files.retain(|path| !deleted.contains(path));
+        }
+        self.groups.retain(|files| files.len() > 1);
+        let count = self.row_count();
+        let selected = self.list_state.selected();
+        self.list_state.select(if count == 0 { None } else { Some(selected.unwrap_or(0).min(count - 1)) });
+    }
+}
+
+// hashing the listed files on background threads to find the ones that are the same
+pub(crate) struct DuplicateScan {
+    pub(crate) receiver: std::sync::mpsc::Receiver<Option<Vec<Vec<PathBuf>>>>, // None if it was cancelled
+    pub(crate) cancel: std::sync::Arc<std::sync::atomic::AtomicBool>, // tells the threads to stop hashing
+    pub(crate) total: std::sync::Arc<std::sync::atomic::AtomicUsize>, // how many files need hashing, once it's known
+    pub(crate) done: std::sync::Arc<std::sync::atomic::AtomicUsize>, // how many have been hashed
+}
+
+impl Drop for DuplicateScan {
+    fn drop(&mut self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// the output device picker overlay. the first row is the default device and the
+// rest are the devices by name.
+pub(crate) struct DevicePicker {
+    pub(crate) names: Vec<String>,
+    pub(crate) default_name: Option<String>, // what the default device is called right now
+    pub(crate) list_state: tui::widgets::ListState,
+}
+
+impl DevicePicker {
+    // lists the output devices with `current`, the device in use, selected
+    pub(crate) fn new(current: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let names = output_device_names()?;
+        let selected = current
+            .and_then(|current| names.iter().position(|name| name.eq_ignore_ascii_case(current)))
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        let mut list_state = ListState::default();
+        list_state.select(Some(selected));
+        Ok(DevicePicker { names, default_name: default_device_name(), list_state })
+    }
+
+    // the device name for the selected row, None being the default device
+    pub(crate) fn selected_device(&self) -> Option<String> {
+        match self.list_state.selected() {
+            Some(index) if index > 0 => self.names.get(index - 1).cloned(),
+            _ => None,
+        }
+    }
+}
+
+// the overlay asking which audio track of a file to play, holding on to how it
+// was going to be played until one is picked
+pub(crate) struct TrackPicker {
+    pub(crate) path: PathBuf,
+    pub(crate) tracks: Vec<AudioTrackInfo>,
+    pub(crate) options: PlayOptions,
+    pub(crate) list_state: tui::widgets::ListState,
+}
+
+impl TrackPicker {
+    pub(crate) fn new(path: PathBuf, tracks: Vec<AudioTrackInfo>, options: PlayOptions) -> Self {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+        TrackPicker { path, tracks, options, list_state }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct AppState {
+    pub(crate) needs_file_list_update: bool,
+    pub(crate) current_directory_path: PathBuf,
+    pub(crate) last_error_msg: String,
+    pub(crate) error_recorded: bool, // set once `last_error_msg` is in the error log
+    pub(crate) error_log: std::collections::VecDeque<LoggedError>, // the latest errors, oldest first
+    pub(crate) error_log_scroll: Option<u16>, // how far the error log overlay is scrolled, when it's open
+
+    pub(crate) entries: Vec<BrowserEntry>, // the parent directory, then the directories, then the files
+    pub(crate) sort_mode: SortMode,
+    pub(crate) theme_kind: theme::ThemeKind,
+    pub(crate) info_pane_mode: InfoPaneMode,
+    pub(crate) info_pane_width: u16,
+    pub(crate) keymap: keymap::Keymap,
+    pub(crate) pending_count: Option<usize>, // a count typed for the next motion, like the 5 in '5j'
+    pub(crate) pending_g: bool, // set after the first 'g' of 'gg'
+    pub(crate) show_hidden: bool, // when set, entries starting with a '.' are listed too
+    pub(crate) listed_extensions: Option<Vec<String>>, // the extensions of the files listed, when they aren't `SUPPORTED_EXTS`
+    pub(crate) probe_content: bool, // when set, files of any extension are listed if their content probes as audio
+    pub(crate) recursive: bool, // when set, the files in every subdirectory are listed by their relative path
+    pub(crate) scan: Option<RecursiveScan>, // the background scan filling in the recursive file list
+    pub(crate) scan_truncated: bool, // set when the last recursive scan stopped at `scan_options.max_files`
+    pub(crate) scan_options: ScanOptions,
+    pub(crate) duration_probe: Option<DurationProbe>, // the background probe filling in file durations
+    pub(crate) probe_count: u64, // how many duration probes have been started, for their ids
+    pub(crate) duration_cache: std::collections::HashMap<PathBuf, (FileStamp, std::time::Duration)>, // probed durations by full path, saved in the data directory
+    pub(crate) duration_cache_changed: bool, // set when the duration cache has durations that aren't saved yet
+    pub(crate) covers: std::collections::HashMap<PathBuf, Option<CoverArt>>, // cover art by full path, None when the file has none
+    pub(crate) cover_job: Option<CoverJob>, // the cover art being read for the selected file
+    pub(crate) loudness: std::collections::HashMap<PathBuf, (FileStamp, Loudness)>, // measured loudness by full path
+    pub(crate) loudness_job: Option<LoudnessJob>, // the loudness of the selected file being measured
+    pub(crate) duration_count: Option<DurationCount>, // the length of the playing file being counted up
+    pub(crate) waveforms: std::collections::HashMap<PathBuf, Option<Vec<(f32, f32)>>>, // decoded waveforms by full path, None when the file couldn't be decoded
+    pub(crate) waveform_job: Option<WaveformJob>, // the waveform of the selected file being decoded
+    pub(crate) file_list_state: tui::widgets::ListState, // the selection is an index into `visible_entries`
+    pub(crate) filter: String, // only entries containing this text, ignoring case, are listed
+    pub(crate) ext_filter: Option<String>, // only files with this extension are listed, when set
+    pub(crate) min_rating: Option<u8>, // only files rated at least this many stars are listed, when set
+    pub(crate) ratings: Ratings,
+    pub(crate) rating_mode: bool, // when set, the digits rate the selected file instead of typing a count
+    pub(crate) visible_entries: Vec<usize>, // the directories and then files that pass the filter, indexed together
+    pub(crate) select_file_info: SoundFileCodecData,
+    pub(crate) select_file_metadata: SoundFileMetadata,
+    pub(crate) info_pending: Option<PathBuf>, // the selected file while the info worker is still probing it
+    pub(crate) info_requests: Option<std::sync::mpsc::Sender<PathBuf>>, // sends files to the info worker, once it's started
+    pub(crate) file_info_cache: std::collections::HashMap<PathBuf, (FileStamp, Option<(SoundFileCodecData, SoundFileMetadata)>)>, // probed files by full path, None when they couldn't be probed
+    pub(crate) decoded_sounds: Vec<DecodedSound>, // the most recently decoded last
+
+    pub(crate) sound_state: SoundState,
+    pub(crate) playing_file_path: Option<PathBuf>, // the full path of the file last started
+    pub(crate) advance_mode: AdvanceMode,
+    pub(crate) shuffle: bool, // auto-advance picks files in a random order when set
+    pub(crate) shuffle_remaining: Option<Vec<usize>>, // indices into `entries` of the files left to play this shuffle round
+    pub(crate) play_all: Option<PlayAllRun>,
+    pub(crate) prompt: Option<Prompt>, // a question waiting on a y/n answer from the user
+    pub(crate) text_input: Option<TextInput>, // text the user is in the middle of typing
+    pub(crate) preview_due: Option<std::time::Instant>, // when to preview the selected file after moving to it
+    pub(crate) list_viewport_height: usize, // how many entries the file list showed on the last draw
+    pub(crate) list_offset: usize, // the index into `visible_entries` of the top row of the file list
+    pub(crate) list_area: Rect, // where the rows of the file list were drawn, for mouse clicks
+    pub(crate) gauge_area: Option<Rect>, // where the progress gauge was drawn, if it was
+    pub(crate) scrub: Option<Scrub>, // the drag along the progress gauge going on, if there is one
+    pub(crate) last_click: Option<(std::time::Instant, usize)>, // when and on which row of the list the last click was
+    pub(crate) finder: Option<FuzzyFinder>, // the fuzzy finder overlay, when it's open
+    pub(crate) help_scroll: Option<u16>, // how far the help overlay is scrolled, when it's open
+    pub(crate) history: DirHistory,
+    pub(crate) bookmarks: Vec<PathBuf>, // bookmarked directories, saved in the data directory
+    pub(crate) bookmark_list: Option<tui::widgets::ListState>, // the bookmarks overlay, when it's open
+    pub(crate) recent: Vec<RecentPlay>, // files played lately, newest first, saved in the data directory
+    pub(crate) recent_list: Option<tui::widgets::ListState>, // the recently played overlay, when it's open
+    pub(crate) duplicates: Option<DuplicateList>, // the duplicates overlay, when it's open
+    pub(crate) device_picker: Option<DevicePicker>, // the output device overlay, when it's open
+    pub(crate) track_picker: Option<TrackPicker>, // the audio track overlay, when a file with several is played
+    pub(crate) chosen_tracks: std::collections::HashMap<PathBuf, u32>, // the track picked for each file with several, for this run
+
+    pub(crate) focus: Focus,
+    pub(crate) tree_layout: bool, // when set, wide terminals show a directory tree beside the files instead of listing the directories
+    pub(crate) tree_shown: bool, // set while the terminal is wide enough for the tree and it's drawn
+    pub(crate) tree: Option<DirTree>, // made the first time the tree is shown
+    pub(crate) tree_area: Rect, // where the rows of the tree were drawn, for mouse clicks
+    pub(crate) queue: Vec<PathBuf>, // full paths so the queue survives directory changes
+    pub(crate) marked: std::collections::HashSet<PathBuf>, // full paths of the marked files, kept for the session
+    pub(crate) permanent_delete: bool, // when set, deleted files are removed instead of moved to the trash
+    pub(crate) file_operation: Option<FileOperation>, // files being copied or moved in the background
+    pub(crate) export_job: Option<ExportJob>, // a file being trimmed or converted in the background
+    pub(crate) quit_confirmed: bool, // set once quitting is confirmed with jobs still running
+    pub(crate) trim_bit_depth: u16, // the bits per sample of trimmed wavs from lossy files, 16 or 24
+    pub(crate) convert_bit_depth: u16, // the bits per sample of files converted from lossy ones, 16 or 24
+    pub(crate) last_info_msg: String, // a confirmation shown on the bottom line until the next key, like the errors
+    pub(crate) clipboard: Option<arboard::Clipboard>, // kept open since on X11 the copied text goes away with it
+    pub(crate) open_command: Option<Vec<String>>, // opens the selected file, instead of the platform's default opener
+    pub(crate) open_dir_command: Option<Vec<String>>, // opens the current directory, instead of the platform's default opener
+    pub(crate) listed_dir_modified: Option<std::time::SystemTime>, // when the directory had last changed as of listing it
+    pub(crate) dir_changed: bool, // set once the directory changes on disk after it was listed
+    pub(crate) last_dir_check: Option<std::time::Instant>,
+    pub(crate) dir_watch: Option<DirWatch>, // notices changes to the listed directory, if it could be watched
+    pub(crate) external_errors: Option<(std::sync::mpsc::Sender<String>, std::sync::mpsc::Receiver<String>)>, // failures of launched programs
+    pub(crate) queue_list_state: tui::widgets::ListState,
+    pub(crate) event_sender: Option<std::sync::mpsc::Sender<AppEvent>>, // where background work reports back to the main loop
+}
+
+impl AppState {
+    pub(crate) fn clear_error(&mut self) {
+        self.last_error_msg.clear();
+        self.last_info_msg.clear();
+        self.error_recorded = false;
+    }
+
+    // whether anything is changing on screen without a key press, so the main loop
+    // needs to wake up at the tick rate to redraw it
+    pub(crate) fn has_background_work(&self) -> bool {
+        // the progress bar moving, or the finished message waiting to clear
+        self.sound_state.is_audible()
+            || self.sound_state.show_finished()
+            // a preview waiting for the selection to settle
+            || self.preview_due.is_some()
+            // background jobs filling in the list and the info pane
+            || self.scan.is_some()
+            || self.duration_probe.is_some()
+            || self.waveform_job.is_some()
+            || self.cover_job.is_some()
+            || self.loudness_job.is_some()
+            || self.duration_count.is_some()
+            || matches!(&self.duplicates, Some(list) if list.scan.is_some())
+            // copies, moves and exports showing their progress
+            || self.file_operation.is_some()
+            || self.export_job.is_some()
+            // directory changes waiting to be reloaded together
+            || matches!(&self.dir_watch, Some(watch) if watch.last_event.is_some())
+    }
+
+    // adds the error being shown to the error log, unless it's already there
+    pub(crate) fn record_error(&mut self) {
+        if self.last_error_msg.is_empty() {
+            return;
+        }
+        if self.error_recorded && matches!(self.error_log.back(), Some(logged) if logged.message == self.last_error_msg) {
+            return;
+        }
+        if self.error_log.len() >= MAX_ERROR_LOG {
+            self.error_log.pop_front();
+        }
+        self.error_log.push_back(LoggedError {
+            time: chrono::Local::now(),
+            message: self.last_error_msg.clone(),
+        });
+        self.error_recorded = true;
+    }
+
+    pub(crate) fn set_current_directory(&mut self, dir: &Path) {
+        self.current_directory_path = dir.to_path_buf();
+        self.filter.clear();
+        self.ext_filter = None;
+        self.needs_file_list_update = true;
+    }
+
+    // lists the directory again, keeping the selection on the same entry if it's
+    // still there and otherwise on the same row. the selected file's info and
+    // waveform are read again too since it may have been rendered over. playback
+    // carries on.
+    pub(crate) fn refresh_listing(&mut self) {
+        let previous_key = self.selected_entry_key();
+        let previous_row = self.file_list_state.selected().unwrap_or(0);
+        if let Some(path) = self.selected_file_path() {
+            self.waveforms.remove(&path);
+        }
+        // files may have been replaced or renamed over, so everything is probed again
+        self.file_info_cache.clear();
+        self.needs_file_list_update = true;
+        self.update_file_names();
+        // directories may have been added or removed too
+        if let Some(tree) = &mut self.tree {
+            tree.reload(self.show_hidden);
+        }
+        match self.view_index_of_key(previous_key) {
+            Some(view_index) => self.select_list_item(view_index),
+            None => self.select_row_clamped(previous_row),
+        }
+    }
+
+    // notices when files were added to or removed from the directory since it was
+    // listed, checking at most once every DIR_CHECK_INTERVAL. only the directory
+    // itself is checked, so changes deep in a recursive listing go unnoticed.
+    pub(crate) fn check_directory_changed(&mut self) {
+        if self.dir_watch.is_some() || self.dir_changed || matches!(self.last_dir_check, Some(at) if at.elapsed() < DIR_CHECK_INTERVAL) {
+            return;
+        }
+        self.last_dir_check = Some(std::time::Instant::now());
+        let modified = fs::metadata(&self.current_directory_path).and_then(|m| m.modified()).ok();
+        self.dir_changed = modified.is_some() && modified != self.listed_dir_modified;
+    }
+
+    // refreshes the list once the watched directory has been quiet for
+    // DIR_WATCH_DEBOUNCE, so a big export only lists the directory again at the end.
+    // waits while an overlay or prompt that points into the list is open.
+    pub(crate) fn update_dir_watch(&mut self) {
+        let watch = match &mut self.dir_watch {
+            Some(watch) => watch,
+            None => return,
+        };
+        let quiet = matches!(watch.last_event, Some(at) if at.elapsed() >= DIR_WATCH_DEBOUNCE);
+        if quiet && self.finder.is_none() && self.prompt.is_none() && self.text_input.is_none() {
+            watch.last_event = None;
+            self.refresh_listing();
+        }
+    }
+
+    // notes a change the watch saw in `dir`, which is refreshed once things settle down
+    pub(crate) fn note_dir_change(&mut self, dir: &Path) {
+        if let Some(watch) = &mut self.dir_watch {
+            if watch.path == dir {
+                watch.last_event = Some(std::time::Instant::now());
+            }
+        }
+    }
+
+    // moves to a new directory, remembering the one being left for going back
+    pub(crate) fn enter_directory(&mut self, dir: &Path) {
+        if dir != self.current_directory_path {
+            let current = self.history_entry();
+            self.history.visit(current);
+        }
+        self.change_directory(dir);
+    }
+
+    // returns to the directory visited before the current one
+    pub(crate) fn go_back(&mut self) {
+        let current = self.history_entry();
+        if let Some(entry) = self.history.go_back(current) {
+            self.restore_history_entry(entry);
+        }
+    }
+
+    // undoes going back
+    pub(crate) fn go_forward(&mut self) {
+        let current = self.history_entry();
+        if let Some(entry) = self.history.go_forward(current) {
+            self.restore_history_entry(entry);
+        }
+    }
+
+    pub(crate) fn history_entry(&self) -> HistoryEntry {
+        HistoryEntry {
+            dir: self.current_directory_path.clone(),
+            selected: self.selected_entry_key(),
+        }
+    }
+
+    pub(crate) fn restore_history_entry(&mut self, entry: HistoryEntry) {
+        self.change_directory(&entry.dir);
+        self.select_entry_key(entry.selected);
+    }
+
+    // moves to a new directory, refreshing the lists and asking whether a
+    // play-all run should carry on
+    pub(crate) fn change_directory(&mut self, dir: &Path) {
+        self.set_current_directory(dir);
+        self.update_file_names();
+        self.select_list_item(0);
+        if self.play_all.is_some() {
+            self.prompt = Some(Prompt::KeepPlayAll);
+        }
+    }
+
+    // bookmarks the current directory and saves the bookmarks
+    pub(crate) fn bookmark_current_directory(&mut self) -> io::Result<()> {
+        let dir = self.current_directory_path.clone();
+        if self.bookmarks.contains(&dir) {
+            return Ok(());
+        }
+        self.bookmarks.push(dir);
+        save_bookmarks(&self.bookmarks)
+    }
+
+    pub(crate) fn open_bookmarks(&mut self) {
+        let mut list_state = ListState::default();
+        if !self.bookmarks.is_empty() {
+            list_state.select(Some(0));
+        }
+        self.bookmark_list = Some(list_state);
+    }
+
+    // goes to the bookmarked directory the same way entering it from the list does
+    pub(crate) fn jump_to_bookmark(&mut self, index: usize) -> Result<(), SpinupError> {
+        let dir = self.bookmarks.get(index)
+            .ok_or_else(|| SpinupError::InvalidRequest("there's no bookmark with that number".to_string()))?;
+        if !dir.is_dir() {
+            return Err(SpinupError::InvalidPath { path: dir.clone(), reason: "no longer exists".to_string() });
+        }
+        let dir = dir.clone();
+        self.bookmark_list = None;
+        self.enter_directory(&dir);
+        Ok(())
+    }
+
+    // puts a file that started playing at the top of the recently played files and
+    // saves them. playing the same file again only updates when it was played.
+    pub(crate) fn remember_played(&mut self, path: &Path) {
+        // the file might be waiting on the track picker instead
+        if self.playing_file_path.as_deref() != Some(path) {
+            return;
+        }
+        let played = RecentPlay { path: path.to_path_buf(), played_at: std::time::SystemTime::now() };
+        match self.recent.first_mut() {
+            Some(last) if last.path == path => *last = played,
+            _ => self.recent.insert(0, played),
+        }
+        self.recent.truncate(RECENT_MAX);
+        if let Err(err) = save_recent(&self.recent) {
+            self.last_error_msg = format!("Recent Error: {}", err);
+        }
+    }
+
+    pub(crate) fn open_recent(&mut self) {
+        let mut list_state = ListState::default();
+        if !self.recent.is_empty() {
+            list_state.select(Some(0));
+        }
+        self.recent_list = Some(list_state);
+    }
+
+    // the recently played file selected in the overlay, as long as it's still there
+    pub(crate) fn selected_recent_path(&self) -> Result<PathBuf, SpinupError> {
+        let nothing_selected = || SpinupError::InvalidRequest("no file is selected".to_string());
+        let index = self.recent_list.as_ref().and_then(|list_state| list_state.selected()).ok_or_else(nothing_selected)?;
+        let path = &self.recent.get(index).ok_or_else(nothing_selected)?.path;
+        if !path.is_file() {
+            return Err(SpinupError::InvalidPath { path: path.clone(), reason: "no longer exists".to_string() });
+        }
+        Ok(path.clone())
+    }
+
+    // goes to the directory of the recently played file selected in the overlay and
+    // selects the file there
+    pub(crate) fn jump_to_recent(&mut self) -> Result<(), SpinupError> {
+        let path = self.selected_recent_path()?;
+        let dir = path.parent()
+            .ok_or_else(|| SpinupError::InvalidPath { path: path.clone(), reason: "isn't in a directory".to_string() })?;
+        self.recent_list = None;
+        self.enter_directory(dir);
+        self.select_file_path(&path);
+        Ok(())
+    }
+
+    // opens the duplicates overlay and starts hashing the listed files in the
+    // background to fill it, by their bytes or with `deep` by their decoded sound
+    pub(crate) fn find_duplicates(&mut self, deep: bool) -> Result<(), SpinupError> {
+        let files: Vec<(PathBuf, u64)> = self.entries.iter()
+            .filter(|entry| entry.kind == EntryKind::File)
+            .map(|entry| (entry.path.clone(), entry.size))
+            .collect();
+        if files.len() < 2 {
+            return Err(SpinupError::InvalidRequest("there aren't enough files listed to compare".to_string()));
+        }
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let total = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (thread_cancel, thread_total, thread_done) = (cancel.clone(), total.clone(), done.clone());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let groups = find_duplicates(files, deep, &thread_cancel, &thread_total, &thread_done);
+            _ = sender.send(groups);
+        });
+        self.duplicates = Some(DuplicateList {
+            groups: vec![],
+            list_state: ListState::default(),
+            deep,
+            scan: Some(DuplicateScan { receiver, cancel, total, done }),
+        });
+        Ok(())
+    }
+
+    // fills the duplicates overlay once the hashing is done
+    pub(crate) fn update_duplicate_scan(&mut self) {
+        let list = match &mut self.duplicates {
+            Some(list) => list,
+            None => return,
+        };
+        let scan = match &list.scan {
+            Some(scan) => scan,
+            None => return,
+        };
+        let groups = match scan.receiver.try_recv() {
+            Ok(groups) => groups,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => None,
+        };
+        list.scan = None;
+        match groups {
+            Some(groups) => {
+                list.list_state.select(if groups.is_empty() { None } else { Some(0) });
+                list.groups = groups;
+            },
+            None => {
+                self.duplicates = None;
+                self.last_error_msg = "Duplicates Error: the hashing stopped unexpectedly".to_string();
+            },
+        }
+    }
+
+    // marks every file in each group of duplicates but the first, ready to be deleted
+    pub(crate) fn mark_duplicate_copies(&mut self) {
+        let list = match &self.duplicates {
+            Some(list) => list,
+            None => return,
+        };
+        let mut count = 0;
+        for files in &list.groups {
+            self.marked.remove(&files[0]);
+            for path in &files[1..] {
+                self.marked.insert(path.clone());
+                count += 1;
+            }
+        }
+        self.last_info_msg = format!("Marked {} copies, keeping the first file of each group", count);
+    }
+
+    // marks or unmarks the file selected in the duplicates overlay and moves down
+    pub(crate) fn toggle_duplicate_mark(&mut self) {
+        let list = match &mut self.duplicates {
+            Some(list) => list,
+            None => return,
+        };
+        let path = match list.selected_path() {
+            Some(path) => path,
+            None => return,
+        };
+        let count = list.row_count();
+        list.list_state.select(list.list_state.selected().map(|i| (i + 1).min(count - 1)));
+        if !self.marked.remove(&path) {
+            self.marked.insert(path);
+        }
+    }
+
+    // asks to delete the marked files from the duplicates overlay
+    pub(crate) fn confirm_delete_duplicates(&mut self) -> Result<(), SpinupError> {
+        if self.marked.is_empty() {
+            return Err(SpinupError::InvalidRequest("mark the copies to delete first".to_string()));
+        }
+        let mut paths: Vec<PathBuf> = self.marked.iter().cloned().collect();
+        paths.sort();
+        self.prompt = Some(Prompt::Delete { paths, permanent: self.permanent_delete });
+        Ok(())
+    }
+
+    // closes the duplicates overlay and selects the file that was selected in it
+    pub(crate) fn jump_to_duplicate(&mut self) {
+        let path = self.duplicates.as_ref().and_then(|list| list.selected_path());
+        self.duplicates = None;
+        if let Some(path) = path {
+            self.select_file_path(&path);
+        }
+    }
+
+    // removes the selected bookmark from the overlay and saves the bookmarks
+    pub(crate) fn remove_selected_bookmark(&mut self) -> io::Result<()> {
+        let list_state = match &mut self.bookmark_list {
+            Some(list_state) => list_state,
+            None => return Ok(()),
+        };
+        let index = match list_state.selected() {
+            Some(index) if index < self.bookmarks.len() => index,
+            _ => return Ok(()),
+        };
+        self.bookmarks.remove(index);
+        list_state.select(if self.bookmarks.is_empty() { None } else { Some(index.min(self.bookmarks.len() - 1)) });
+        save_bookmarks(&self.bookmarks)
+    }
+
+    // shows what's known about the selected file in the info pane. files that
+    // haven't been probed yet are sent to the info worker, and the pane says it's
+    // probing until the result comes back. without the worker the file is probed
+    // right away, returning why it couldn't be.
+    pub(crate) fn update_selected_file_info(&mut self) -> Result<(), SpinupError> {
+        self.select_file_info = SoundFileCodecData::default();
+        self.select_file_metadata = SoundFileMetadata::default();
+        self.info_pending = None;
+
+        // measuring takes a while, so it's given up on once the selection moves on
+        let selected = self.selected_file_path();
+        if matches!(&self.loudness_job, Some(job) if Some(&job.path) != selected.as_ref()) {
+            self.loudness_job = None;
+        }
+
+        // nothing to show for directories
+        if !self.is_file_selected() {
+            return Ok(());
+        }
+
+        let snd_path = match self.selected_file_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        self.request_waveform(&snd_path);
+        self.request_cover_art(&snd_path);
+
+        let cached = self.file_info_cache.get(&snd_path)
+            .filter(|(stamp, _)| Some(*stamp) == file_stamp(&snd_path));
+        if let Some((_, info)) = cached {
+            if let Some((codec_data, metadata)) = info.clone() {
+                self.select_file_info = codec_data;
+                self.select_file_metadata = metadata;
+            }
+            return Ok(());
+        }
+        let requests = match &self.info_requests {
+            Some(requests) => requests.clone(),
+            None => match self.start_info_worker() {
+                Some(requests) => requests,
+                // without the event channel there's nowhere to send the result, so
+                // the file is probed right here
+                None => {
+                    let stamp = file_stamp(&snd_path);
+                    let result = probe_file_info(&snd_path);
+                    let info = result.as_ref().ok().cloned();
+                    self.file_info_probed(snd_path, stamp, info);
+                    return result.map(|_| ());
+                },
+            },
+        };
+        if requests.send(snd_path.clone()).is_ok() {
+            self.info_pending = Some(snd_path);
+        } else {
+            self.info_requests = None;
+        }
+        Ok(())
+    }
+
+    // starts the thread that probes the files selected in the list, returning where
+    // to send it paths. it reports back through the event channel.
+    pub(crate) fn start_info_worker(&mut self) -> Option<std::sync::mpsc::Sender<PathBuf>> {
+        let events = self.event_sender.clone()?;
+        let (sender, receiver) = std::sync::mpsc::channel::<PathBuf>();
+        std::thread::spawn(move || {
+            while let Ok(mut path) = receiver.recv() {
+                // only the latest selection matters when the list was scrolled through
+                while let Ok(newer) = receiver.try_recv() {
+                    path = newer;
+                }
+                let stamp = file_stamp(&path);
+                let info = probe_file_info(&path).ok().map(Box::new);
+                if events.send(AppEvent::FileInfo(path, stamp, info)).is_err() {
+                    return;
+                }
+            }
+        });
+        self.info_requests = Some(sender.clone());
+        Some(sender)
+    }
+
+    // caches what the info worker found out about a file, or None if it couldn't be
+    // probed, and shows it if the file is still the one selected. `stamp` is the file
+    // as it was probed, and without one the result isn't cached.
+    pub(crate) fn file_info_probed(&mut self, path: PathBuf, stamp: Option<FileStamp>, info: Option<(SoundFileCodecData, SoundFileMetadata)>) {
+        // remember the duration so the list can be sorted by it
+        if let Some((codec_data, _)) = &info {
+            if let (Some(duration), false) = (codec_data.duration, codec_data.duration_estimated) {
+                if let Some(entry) = self.entries.iter_mut().find(|e| e.path == path) {
+                    entry.duration = Some(duration);
+                }
+            }
+        }
+        if self.selected_file_path().as_ref() == Some(&path) {
+            self.info_pending = None;
+            if let Some((codec_data, metadata)) = info.clone() {
+                self.select_file_info = codec_data;
+                self.select_file_metadata = metadata;
+            }
+        }
+        if let Some(stamp) = stamp {
+            if self.file_info_cache.len() >= FILE_INFO_CACHE_SIZE {
+                self.file_info_cache.clear();
+            }
+            self.file_info_cache.insert(path, (stamp, info));
+        }
+    }
+
+    // the ReplayGain or R128 gain tagged on the file at `path`, read from the info
+    // pane's cache when the file's been looked at since it last changed
+    pub(crate) fn track_gain_of(&self, path: &Path) -> Option<f64> {
+        let stamp = file_stamp(path);
+        match self.file_info_cache.get(path) {
+            Some((cached_stamp, info)) if Some(*cached_stamp) == stamp => {
+                info.as_ref().and_then(|(_, metadata)| metadata.track_gain())
+            },
+            _ => probe_tags(path).and_then(|metadata| metadata.track_gain()),
+        }
+    }
+
+    // returns the decoded sound for the file at `path` if it's been decoded since it
+    // last changed on disk
+    pub(crate) fn cached_sound(&self, path: &Path) -> Option<StaticSoundData> {
+        let stamp = file_stamp(path)?;
+        self.decoded_sounds.iter()
+            .find(|decoded| decoded.path == path && decoded.stamp == stamp)
+            .map(|decoded| decoded.sound_data.clone())
+    }
+
+    // keeps the decoded sound of a file around, forgetting the oldest one when there
+    // are already DECODED_CACHE_SIZE
+    pub(crate) fn cache_sound(&mut self, path: &Path, stamp: FileStamp, sound_data: StaticSoundData) {
+        self.decoded_sounds.retain(|decoded| decoded.path != path);
+        if self.decoded_sounds.len() >= DECODED_CACHE_SIZE {
+            self.decoded_sounds.remove(0);
+        }
+        self.decoded_sounds.push(DecodedSound { path: path.to_path_buf(), stamp, sound_data });
+    }
+
+    // plays `track_id` of the file the track picker was opened for from now on. what
+    // was decoded of it before was the default track, so that's dropped.
+    pub(crate) fn choose_track(&mut self, path: &Path, track_id: u32) {
+        self.chosen_tracks.insert(path.to_path_buf(), track_id);
+        self.decoded_sounds.retain(|decoded| decoded.path != path);
+        self.waveforms.remove(path);
+        if matches!(&self.waveform_job, Some(job) if job.path == path) {
+            self.waveform_job = None;
+        }
+    }
+
+    // returns the path of the file to play after the current one finishes, taken from
+    // the queue or the play-all run if there is one and otherwise from the auto-advance mode.
+    pub(crate) fn next_file_path(&mut self) -> Option<PathBuf> {
+        if !self.queue.is_empty() {
+            let next_path = self.queue.remove(0);
+            self.clamp_queue_selection();
+            return Some(next_path);
+        }
+        if let Some(run) = &mut self.play_all {
+            if run.next < run.paths.len() {
+                run.next += 1;
+                return Some(run.paths[run.next - 1].clone());
+            }
+            self.play_all = None;
+            return None;
+        }
+        // a repeating file plays again even after leaving its directory
+        if self.advance_mode == AdvanceMode::RepeatOne {
+            return self.playing_file_path.clone();
+        }
+        let next_index = self.next_file_index()?;
+        Some(self.entries[next_index].path.clone())
+    }
+
+    // selects the file in the list if it's listed for the current directory
+    pub(crate) fn select_file_path(&mut self, path: &Path) {
+        if let Some(index) = self.file_entry_index(path) {
+            self.select_entry(index);
+        }
+    }
+
+    // returns the index into `entries` of the file at `path`
+    pub(crate) fn file_entry_index(&self, path: &Path) -> Option<usize> {
+        self.entries.iter().position(|e| e.kind == EntryKind::File && e.path == path)
+    }
+
+    // returns the index into `entries` of the first file, which all come after the
+    // directories. it's the length of `entries` when there are no files.
+    pub(crate) fn first_file_entry(&self) -> usize {
+        self.entries.partition_point(|e| e.is_dir())
+    }
+
+    pub(crate) fn file_count(&self) -> usize {
+        self.entries.len() - self.first_file_entry()
+    }
+
+    // selects the entry (an index into `entries`) if it passes the filter
+    pub(crate) fn select_entry(&mut self, entry: usize) {
+        if let Some(view_index) = self.visible_entries.iter().position(|&e| e == entry) {
+            self.select_list_item(view_index);
+        }
+    }
+
+    // returns the index into `entries` of the entry under the selection
+    pub(crate) fn selected_entry(&self) -> Option<usize> {
+        let sel_index = self.file_list_state.selected()?;
+        self.visible_entries.get(sel_index).copied()
+    }
+
+    // changes the filter and rebuilds the visible entries, keeping the selected
+    // entry selected if it still passes
+    pub(crate) fn set_filter(&mut self, filter: &str) {
+        let previous_entry = self.selected_entry();
+        self.filter = filter.to_string();
+        self.apply_filter();
+        let view_index = previous_entry
+            .and_then(|entry| self.visible_entries.iter().position(|&e| e == entry))
+            .unwrap_or(0);
+        self.select_list_item(view_index);
+    }
+
+    // ranks every directory and file against the fuzzy finder's query
+    pub(crate) fn update_finder_results(&mut self) {
+        let finder = match &mut self.finder {
+            Some(finder) => finder,
+            None => return,
+        };
+        let mut results: Vec<FinderResult> = self.entries.iter()
+            .enumerate()
+            .filter_map(|(entry, e)| {
+                let name = e.display_name();
+                let found = fuzzy::fuzzy_match(&finder.query, &name)?;
+                Some(FinderResult {
+                    entry,
+                    name: name.to_string(),
+                    is_dir: matches!(e.kind, EntryKind::ParentDir | EntryKind::Dir),
+                    score: found.score,
+                    positions: found.positions,
+                })
+            })
+            .collect();
+
+        // the best scores come first, and shorter names win ties
+        results.sort_by(|a, b| b.score.cmp(&a.score).then(a.name.len().cmp(&b.name.len())));
+        finder.results = results;
+        finder.list_state.select(if finder.results.is_empty() { None } else { Some(0) });
+    }
+
+    // rebuilds `visible_entries` from the directory and file names. the parent
+    // directory entry is always kept so the user can still navigate up, unless the
+    // tree is showing the directories instead.
+    pub(crate) fn apply_filter(&mut self) {
+        let tree_shown = self.tree_shown;
+        self.visible_entries = self.entries.iter()
+            .enumerate()
+            .filter(|(_, e)| match e.kind {
+                EntryKind::ParentDir | EntryKind::Dir if tree_shown => false,
+                EntryKind::ParentDir => true,
+                EntryKind::Dir | EntryKind::Playlist => find_case_insensitive(&e.display_name(), &self.filter).is_some(),
+                EntryKind::File => find_case_insensitive(&e.display_name(), &self.filter).is_some() && self.matches_ext_filter(&e.path) && self.matches_rating_filter(e.rating),
+            })
+            .map(|(i, _)| i)
+            .collect();
+    }
+
+    // returns true if the file has the extension the list is limited to, if it is
+    pub(crate) fn matches_ext_filter(&self, path: &Path) -> bool {
+        let ext = match &self.ext_filter {
+            Some(ext) => ext,
+            None => return true,
+        };
+        matches!(path.extension(), Some(e) if e.eq_ignore_ascii_case(ext))
+    }
+
+    // returns true if a file with `rating` is rated highly enough to be listed
+    pub(crate) fn matches_rating_filter(&self, rating: Option<u8>) -> bool {
+        match self.min_rating {
+            Some(min_rating) => rating.unwrap_or(0) >= min_rating,
+            None => true,
+        }
+    }
+
+    // limits the files listed to the ones rated at least one star more than before,
+    // going back to all of them after five
+    pub(crate) fn cycle_rating_filter(&mut self) {
+        self.min_rating = match self.min_rating {
+            None => Some(1),
+            Some(min_rating) if min_rating < MAX_RATING => Some(min_rating + 1),
+            Some(_) => None,
+        };
+        let previous_entry = self.selected_entry();
+        let previous_row = self.file_list_state.selected().unwrap_or(0);
+        self.apply_filter();
+        match previous_entry.and_then(|entry| self.visible_entries.iter().position(|&e| e == entry)) {
+            Some(view_index) => self.select_list_item(view_index),
+            None => self.select_row_clamped(previous_row),
+        }
+        self.schedule_preview();
+    }
+
+    // fills in the ratings of the listed files, reading the ratings file of each
+    // directory they're in the first time when ratings are kept in each directory
+    pub(crate) fn fill_ratings(&mut self) {
+        let dirs: std::collections::HashSet<PathBuf> = self.entries.iter()
+            .filter(|e| e.kind == EntryKind::File)
+            .filter_map(|e| e.path.parent().map(Path::to_path_buf))
+            .collect();
+        for dir in dirs {
+            if let Err(err) = self.ratings.load_dir(&dir) {
+                self.last_error_msg = format!("Rating Error: {}", err);
+            }
+        }
+        for entry in self.entries.iter_mut().filter(|e| e.kind == EntryKind::File) {
+            entry.rating = self.ratings.get(&entry.path);
+        }
+    }
+
+    // rates the marked files, or the selected one, with `rating` stars, or takes
+    // their rating away with 0. in rating mode the selection moves on to the next
+    // file afterwards, to rate through a directory quickly.
+    pub(crate) fn rate_files(&mut self, rating: u8) -> Result<(), SpinupError> {
+        let paths = self.marked_or_selected_files()?;
+        if paths.is_empty() {
+            return Err(SpinupError::InvalidRequest("no file is selected".to_string()));
+        }
+        let rating = if rating == 0 { None } else { Some(rating.min(MAX_RATING)) };
+        for path in &paths {
+            self.ratings.set(path, rating)?;
+        }
+        for entry in self.entries.iter_mut().filter(|e| paths.contains(&e.path)) {
+            entry.rating = rating;
+        }
+        if self.sort_mode == SortMode::RatingHighest || self.min_rating.is_some() {
+            self.resort_entries();
+        }
+        if self.rating_mode && self.marked.is_empty() {
+            self.next_list_item();
+        }
+        Ok(())
+    }
+
+    // limits the files listed to the next listed extension in turn, going back
+    // to all of them after the last. the selection stays on the same entry if it's
+    // still listed, otherwise on the same row.
+    pub(crate) fn cycle_ext_filter(&mut self) {
+        let extensions: Vec<&str> = match &self.listed_extensions {
+            Some(extensions) => extensions.iter().map(String::as_str).collect(),
+            None => SUPPORTED_EXTS.to_vec(),
+        };
+        self.ext_filter = match &self.ext_filter {
+            None => extensions.first().map(|ext| ext.to_string()),
+            Some(ext) => extensions.iter()
+                .position(|e| e == ext)
+                .and_then(|i| extensions.get(i + 1))
+                .map(|ext| ext.to_string()),
+        };
+        let previous_entry = self.selected_entry();
+        let previous_row = self.file_list_state.selected().unwrap_or(0);
+        self.apply_filter();
+        match previous_entry.and_then(|entry| self.visible_entries.iter().position(|&e| e == entry)) {
+            Some(view_index) => self.select_list_item(view_index),
+            None => self.select_row_clamped(previous_row),
+        }
+        self.schedule_preview();
+    }
+
+    // sorts the directories and the files by the sort mode. the parent directory entry
+    // stays at the top and directories are sorted by name when sizing files.
+    pub(crate) fn sort_entries(&mut self) {
+        let file_mode = self.sort_mode;
+        let dir_mode = match file_mode {
+            SortMode::SizeLargest => SortMode::NameAscending,
+            mode => mode,
+        };
+        self.entries.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| {
+            let mode = if a.kind == EntryKind::File { file_mode } else { dir_mode };
+            mode.compare(a, b)
+        }));
+    }
+
+    // switches to the next sort mode, keeping the selected entry selected
+    pub(crate) fn cycle_sort_mode(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        self.resort_entries();
+    }
+
+    // sorts the entries again after they changed, keeping the selected entry selected
+    pub(crate) fn resort_entries(&mut self) {
+        let previous = self.selected_entry_key();
+        self.sort_entries();
+        self.apply_filter();
+        // the shuffle round holds indices into the old order
+        self.shuffle_remaining = None;
+        self.select_entry_key(previous);
+    }
+
+    // starts probing the durations of the listed files that don't have one yet on
+    // a background thread, replacing any probe that was still running. files whose
+    // duration was cached and haven't changed since are filled in right away.
+    pub(crate) fn start_duration_probe(&mut self) {
+        self.duration_probe = None;
+        let mut paths = Vec::new();
+        for entry in self.entries.iter_mut().filter(|e| e.kind == EntryKind::File && e.duration.is_none()) {
+            let stamp = FileStamp { modified: entry.modified, size: entry.size };
+            match self.duration_cache.get(&entry.path) {
+                Some((cached_stamp, duration)) if *cached_stamp == stamp => entry.duration = Some(*duration),
+                _ => paths.push(entry.path.clone()),
+            }
+        }
+        let sender = match &self.event_sender {
+            Some(sender) if !paths.is_empty() => sender.clone(),
+            _ => return,
+        };
+        self.probe_count += 1;
+        let id = self.probe_count;
+        let total = paths.len();
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let thread_done = done.clone();
+        std::thread::spawn(move || {
+            for path in paths {
+                // the list moved on if the probe was cancelled or nobody is listening anymore
+                if thread_cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                    return;
+                }
+                // the stamp is taken first so a file changed while it's probed looks stale later
+                let stamp = file_stamp(&path);
+                if let Some(duration) = probe_duration(&path) {
+                    if sender.send(AppEvent::ProbeResult(path, stamp, duration)).is_err() {
+                        return;
+                    }
+                }
+                thread_done.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            _ = sender.send(AppEvent::ProbeFinished(id));
+        });
+        self.duration_probe = Some(DurationProbe { id, cancel, found: std::collections::HashMap::new(), done, total });
+    }
+
+    // keeps a duration the background probe found until the next update fills it in,
+    // and caches it for the next time the file is listed. a late one from a probe that
+    // was replaced is still right for its file.
+    pub(crate) fn probe_result(&mut self, path: PathBuf, stamp: Option<FileStamp>, duration: std::time::Duration) {
+        if let Some(stamp) = stamp {
+            self.cache_duration(path.clone(), stamp, duration);
+        }
+        if let Some(probe) = &mut self.duration_probe {
+            probe.found.insert(path, duration);
+        }
+    }
+
+    // remembers the duration of a file as of `stamp`, emptying the cache first when it's full
+    pub(crate) fn cache_duration(&mut self, path: PathBuf, stamp: FileStamp, duration: std::time::Duration) {
+        if self.duration_cache.len() >= DURATION_CACHE_SIZE && !self.duration_cache.contains_key(&path) {
+            self.duration_cache.clear();
+        }
+        self.duration_cache.insert(path, (stamp, duration));
+        self.duration_cache_changed = true;
+    }
+
+    // writes the duration cache to the data directory if it has anything new in it
+    pub(crate) fn save_durations(&mut self) -> Result<(), SpinupError> {
+        if !self.duration_cache_changed {
+            return Ok(());
+        }
+        self.duration_cache_changed = false;
+        save_duration_cache(&self.duration_cache)?;
+        Ok(())
+    }
+
+    // how far the background probe has got, as the files it's gone through and the
+    // files it was given, while one is running
+    pub(crate) fn duration_probe_progress(&self) -> Option<(usize, usize)> {
+        let probe = self.duration_probe.as_ref()?;
+        Some((probe.done.load(std::sync::atomic::Ordering::Relaxed), probe.total))
+    }
+
+    // ends the probe with the id `id`, unless it was replaced by another one
+    pub(crate) fn probe_finished(&mut self, id: u64) {
+        if !matches!(&self.duration_probe, Some(probe) if probe.id == id) {
+            return;
+        }
+        self.update_duration_probe();
+        self.duration_probe = None;
+        if let Err(err) = self.save_durations() {
+            self.last_error_msg = format!("Duration Cache Error: {}", err);
+        }
+        // the list can only be put in duration order once every duration is in
+        if self.sort_mode == SortMode::DurationLongest {
+            self.resort_entries();
+        }
+    }
+
+    // fills in the durations the background probe has found so far
+    pub(crate) fn update_duration_probe(&mut self) {
+        let mut found = match &mut self.duration_probe {
+            Some(probe) if !probe.found.is_empty() => std::mem::take(&mut probe.found),
+            _ => return,
+        };
+        for entry in &mut self.entries {
+            if let Some(duration) = found.remove(&entry.path) {
+                entry.duration = Some(duration);
+            }
+        }
+    }
+
+    // starts decoding the waveform of a file on a background thread unless it's
+    // cached already, giving up on the one being decoded for the last selection
+    pub(crate) fn request_waveform(&mut self, snd_path: &Path) {
+        if self.waveforms.contains_key(snd_path) || matches!(&self.waveform_job, Some(job) if job.path == snd_path) {
+            return;
+        }
+        let stamp = file_stamp(snd_path);
+        // files big enough to be streamed aren't kept in memory to play
+        let keep_frames = matches!(stamp, Some(stamp) if stamp.size <= self.sound_state.stream_threshold);
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let thread_path = snd_path.to_path_buf();
+        let track_id = self.chosen_tracks.get(snd_path).copied();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let peaks = compute_waveform(&thread_path, &thread_cancel, keep_frames, track_id);
+            if !thread_cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                _ = sender.send(peaks);
+            }
+        });
+        self.waveform_job = Some(WaveformJob { path: snd_path.to_path_buf(), stamp, receiver, cancel });
+    }
+
+    // the loudness of the selected file, if it's been measured since it last changed
+    pub(crate) fn selected_loudness(&self) -> Option<Loudness> {
+        let path = self.selected_file_path()?;
+        let (stamp, loudness) = self.loudness.get(&path)?;
+        if file_stamp(&path) != Some(*stamp) {
+            return None;
+        }
+        Some(*loudness)
+    }
+
+    // starts measuring the loudness of the selected file on a background thread,
+    // unless it's been measured already
+    pub(crate) fn measure_selected_loudness(&mut self) -> Result<(), SpinupError> {
+        let snd_path = self.selected_file_path()
+            .ok_or_else(|| SpinupError::InvalidRequest("select a file to measure".to_string()))?;
+        if self.selected_loudness().is_some() || matches!(&self.loudness_job, Some(job) if job.path == snd_path) {
+            return Ok(());
+        }
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let thread_path = snd_path.clone();
+        let track_id = self.chosen_tracks.get(&snd_path).copied();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let loudness = measure_loudness(&thread_path, track_id, &thread_cancel).map_err(|err| err.to_string());
+            if !thread_cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                _ = sender.send(loudness);
+            }
+        });
+        let stamp = file_stamp(&snd_path);
+        self.loudness_job = Some(LoudnessJob { path: snd_path, stamp, receiver, cancel });
+        Ok(())
+    }
+
+    // caches the loudness of the selected file once the background thread has it
+    pub(crate) fn update_loudness_job(&mut self) {
+        let job = match &self.loudness_job {
+            Some(job) => job,
+            None => return,
+        };
+        let loudness = match job.receiver.try_recv() {
+            Ok(loudness) => loudness,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err("the measuring stopped unexpectedly".to_string()),
+        };
+        let job = match self.loudness_job.take() {
+            Some(job) => job,
+            None => return,
+        };
+        match (loudness, job.stamp) {
+            (Ok(loudness), Some(stamp)) => {
+                self.loudness.insert(job.path.clone(), (stamp, loudness));
+            },
+            (Ok(_), None) => {},
+            (Err(err), _) => self.last_error_msg = format!("Loudness Error: {}", err),
+        }
+    }
+
+    // starts reading the cover art of a file on a background thread unless it's
+    // cached already, giving up on the one being read for the last selection. a
+    // picture that's slow to decode never holds up moving through the list.
+    pub(crate) fn request_cover_art(&mut self, snd_path: &Path) {
+        if self.covers.contains_key(snd_path) || matches!(&self.cover_job, Some(job) if job.path == snd_path) {
+            return;
+        }
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let thread_path = snd_path.to_path_buf();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let cover = read_cover_art(&thread_path);
+            if !thread_cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                _ = sender.send(cover);
+            }
+        });
+        self.cover_job = Some(CoverJob { path: snd_path.to_path_buf(), receiver, cancel });
+    }
+
+    // starts counting up the length of the file that just started playing, giving up
+    // on the one that was playing before
+    pub(crate) fn start_duration_count(&mut self, snd_path: &Path) {
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let thread_path = snd_path.to_path_buf();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let duration = count_duration(&thread_path, &thread_cancel);
+            if !thread_cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                _ = sender.send(duration);
+            }
+        });
+        self.duration_count = Some(DurationCount { path: snd_path.to_path_buf(), receiver, cancel });
+    }
+
+    // puts the counted length on the gauge once the background thread has it, if the
+    // file is still the one playing
+    pub(crate) fn update_duration_count(&mut self) {
+        let count = match &self.duration_count {
+            Some(count) => count,
+            None => return,
+        };
+        let duration = match count.receiver.try_recv() {
+            Ok(duration) => duration,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => None,
+        };
+        let path = match self.duration_count.take() {
+            Some(count) => count.path.clone(),
+            None => return,
+        };
+        let duration = match duration {
+            Some(duration) => duration,
+            None => return,
+        };
+        if self.sound_state.playing_path.as_ref() == Some(&path) && self.sound_state.duration_estimated {
+            self.sound_state.play_duration = duration;
+            self.sound_state.duration_estimated = false;
+        }
+        if let Some(stamp) = file_stamp(&path) {
+            self.cache_duration(path.clone(), stamp, duration);
+        }
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.path == path) {
+            entry.duration = Some(duration);
+        }
+    }
+
+    // caches the cover art of the selected file once the background thread has it
+    pub(crate) fn update_cover_job(&mut self) {
+        let job = match &self.cover_job {
+            Some(job) => job,
+            None => return,
+        };
+        let cover = match job.receiver.try_recv() {
+            Ok(cover) => cover,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => None,
+        };
+        if self.covers.len() >= COVER_CACHE_SIZE {
+            self.covers.clear();
+        }
+        if let Some(job) = self.cover_job.take() {
+            self.covers.insert(job.path.clone(), cover);
+        }
+    }
+
+    // caches the waveform of the selected file once the background thread has it
+    pub(crate) fn update_waveform_job(&mut self) {
+        let job = match &self.waveform_job {
+            Some(job) => job,
+            None => return,
+        };
+        let decoded = match job.receiver.try_recv() {
+            Ok(decoded) => decoded,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => None,
+        };
+        if self.waveforms.len() >= WAVEFORM_CACHE_SIZE {
+            self.waveforms.clear();
+        }
+        if let Some(job) = self.waveform_job.take() {
+            let (peaks, sound_data) = match decoded {
+                Some(decoded) => (Some(decoded.peaks), decoded.sound_data),
+                None => (None, None),
+            };
+            // the same decode makes the file ready to play
+            if let (Some(stamp), Some(sound_data)) = (job.stamp, sound_data) {
+                self.cache_sound(&job.path, stamp, sound_data);
+            }
+            self.waveforms.insert(job.path.clone(), peaks);
+        }
+    }
+
+    // shows or hides the entries starting with a '.', keeping the selected entry
+    // selected if it's still listed
+    pub(crate) fn toggle_hidden(&mut self) {
+        let previous = self.selected_entry_key();
+        self.show_hidden = !self.show_hidden;
+        self.needs_file_list_update = true;
+        self.update_file_names();
+        self.select_entry_key(previous);
+    }
+
+    // switches between listing the files in the current directory and every file
+    // beneath it, keeping the selected entry selected if it's still listed
+    pub(crate) fn toggle_recursive(&mut self) {
+        let previous = self.selected_entry_key();
+        self.recursive = !self.recursive;
+        self.needs_file_list_update = true;
+        self.update_file_names();
+        self.select_entry_key(previous);
+    }
+
+    // picks up the progress of the recursive scan and swaps its files into the
+    // list once it finishes
+    pub(crate) fn update_recursive_scan(&mut self) {
+        let scan = match &mut self.scan {
+            Some(scan) => scan,
+            None => return,
+        };
+        let mut finished = None;
+        loop {
+            match scan.receiver.try_recv() {
+                Ok(ScanMessage::Progress(files_found)) => scan.files_found = files_found,
+                Ok(ScanMessage::Done(entries)) => {
+                    finished = Some(entries);
+                    break;
+                },
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                // the scan thread went away without finishing
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    finished = Some(Vec::new());
+                    break;
+                },
+            }
+        }
+        let entries = match finished {
+            Some(entries) => entries,
+            None => return,
+        };
+
+        self.scan = None;
+        self.scan_truncated = entries.len() >= self.scan_options.max_files;
+        self.entries.retain(|e| e.is_dir());
+        self.entries.extend(entries);
+        self.fill_ratings();
+        self.resort_entries();
+        self.start_duration_probe();
+        if self.finder.is_some() {
+            self.update_finder_results();
+        }
+    }
+
+    // returns the kind of the selected entry along with its name, which identifies
+    // it even after the entries are sorted or listed again
+    pub(crate) fn selected_entry_key(&self) -> Option<(EntryKind, OsString)> {
+        let entry = &self.entries[self.selected_entry()?];
+        Some((entry.kind, entry.name.clone()))
+    }
+
+    // selects the directory entry with the given name, or the first entry if it's
+    // not listed anymore
+    pub(crate) fn select_directory_named(&mut self, name: &std::ffi::OsStr) {
+        self.select_entry_key(Some((EntryKind::Dir, name.to_os_string())));
+    }
+
+    // selects the entry returned by `selected_entry_key`, or the first entry if
+    // it's gone or filtered out
+    pub(crate) fn select_entry_key(&mut self, key: Option<(EntryKind, OsString)>) {
+        let view_index = self.view_index_of_key(key).unwrap_or(0);
+        self.select_list_item(view_index);
+    }
+
+    // returns the row of the list showing the entry from `selected_entry_key`, if
+    // it's still listed
+    pub(crate) fn view_index_of_key(&self, key: Option<(EntryKind, OsString)>) -> Option<usize> {
+        let (kind, name) = key?;
+        let entry = self.entries.iter().position(|e| e.kind == kind && e.name == name)?;
+        self.visible_entries.iter().position(|&e| e == entry)
+    }
+
+    // returns the index into `entries` of the file to auto-advance to after the
+    // playing file, or None if auto-advance is off, the end of the list was reached,
+    // or the playing file isn't in the current directory anymore.
+    pub(crate) fn next_file_index(&mut self) -> Option<usize> {
+        if self.advance_mode == AdvanceMode::Off {
+            return None;
+        }
+        let playing_path = self.playing_file_path.as_ref()?;
+        let index = self.file_entry_index(playing_path)?;
+
+        // shuffling plays every file once in a random order before any repeat
+        if self.shuffle {
+            match &self.shuffle_remaining {
+                // a finished round only starts over when wrapping
+                Some(remaining) if remaining.is_empty() => {
+                    if self.advance_mode != AdvanceMode::Wrap {
+                        return None;
+                    }
+                    self.shuffle_remaining = Some(shuffled_file_indices(self.first_file_entry()..self.entries.len(), index));
+                },
+                Some(_) => {},
+                None => self.shuffle_remaining = Some(shuffled_file_indices(self.first_file_entry()..self.entries.len(), index)),
+            }
+            return self.shuffle_remaining.as_mut()?.pop();
+        }
+
+        // the files are all at the end of the entries
+        if index + 1 < self.entries.len() {
+            Some(index + 1)
+        } else if self.advance_mode == AdvanceMode::Wrap {
+            Some(self.first_file_entry())
+        } else {
+            None
+        }
+    }
+
+    // moves the focus on to the next pane: the tree (when it's shown), the file
+    // list and then the queue
+    pub(crate) fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Tree => Focus::Files,
+            Focus::Files => Focus::Queue,
+            Focus::Queue if self.tree_shown => Focus::Tree,
+            Focus::Queue => Focus::Files,
+        };
+        self.clamp_queue_selection();
+    }
+
+    // shows the directory tree beside the file list when the tree layout is on and
+    // the terminal is `width` columns wide enough for both, and keeps the tree on
+    // the directory being listed
+    pub(crate) fn update_tree_layout(&mut self, width: u16) {
+        let shown = self.tree_layout && width >= TREE_LAYOUT_MIN_WIDTH;
+        if shown != self.tree_shown {
+            self.tree_shown = shown;
+            if !shown && self.focus == Focus::Tree {
+                self.focus = Focus::Files;
+            }
+            // the directories move between the tree and the file list
+            let previous = self.selected_entry_key();
+            self.apply_filter();
+            self.select_entry_key(previous);
+        }
+        if !shown {
+            return;
+        }
+        let tree = self.tree.get_or_insert_with(|| DirTree::new(&self.current_directory_path, self.show_hidden));
+        if tree.show_hidden != self.show_hidden {
+            tree.reload(self.show_hidden);
+        }
+        if tree.shown_dir != self.current_directory_path {
+            tree.reveal(&self.current_directory_path);
+        }
+    }
+
+    // moves the tree's selection by `delta` rows and lists the directory it lands on
+    pub(crate) fn move_tree_selection(&mut self, delta: isize) {
+        let dir = match &mut self.tree {
+            Some(tree) => {
+                tree.move_selection(delta);
+                tree.selected_dir().map(Path::to_path_buf)
+            },
+            None => return,
+        };
+        if let Some(dir) = dir.filter(|dir| *dir != self.current_directory_path) {
+            self.enter_directory(&dir);
+        }
+    }
+
+    // expands or collapses the selected directory of the tree, or on its '..' row
+    // starts the tree over further up
+    pub(crate) fn toggle_tree_selected(&mut self) {
+        if let Some(tree) = &mut self.tree {
+            tree.toggle_selected(&self.current_directory_path);
+        }
+    }
+
+    // selects the row `row` of the tree after a click on it, toggling it when it was
+    // already selected
+    pub(crate) fn click_tree_row(&mut self, row: usize) {
+        let selected = match &self.tree {
+            Some(tree) if row < tree.rows.len() => tree.list_state.selected(),
+            _ => return,
+        };
+        self.focus = Focus::Tree;
+        match selected {
+            Some(selected) if selected == row => self.toggle_tree_selected(),
+            selected => self.move_tree_selection(row as isize - selected.unwrap_or(0) as isize),
+        }
+    }
+
+    // appends the selected file to the end of the queue
+    pub(crate) fn queue_selected_file(&mut self) {
+        if self.focus == Focus::Queue {
+            return;
+        }
+        // with files marked, all of them get queued instead of the selection
+        if !self.marked.is_empty() {
+            let mut marked: Vec<PathBuf> = self.marked.iter().cloned().collect();
+            marked.sort();
+            self.queue.append(&mut marked);
+            self.clamp_queue_selection();
+            return;
+        }
+        if let Some(path) = self.selected_file_path() {
+            self.queue.push(path);
+            self.clamp_queue_selection();
+        }
+    }
+
+    // marks or unmarks the selected file and moves on to the next entry so runs of
+    // files can be marked quickly
+    pub(crate) fn toggle_mark_selected(&mut self) -> Result<(), SpinupError> {
+        if self.focus == Focus::Queue {
+            return Ok(());
+        }
+        if self.is_dir_selected() {
+            return Err(SpinupError::InvalidRequest("only files can be marked".to_string()));
+        }
+        let path = match self.selected_file_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if !self.marked.remove(&path) {
+            self.marked.insert(path);
+        }
+        self.next_list_item();
+        self.update_selected_file_info()?;
+        Ok(())
+    }
+
+    // asks to delete the marked files, or the selected file when none are marked
+    pub(crate) fn confirm_delete(&mut self) -> Result<(), SpinupError> {
+        if self.focus == Focus::Queue {
+            return Ok(());
+        }
+        let paths = self.marked_or_selected_files()?;
+        if !paths.is_empty() {
+            self.prompt = Some(Prompt::Delete { paths, permanent: self.permanent_delete });
+        }
+        Ok(())
+    }
+
+    // puts the absolute path of the selected entry on the clipboard. without a
+    // clipboard to talk to, like over ssh, the path is sent to the terminal as an
+    // OSC 52 sequence instead, which most terminals copy for the program.
+    pub(crate) fn copy_selected_path(&mut self) -> Result<(), SpinupError> {
+        let path = match self.selected_entry_path() {
+            Some(path) => path.canonicalize()?,
+            None => return Ok(()),
+        };
+        let text = path.to_string_lossy().to_string();
+
+        if self.clipboard.is_none() {
+            self.clipboard = arboard::Clipboard::new().ok();
+        }
+        let copied = match &mut self.clipboard {
+            Some(clipboard) => clipboard.set_text(text.clone()).is_ok(),
+            None => false,
+        };
+        if copied {
+            self.last_info_msg = format!("Copied: {}", text);
+        } else {
+            use std::io::Write;
+            let mut stdout = io::stdout();
+            write!(stdout, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))?;
+            stdout.flush()?;
+            self.last_info_msg = format!("Copied through the terminal: {}", text);
+        }
+        Ok(())
+    }
+
+    // launches the configured command (or the platform's default opener) on `path`
+    // without waiting for it. the program's output is thrown away, except that its
+    // stderr is kept to report if it fails, since anything it printed would be
+    // drawn over the interface.
+    pub(crate) fn open_externally(&mut self, path: &Path, is_dir: bool) -> Result<(), SpinupError> {
+        let configured = if is_dir { &self.open_dir_command } else { &self.open_command };
+        let command_line = configured.clone().unwrap_or_else(default_open_command);
+        let path_text = path.to_string_lossy();
+        let mut args: Vec<String> = command_line.iter().map(|arg| arg.replace("{}", &path_text)).collect();
+        if !command_line.iter().any(|arg| arg.contains("{}")) {
+            args.push(path_text.to_string());
+        }
+
+        let mut child = std::process::Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|err| SpinupError::InvalidPath { path: PathBuf::from(&args[0]), reason: format!("couldn't be run: {}", err) })?;
+
+        let sender = self.external_errors.get_or_insert_with(std::sync::mpsc::channel).0.clone();
+        let program = args[0].clone();
+        std::thread::spawn(move || {
+            use std::io::Read;
+            let mut stderr_text = String::new();
+            if let Some(mut stderr) = child.stderr.take() {
+                _ = stderr.read_to_string(&mut stderr_text);
+            }
+            let status = match child.wait() {
+                Ok(status) if status.success() => return,
+                Ok(status) => status.to_string(),
+                Err(err) => err.to_string(),
+            };
+            let message = match stderr_text.lines().map(str::trim).find(|line| !line.is_empty()) {
+                Some(line) => format!("Open Error: {} failed ({}): {}", program, status, line),
+                None => format!("Open Error: {} failed ({})", program, status),
+            };
+            _ = sender.send(message);
+        });
+        self.last_info_msg = format!("Opened: {}", path_text);
+        Ok(())
+    }
+
+    // shows the failures reported by programs launched with `open_externally`
+    pub(crate) fn update_external_errors(&mut self) {
+        while let Some(message) = self.external_errors.as_ref().and_then(|(_, receiver)| receiver.try_recv().ok()) {
+            // an error already showing is logged before it's replaced
+            self.record_error();
+            self.clear_error();
+            self.last_error_msg = message;
+        }
+    }
+
+    // returns the marked files sorted, or the selected file when none are marked.
+    // directories can't be acted on this way, so selecting one is an error.
+    pub(crate) fn marked_or_selected_files(&self) -> Result<Vec<PathBuf>, SpinupError> {
+        if !self.marked.is_empty() {
+            let mut marked: Vec<PathBuf> = self.marked.iter().cloned().collect();
+            marked.sort();
+            return Ok(marked);
+        }
+        if self.is_dir_selected() {
+            return Err(SpinupError::InvalidRequest("directories can only be browsed".to_string()));
+        }
+        Ok(self.selected_file_path().into_iter().collect())
+    }
+
+    // starts copying or moving the marked or selected files to `dest_dir` on a
+    // background thread
+    pub(crate) fn start_file_operation(&mut self, dest_dir: PathBuf, moving: bool) -> Result<(), SpinupError> {
+        if self.file_operation.is_some() {
+            return Err(SpinupError::InvalidRequest("wait for the files being copied or moved to finish".to_string()));
+        }
+        let paths = self.marked_or_selected_files()?;
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (answer_sender, answer_receiver) = std::sync::mpsc::channel();
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (thread_dest, thread_cancel) = (dest_dir.clone(), cancel.clone());
+        std::thread::spawn(move || run_file_operation(paths, &thread_dest, moving, &sender, &answer_receiver, &thread_cancel));
+        self.file_operation = Some(FileOperation {
+            receiver,
+            answers: answer_sender,
+            dest_dir,
+            moving,
+            progress: None,
+            cancel,
+        });
+        Ok(())
+    }
+
+    // follows along with the copy or move running in the background, asking about
+    // files in the way and refreshing the list once it's done
+    pub(crate) fn update_file_operation(&mut self) {
+        let op = match &mut self.file_operation {
+            Some(op) => op,
+            None => return,
+        };
+        let mut finished = None;
+        loop {
+            match op.receiver.try_recv() {
+                Ok(FileOpMessage::Progress(progress)) => op.progress = Some(progress),
+                Ok(FileOpMessage::Collision(dest)) => self.prompt = Some(Prompt::Collision(dest)),
+                Ok(FileOpMessage::Done { transferred, failures }) => finished = Some((transferred, failures)),
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    finished.get_or_insert((vec![], vec!["the copy stopped unexpectedly".to_string()]));
+                    break;
+                },
+            }
+            if finished.is_some() {
+                break;
+            }
+        }
+        let (transferred, failures) = match finished {
+            Some(finished) => finished,
+            None => return,
+        };
+        let op = match self.file_operation.take() {
+            Some(op) => op,
+            None => return,
+        };
+        if matches!(self.prompt, Some(Prompt::Collision(_))) {
+            self.prompt = None;
+        }
+
+        // moved files keep their place in the queue and their rating, and lose their marks
+        if op.moving {
+            for (src, dest) in &transferred {
+                self.marked.remove(src);
+                for queued in self.queue.iter_mut().filter(|p| *p == src) {
+                    *queued = dest.clone();
+                }
+                if let Err(err) = self.ratings.rename(src, dest) {
+                    self.last_error_msg = format!("Rating Error: {}", err);
+                }
+            }
+        }
+
+        // the list only changes if files went into or out of the current directory
+        let current_dir = self.current_directory_path.canonicalize().ok();
+        if op.moving || current_dir.as_deref() == Some(op.dest_dir.as_path()) {
+            let previous = self.selected_entry_key();
+            self.needs_file_list_update = true;
+            self.update_file_names();
+            self.select_entry_key(previous);
+        }
+
+        let verb = if op.moving { "Move" } else { "Copy" };
+        match failures.len() {
+            0 => {},
+            1 => self.last_error_msg = format!("{} Error: {}", verb, failures[0]),
+            n => self.last_error_msg = format!("{} Error: {} files failed, like {}", verb, n, failures[0]),
+        }
+    }
+
+    // deletes the files, or moves them to the trash, stopping them first if one is
+    // playing. the files that are gone are taken out of the list, the marks and the
+    // queue, and the selection moves to the next file that's still there.
+    pub(crate) fn delete_files(&mut self, paths: &[PathBuf], permanent: bool) -> Result<(), SpinupError> {
+        if matches!(&self.sound_state.playing_path, Some(playing) if paths.contains(playing)) {
+            self.sound_state.stop_sound(std::time::Duration::ZERO)?;
+            self.play_all = None;
+        }
+
+        let mut deleted = vec![];
+        let mut failures = vec![];
+        for path in paths {
+            let result = if permanent {
+                fs::remove_file(path).map_err(|err| err.to_string())
+            } else {
+                trash::delete(path).map_err(|err| err.to_string())
+            };
+            match result {
+                Ok(()) => deleted.push(path.clone()),
+                Err(err) => failures.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+
+        // the selection moves on to the first entry after it that survived, or the
+        // last one before it if everything after it is gone
+        let survives = |entry: usize| self.entries[entry].is_dir() || !deleted.contains(&self.entries[entry].path);
+        let selected_row = self.file_list_state.selected().unwrap_or(0);
+        let next_entry = self.visible_entries.iter().skip(selected_row).copied().find(|&e| survives(e))
+            .or_else(|| self.visible_entries.iter().take(selected_row).rev().copied().find(|&e| survives(e)));
+        let next_key = next_entry.map(|entry| (self.entries[entry].kind, self.entries[entry].name.clone()));
+
+        self.entries.retain(|e| e.is_dir() || !deleted.contains(&e.path));
+        self.marked.retain(|p| !deleted.contains(p));
+        self.queue.retain(|p| !deleted.contains(p));
+        self.clamp_queue_selection();
+        self.waveforms.retain(|p, _| !deleted.contains(p));
+        if let Some(duplicates) = &mut self.duplicates {
+            duplicates.forget(&deleted);
+        }
+        self.shuffle_remaining = None;
+        self.apply_filter();
+        self.select_entry_key(next_key);
+
+        match failures.len() {
+            0 => Ok(()),
+            1 => Err(SpinupError::FileOperation(failures.remove(0))),
+            n => Err(SpinupError::FileOperation(format!("{} files couldn't be deleted, like {}", n, failures[0]))),
+        }
+    }
+
+    // the files to export: the marked files sorted, or the listed files in the order
+    // they're shown when none are marked
+    pub(crate) fn export_files(&self) -> Vec<PathBuf> {
+        if !self.marked.is_empty() {
+            let mut marked: Vec<PathBuf> = self.marked.iter().cloned().collect();
+            marked.sort();
+            return marked;
+        }
+        self.visible_entries.iter()
+            .map(|&entry| &self.entries[entry])
+            .filter(|e| e.kind == EntryKind::File)
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    // writes the files to export to `file_path`, returning how many were written.
+    // a .csv file gets a row for each with its size, duration, sample rate and
+    // channels, and anything else gets their full paths one per line.
+    pub(crate) fn export_manifest(&self, file_path: &Path) -> Result<usize, SpinupError> {
+        let files = self.export_files();
+        let is_csv = matches!(file_path.extension(), Some(ext) if ext.eq_ignore_ascii_case("csv"));
+        let text: String = if is_csv {
+            let mut text = String::from("path,size,duration,sample_rate,channels\n");
+            for path in &files {
+                let stamp = file_stamp(path);
+                // files that haven't been looked at in the info pane are probed now
+                let codec_data = match self.file_info_cache.get(path) {
+                    Some((cached_stamp, info)) if Some(*cached_stamp) == stamp => info.as_ref().map(|(codec_data, _)| *codec_data),
+                    _ => probe_file_info(path).ok().map(|(codec_data, _)| codec_data),
+                };
+                let size = stamp.map(|stamp| stamp.size.to_string()).unwrap_or_default();
+                let duration = codec_data.as_ref()
+                    .and_then(|c| c.duration)
+                    .map(|d| format!("{:.3}", d.as_secs_f64()))
+                    .unwrap_or_default();
+                let sample_rate = codec_data.as_ref().and_then(|c| c.sample_rate).map(|sr| sr.to_string()).unwrap_or_default();
+                let channels = codec_data.as_ref().and_then(|c| c.channels).map(|ch| ch.count().to_string()).unwrap_or_default();
+                text.push_str(&format!("{},{},{},{},{}\n", csv_field(&path.display().to_string()), size, duration, sample_rate, channels));
+            }
+            text
+        } else {
+            files.iter()
+                .map(|p| format!("{}\n", p.display()))
+                .collect()
+        };
+        fs::write(file_path, text)?;
+        Ok(files.len())
+    }
+
+    // the files to save as a playlist: the queue when it's focused or nothing is
+    // marked, and otherwise the marked files sorted
+    pub(crate) fn playlist_files(&self) -> Vec<PathBuf> {
+        if self.focus == Focus::Queue || self.marked.is_empty() {
+            return self.queue.clone();
+        }
+        let mut marked: Vec<PathBuf> = self.marked.iter().cloned().collect();
+        marked.sort();
+        marked
+    }
+
+    // writes `files` to the playlist at `path` along with the durations that are
+    // known for them, without probing any that aren't
+    pub(crate) fn save_playlist(&mut self, path: &Path, files: &[PathBuf]) -> Result<(), SpinupError> {
+        let files: Vec<(PathBuf, Option<std::time::Duration>)> = files.iter()
+            .map(|file_path| (file_path.clone(), self.known_duration(file_path)))
+            .collect();
+        write_m3u(path, &files)?;
+        self.last_info_msg = format!("Saved {} files to {}", files.len(), path.display());
+        Ok(())
+    }
+
+    // trims the `region` of `path` out to a wav next to it, asking first if there's
+    // already one there
+    pub(crate) fn request_trim(&mut self, path: PathBuf, region: TrimRegion) -> Result<(), SpinupError> {
+        if self.export_job.is_some() {
+            return Err(SpinupError::InvalidRequest("wait for the file being written to finish".to_string()));
+        }
+        if trim_dest(&path).exists() {
+            self.prompt = Some(Prompt::OverwriteTrim(path, region));
+            return Ok(());
+        }
+        self.start_trim(path, region)
+    }
+
+    // converts all of `path` to `format` next to it, asking first if there's already
+    // a file there
+    pub(crate) fn request_convert(&mut self, path: PathBuf, format: ExportFormat) -> Result<(), SpinupError> {
+        if self.export_job.is_some() {
+            return Err(SpinupError::InvalidRequest("wait for the file being written to finish".to_string()));
+        }
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(format.extension())) {
+            return Err(SpinupError::InvalidPath { path, reason: format!("is already a {} file", format.extension()) });
+        }
+        if convert_dest(&path, format).exists() {
+            self.prompt = Some(Prompt::OverwriteConvert(path, format));
+            return Ok(());
+        }
+        self.start_convert(path, format)
+    }
+
+    pub(crate) fn start_trim(&mut self, path: PathBuf, region: TrimRegion) -> Result<(), SpinupError> {
+        let options = ExportOptions {
+            format: ExportFormat::Wav,
+            region,
+            track_id: self.chosen_tracks.get(&path).copied(),
+            lossy_bits: self.trim_bit_depth,
+        };
+        let dest = trim_dest(&path);
+        self.start_export(path, dest, options, false)
+    }
+
+    pub(crate) fn start_convert(&mut self, path: PathBuf, format: ExportFormat) -> Result<(), SpinupError> {
+        let options = ExportOptions {
+            format,
+            region: TrimRegion::ALL,
+            track_id: self.chosen_tracks.get(&path).copied(),
+            lossy_bits: self.convert_bit_depth,
+        };
+        let dest = convert_dest(&path, format);
+        self.start_export(path, dest, options, true)
+    }
+
+    // starts writing `path` out to `dest` on a background thread. the file is written
+    // under a temporary name and only replaces the destination once it's complete.
+    fn start_export(&mut self, path: PathBuf, dest: PathBuf, options: ExportOptions, converting: bool) -> Result<(), SpinupError> {
+        if self.export_job.is_some() {
+            return Err(SpinupError::InvalidRequest("wait for the file being written to finish".to_string()));
+        }
+        let mut part_name = dest.file_name().unwrap_or_default().to_os_string();
+        part_name.push(".part");
+        let part_path = dest.with_file_name(part_name);
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let progress = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (thread_cancel, thread_progress, thread_dest) = (cancel.clone(), progress.clone(), dest.clone());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let result = export_sound(&path, &part_path, &options, &thread_cancel, &thread_progress)
+                .and_then(|length| {
+                    fs::rename(&part_path, &thread_dest)?;
+                    Ok(length)
+                });
+            if result.is_err() {
+                _ = fs::remove_file(&part_path);
+            }
+            _ = sender.send(result.map_err(|err| err.to_string()));
+        });
+        self.export_job = Some(ExportJob { dest, converting, receiver, cancel, progress });
+        Ok(())
+    }
+
+    // how many jobs are writing files in the background, which quitting would cut
+    // off partway. scans and playback only read, so they don't count.
+    pub(crate) fn running_jobs(&self) -> usize {
+        self.file_operation.is_some() as usize + self.export_job.is_some() as usize
+    }
+
+    // stops the jobs writing files and waits up to `timeout` for them to remove
+    // what they'd written so far
+    pub(crate) fn cancel_jobs(&mut self, timeout: std::time::Duration) {
+        let deadline = std::time::Instant::now() + timeout;
+        if let Some(job) = self.export_job.take() {
+            job.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+            // the result is sent once the part file is gone
+            _ = job.receiver.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now()));
+        }
+        if let Some(op) = self.file_operation.take() {
+            op.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+            // a thread waiting on what to do about a file in the way gives up
+            // without anyone to answer
+            let FileOperation { receiver, answers, .. } = op;
+            drop(answers);
+            loop {
+                match receiver.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+                    Ok(FileOpMessage::Done { .. }) | Err(_) => break,
+                    Ok(_) => {},
+                }
+            }
+        }
+    }
+
+    // reports the trim or conversion running in the background once it's done,
+    // listing the new file if it went into the current directory
+    pub(crate) fn update_export_job(&mut self) {
+        let job = match &self.export_job {
+            Some(job) => job,
+            None => return,
+        };
+        let result = match job.receiver.try_recv() {
+            Ok(result) => result,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err("it stopped unexpectedly".to_string()),
+        };
+        let (dest, converting) = match self.export_job.take() {
+            Some(job) => (job.dest.clone(), job.converting),
+            None => return,
+        };
+        let length = match result {
+            Ok(length) => length,
+            Err(err) => {
+                self.last_error_msg = format!("{} Error: {}", if converting { "Convert" } else { "Trim" }, err);
+                return;
+            },
+        };
+        let name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if converting {
+            self.last_info_msg = format!("Converted {} to {}", format_duration(length), name);
+        } else {
+            self.last_info_msg = format!("Trimmed {} out to {}", format_duration(length), name);
+        }
+        let dest_dir = dest.parent().and_then(|dir| dir.canonicalize().ok());
+        if dest_dir.is_some() && dest_dir == self.current_directory_path.canonicalize().ok() {
+            let previous = self.selected_entry_key();
+            self.needs_file_list_update = true;
+            self.update_file_names();
+            self.select_entry_key(previous);
+        }
+    }
+
+    // the duration of a file from the list or from the duration cache, if it's been probed
+    pub(crate) fn known_duration(&self, path: &Path) -> Option<std::time::Duration> {
+        if let Some(duration) = self.entries.iter().find(|e| e.path == path).and_then(|e| e.duration) {
+            return Some(duration);
+        }
+        let (cached_stamp, duration) = self.duration_cache.get(path)?;
+        match file_stamp(path) {
+            Some(stamp) if stamp == *cached_stamp => Some(*duration),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn remove_selected_queue_item(&mut self) {
+        _ = self.take_selected_queue_item();
+    }
+
+    // removes the selected entry from the queue and returns it
+    pub(crate) fn take_selected_queue_item(&mut self) -> Option<PathBuf> {
+        let sel_index = self.queue_list_state.selected()?;
+        if sel_index >= self.queue.len() {
+            return None;
+        }
+        let path = self.queue.remove(sel_index);
+        self.clamp_queue_selection();
+        Some(path)
+    }
+
+    // keeps the queue selection on a valid entry as the queue grows and shrinks
+    pub(crate) fn clamp_queue_selection(&mut self) {
+        if self.queue.is_empty() {
+            self.queue_list_state.select(None);
+        } else {
+            let sel_index = self.queue_list_state.selected().unwrap_or(0);
+            self.queue_list_state.select(Some(sel_index.min(self.queue.len() - 1)));
+        }
+    }
+
+    pub(crate) fn next_queue_item(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        let i = match self.queue_list_state.selected() {
+            Some(i) if i + 1 < self.queue.len() => i + 1,
+            _ => 0,
+        };
+        self.queue_list_state.select(Some(i));
+    }
+
+    pub(crate) fn previous_queue_item(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        let i = match self.queue_list_state.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => self.queue.len() - 1,
+        };
+        self.queue_list_state.select(Some(i));
+    }
+
+    pub(crate) fn toggle_preview(&mut self) {
+        self.sound_state.preview = !self.sound_state.preview;
+        self.preview_due = None;
+    }
+
+    // in preview mode, queues up a preview of the newly selected file. the preview
+    // waits a moment so that quickly moving through the list doesn't load every file.
+    pub(crate) fn schedule_preview(&mut self) {
+        if self.sound_state.preview && self.focus != Focus::Queue && self.is_file_selected() {
+            self.preview_due = Some(std::time::Instant::now() + PREVIEW_DEBOUNCE_TIME);
+        } else {
+            self.preview_due = None;
+        }
+    }
+
+    // returns true once a scheduled preview should start playing
+    pub(crate) fn preview_ready(&mut self) -> bool {
+        match self.preview_due {
+            Some(due) if std::time::Instant::now() >= due => {
+                self.preview_due = None;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub(crate) fn toggle_shuffle(&mut self) {
+        self.shuffle = !self.shuffle;
+        self.shuffle_remaining = None;
+    }
+
+    pub(crate) fn is_dir_selected(&self) -> bool {
+        matches!(self.selected_entry(), Some(entry) if self.entries[entry].is_dir())
+    }
+
+    pub(crate) fn is_playlist_selected(&self) -> bool {
+        matches!(self.selected_entry(), Some(entry) if self.entries[entry].kind == EntryKind::Playlist)
+    }
+
+    pub(crate) fn is_file_selected(&self) -> bool {
+        matches!(self.selected_entry(), Some(entry) if self.entries[entry].kind == EntryKind::File)
+    }
+
+    // the full path of the selected file, if a file is selected
+    pub(crate) fn selected_file_path(&self) -> Option<PathBuf> {
+        if !self.is_file_selected() {
+            return None;
+        }
+        self.selected_entry_path()
+    }
+
+    // returns the path of the selected item in the list, a file or a directory.
+    // Can return None if there is no selection.
+    pub(crate) fn selected_entry_path(&self) -> Option<PathBuf> {
+        Some(self.entries[self.selected_entry()?].path.clone())
+    }
+
+    pub(crate) fn update_file_names(&mut self) {
+        if !self.needs_file_list_update {
+            return;
+        }
+
+        let full_path = self.current_directory_path.as_path();
+            
+        self.entries.clear();
+        if full_path.parent().is_some() {
+            self.entries.push(BrowserEntry::new("..".into(), full_path.join(".."), EntryKind::ParentDir));
+        }
+        match get_directories_in_dir(full_path, self.show_hidden) {
+            Ok(paths) => {
+                let entries = paths.into_iter()
+                    .filter_map(|dir_path| {
+                        let name = dir_path.file_name()?.to_os_string();
+                        Some(BrowserEntry::new(name, dir_path, EntryKind::Dir))
+                    });
+                self.entries.extend(entries);
+            }
+            Err(e) => self.last_error_msg = format!("Failed to update directory list: {}", e)
+        }
+        match get_playlists_in_dir(full_path, self.show_hidden) {
+            Ok(paths) => {
+                let entries = paths.into_iter()
+                    .filter_map(|playlist_path| {
+                        let name = playlist_path.file_name()?.to_os_string();
+                        Some(BrowserEntry::new(name, playlist_path, EntryKind::Playlist))
+                    });
+                self.entries.extend(entries);
+            }
+            Err(e) => self.last_error_msg = format!("Failed to update playlist list: {}", e)
+        }
+
+        // the recursive list fills in once the scan thread finishes with it
+        self.scan = None;
+        if self.recursive {
+            self.scan_options.show_hidden = self.show_hidden;
+            self.scan_options.listed_extensions = self.listed_extensions.clone();
+            self.scan_options.probe_content = self.probe_content;
+            self.scan = Some(start_recursive_scan(full_path.to_path_buf(), self.scan_options.clone()));
+        } else {
+            match get_supported_files_in_dir(full_path, self.show_hidden, self.listed_extensions.as_deref(), self.probe_content) {
+                Ok(paths) => {
+                    let probe_content = self.probe_content;
+                    let entries = paths.into_iter()
+                        // playlists are already listed as playlists when their extension is asked for
+                        .filter(|file_path| !is_playlist(file_path))
+                        .filter_map(|file_path| {
+                            let name = file_path.file_name()?.to_os_string();
+                            let mut entry = BrowserEntry::new(name, file_path, EntryKind::File);
+                            // the probe is cached, so files listed for their content aren't read again
+                            entry.probe_failed = probe_content && !probed_as_audio(&entry.path);
+                            Some(entry)
+                        });
+                    self.entries.extend(entries);
+                }
+                Err(e) => self.last_error_msg = format!("Failed to update file list: {}", e)
+            }
+        }
+
+        // ratings of files that went away outside of spinup are only dropped once
+        // their directory is listed again
+        if let Err(err) = self.ratings.forget_missing_in(full_path) {
+            self.last_error_msg = format!("Rating Error: {}", err);
+        }
+        self.fill_ratings();
+        self.sort_entries();
+        self.file_list_state = ListState::default();
+        self.apply_filter();
+        self.shuffle_remaining = None;
+        self.start_duration_probe();
+        self.listed_dir_modified = fs::metadata(&self.current_directory_path).and_then(|m| m.modified()).ok();
+        self.dir_changed = false;
+        if !matches!(&self.dir_watch, Some(watch) if watch.path == self.current_directory_path) {
+            // the old watch goes away with it, and a directory that can't be watched
+            // can still be refreshed by hand
+            self.dir_watch = self.event_sender.clone().and_then(|sender| DirWatch::new(&self.current_directory_path, sender));
+        }
+        self.needs_file_list_update = false;        
+    }
+
+    // selects row `i` of the list, clamped to the last row. an empty list is left
+    // with nothing selected.
+    pub(crate) fn select_list_item(&mut self, i: usize) {
+        if self.visible_entries.is_empty() {
+            self.file_list_state.select(None);
+        } else {
+            self.file_list_state.select(Some(i.min(self.visible_entries.len() - 1)));
+        }
+        if let Err(err) = self.update_selected_file_info() {
+            self.last_error_msg = format!("File Info Error: {}", err);
+        }
+    }
+
+    pub(crate) fn next_list_item(&mut self) {
+        // a filter can leave nothing to move through
+        if self.visible_entries.is_empty() {
+            return;
+        }
+        let i = match self.file_list_state.selected() {
+            Some(i) => {
+                let total_size = self.visible_entries.len();
+                if i >= total_size - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.file_list_state.select(Some(i));
+    }
+
+    pub(crate) fn previous_list_item(&mut self) {
+        if self.visible_entries.is_empty() {
+            return;
+        }
+        let i = match self.file_list_state.selected() {
+            Some(i) => {
+                let total_size = self.visible_entries.len();
+                if i == 0 {
+                    total_size - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.file_list_state.select(Some(i));
+    }
+
+    // moves the selection by `delta` entries, stopping at the first and last entries
+    // instead of wrapping around
+    pub(crate) fn move_list_selection_by(&mut self, delta: isize) {
+        let total_size = self.visible_entries.len();
+        if total_size == 0 {
+            return;
+        }
+        let current = self.file_list_state.selected().unwrap_or(0) as isize;
+        let new_index = current.saturating_add(delta).clamp(0, total_size as isize - 1);
+        self.select_list_item(new_index as usize);
+    }
+
+    // selects the row `row` of the list, or the last row if there aren't that many
+    pub(crate) fn select_row_clamped(&mut self, row: usize) {
+        if self.visible_entries.is_empty() {
+            return;
+        }
+        self.select_list_item(row.min(self.visible_entries.len() - 1));
+    }
+
+    // adds the digit pressed to the count for the next motion, returning false if
+    // the key isn't part of a count. the digits jump within the playing sound
+    // instead, so a count can only be started while nothing plays.
+    pub(crate) fn push_count_digit(&mut self, key: &crossterm::event::KeyEvent) -> bool {
+        let digit = match key.code {
+            crossterm::event::KeyCode::Char(c) if key.modifiers.is_empty() => match c.to_digit(10) {
+                Some(digit) => digit as usize,
+                None => return false,
+            },
+            _ => return false,
+        };
+        let count = match self.pending_count {
+            Some(count) => count,
+            // like vim, a count can't start with a zero
+            None if digit == 0 || self.sound_state.is_audible() || self.focus == Focus::Queue => return false,
+            None => 0,
+        };
+        self.pending_count = Some((count * 10 + digit).min(MAX_COUNT));
+        true
+    }
+
+    pub fn _unselect_list_item(&mut self) {
+        self.file_list_state.select(None);
+    }
+}
+
+// quotes a CSV field when it has a comma, a quote or a line break in it, doubling
+// any quotes inside
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// finds the first match of `needle` in `haystack` ignoring case, returning the
+// byte range of the match in `haystack`
+pub(crate) fn find_case_insensitive(haystack: &str, needle: &str) -> Option<(usize, usize)> {
+    if needle.is_empty() {
+        return Some((0, 0));
+    }
+    for (start, _) in haystack.char_indices() {
+        let mut hay_chars = haystack[start..].char_indices();
+        let mut matched_end = None;
+        for needle_char in needle.chars() {
+            match hay_chars.next() {
+                Some((offset, hay_char)) if hay_char.to_lowercase().eq(needle_char.to_lowercase()) => {
+                    matched_end = Some(start + offset + hay_char.len_utf8());
+                },
+                _ => {
+                    matched_end = None;
+                    break;
+                },
+            }
+        }
+        if let Some(end) = matched_end {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+// parses a time like "90", "1:30" or "1:02:30.5" into a duration
+pub(crate) fn parse_timestamp(text: &str) -> Option<std::time::Duration> {
+    let mut total_secs = 0.0;
+    let parts: Vec<&str> = text.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    for (i, part) in parts.iter().enumerate() {
+        // only the seconds can have a fraction
+        let value: f64 = if i + 1 == parts.len() {
+            part.parse().ok()?
+        } else {
+            part.parse::<u64>().ok()? as f64
+        };
+        if value < 0.0 || !value.is_finite() {
+            return None;
+        }
+        total_secs = total_secs * 60.0 + value;
+    }
+    // a time too long for a duration isn't one that can be in a file anyway
+    std::time::Duration::try_from_secs_f64(total_secs).ok()
+}
+
+// reads a range of a file like '1:05-1:20'. the start can be left off to start at
+// the beginning and the end to go to the end of the file.
+pub(crate) fn parse_trim_range(text: &str) -> Option<TrimRegion> {
+    let (start, end) = text.split_once('-')?;
+    let start = match start.trim() {
+        "" => std::time::Duration::ZERO,
+        start => parse_timestamp(start)?,
+    };
+    let end = match end.trim() {
+        "" => None,
+        end => Some(parse_timestamp(end)?),
+    };
+    Some(TrimRegion { start, end })
+}
+
+// builds a random permutation of the file indices from 0 to `count`, leaving out
+// `exclude` which is the file that just played.
+pub(crate) fn shuffled_file_indices(files: std::ops::Range<usize>, exclude: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = files.filter(|i| *i != exclude).collect();
+    indices.shuffle(&mut rand::thread_rng());
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lists a directory with two subdirectories and three files of different sizes
+    fn listed_fixture() -> (tempfile::TempDir, AppState) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.wav"), vec![0u8; 30]).unwrap();
+        fs::write(dir.path().join("A.wav"), vec![0u8; 10]).unwrap();
+        fs::write(dir.path().join("c.wav"), vec![0u8; 20]).unwrap();
+        fs::create_dir(dir.path().join("zdir")).unwrap();
+        fs::create_dir(dir.path().join("adir")).unwrap();
+        let mut app_state = AppState::default();
+        app_state.set_current_directory(dir.path());
+        app_state.update_file_names();
+        (dir, app_state)
+    }
+
+    fn listed_names(app_state: &AppState) -> Vec<String> {
+        app_state.visible_entries.iter().map(|&e| app_state.entries[e].display_name().to_string()).collect()
+    }
+
+    #[test]
+    fn directories_are_listed_before_files_by_name() {
+        let (_dir, app_state) = listed_fixture();
+        assert_eq!(listed_names(&app_state), ["..", "adir", "zdir", "A.wav", "b.wav", "c.wav"]);
+    }
+
+    #[test]
+    fn sort_modes_order_the_files() {
+        let (_dir, mut app_state) = listed_fixture();
+        app_state.sort_mode = SortMode::NameDescending;
+        app_state.resort_entries();
+        assert_eq!(listed_names(&app_state), ["..", "zdir", "adir", "c.wav", "b.wav", "A.wav"]);
+        // directories don't have a size to sort by, so they stay in name order
+        app_state.sort_mode = SortMode::SizeLargest;
+        app_state.resort_entries();
+        assert_eq!(listed_names(&app_state), ["..", "adir", "zdir", "b.wav", "c.wav", "A.wav"]);
+    }
+
+    #[test]
+    fn resorting_keeps_the_selected_entry() {
+        let (_dir, mut app_state) = listed_fixture();
+        app_state.select_list_item(3);
+        assert_eq!(app_state.selected_file_path().unwrap().file_name().unwrap(), "A.wav");
+        app_state.cycle_sort_mode();
+        assert_eq!(app_state.selected_file_path().unwrap().file_name().unwrap(), "A.wav");
+        assert_eq!(app_state.file_list_state.selected(), Some(5));
+    }
+
+    #[test]
+    fn selection_wraps_when_stepping_past_either_end() {
+        let (_dir, mut app_state) = listed_fixture();
+        app_state.select_list_item(5);
+        app_state.next_list_item();
+        assert_eq!(app_state.file_list_state.selected(), Some(0));
+        app_state.previous_list_item();
+        assert_eq!(app_state.file_list_state.selected(), Some(5));
+    }
+
+    #[test]
+    fn selection_stops_at_either_end_when_moved_by_more() {
+        let (_dir, mut app_state) = listed_fixture();
+        app_state.select_list_item(2);
+        app_state.move_list_selection_by(10);
+        assert_eq!(app_state.file_list_state.selected(), Some(5));
+        app_state.move_list_selection_by(-10);
+        assert_eq!(app_state.file_list_state.selected(), Some(0));
+        app_state.select_row_clamped(99);
+        assert_eq!(app_state.file_list_state.selected(), Some(5));
+        app_state.select_list_item(99);
+        assert_eq!(app_state.file_list_state.selected(), Some(5));
+    }
+
+    #[test]
+    fn the_filter_narrows_the_list_but_keeps_the_parent() {
+        let (_dir, mut app_state) = listed_fixture();
+        app_state.set_filter("B.W");
+        assert_eq!(listed_names(&app_state), ["..", "b.wav"]);
+        assert_eq!(app_state.file_list_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn a_file_that_cant_be_probed_is_a_decode_error() {
+        let (_dir, mut app_state) = listed_fixture();
+        app_state.file_list_state.select(Some(3));
+        assert!(matches!(app_state.update_selected_file_info(), Err(SpinupError::Decode(_))));
+        // selecting one shows why
+        app_state.select_list_item(4);
+        assert!(app_state.last_error_msg.starts_with("File Info Error: "));
+        // directories have nothing to probe
+        app_state.file_list_state.select(Some(1));
+        assert!(app_state.update_selected_file_info().is_ok());
+    }
+
+    #[test]
+    fn navigating_an_empty_list_selects_nothing() {
+        let mut app_state = AppState::default();
+        app_state.apply_filter();
+        assert!(app_state.visible_entries.is_empty());
+
+        app_state.next_list_item();
+        app_state.previous_list_item();
+        app_state.move_list_selection_by(5);
+        app_state.move_list_selection_by(-5);
+        app_state.select_row_clamped(3);
+        app_state.select_list_item(0);
+        assert_eq!(app_state.file_list_state.selected(), None);
+        assert_eq!(app_state.selected_entry(), None);
+        assert_eq!(app_state.selected_file_path(), None);
+        assert!(!app_state.is_file_selected());
+    }
+
+    #[test]
+    fn timestamps_parse() {
+        assert_eq!(parse_timestamp("90"), Some(std::time::Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1:30"), Some(std::time::Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1:02:30.5"), Some(std::time::Duration::from_secs_f64(3750.5)));
+        assert_eq!(parse_timestamp(" 0:05 "), Some(std::time::Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn bad_timestamps_are_rejected() {
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("-5"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("1.5:00"), None);
+        assert_eq!(parse_timestamp("inf"), None);
+        assert_eq!(parse_timestamp("NaN"), None);
+    }
+
+    #[test]
+    fn timestamps_too_long_for_a_duration_are_rejected() {
+        assert_eq!(parse_timestamp("1e30"), None);
+        assert_eq!(parse_timestamp("1e300"), None);
+        assert_eq!(parse_timestamp("18446744073709551615:0"), None);
+        assert_eq!(parse_timestamp("18446744073709551615:0:0"), None);
+    }
+
+    #[test]
+    fn trim_ranges_parse() {
+        let region = parse_trim_range("1:05-1:20").unwrap();
+        assert_eq!(region.start, std::time::Duration::from_secs(65));
+        assert_eq!(region.end, Some(std::time::Duration::from_secs(80)));
+        let region = parse_trim_range("-0:10").unwrap();
+        assert_eq!(region.start, std::time::Duration::ZERO);
+        let region = parse_trim_range("0:10-").unwrap();
+        assert_eq!(region.end, None);
+        assert!(parse_trim_range("0-1e300").is_none());
+        assert!(parse_trim_range("1e30-").is_none());
+    }
+
+    #[test]
+    fn idle_state_has_no_background_work() {
+        let mut app_state = AppState::default();
+        assert!(!app_state.has_background_work());
+        app_state.preview_due = Some(std::time::Instant::now());
+        assert!(app_state.has_background_work());
+    }
+
+    #[test]
+    fn refused_actions_say_why_by_their_kind() {
+        let (dir, mut app_state) = listed_fixture();
+        app_state.bookmarks = vec![dir.path().join("gone")];
+        assert!(matches!(app_state.jump_to_bookmark(0), Err(SpinupError::InvalidPath { .. })));
+        assert!(matches!(app_state.jump_to_bookmark(1), Err(SpinupError::InvalidRequest(_))));
+        let result = app_state.delete_files(&[dir.path().join("missing.wav")], true);
+        assert!(matches!(result, Err(SpinupError::FileOperation(msg)) if msg.contains("missing.wav")));
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// reads the optional config file and applies what it sets, with the command line
+// taking precedence over it.
+
+use std::error::Error;
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Args, MAX_SETTING_SECS};
+use crate::app::SUPPORTED_EXTS;
+use crate::export::EXPORT_BIT_DEPTHS;
+use crate::input::{SEEK_LONG_STEP_SECS, SEEK_STEP_SECS};
+use crate::keymap;
+use crate::player::DEFAULT_STREAM_THRESHOLD;
+use crate::theme::ThemeKind;
+
+// the file in the config directory that settings and key bindings are read from
+pub(crate) const CONFIG_FILE_NAME: &str = "config.toml";
+
+// the settings that can come from the config file or the command line. whatever
+// neither of them sets keeps its default.
+#[derive(Clone)]
+pub(crate) struct Settings {
+    pub(crate) dir: Option<PathBuf>, // the starting directory when no path is given
+    pub(crate) theme: ThemeKind,
+    pub(crate) volume: u32, // in percent from 0 to 200
+    pub(crate) seek_step_secs: f64,
+    pub(crate) seek_long_step_secs: f64,
+    pub(crate) fade_ms: u64,
+    pub(crate) fade_in_ms: u64,
+    pub(crate) crossfade_ms: u64,
+    pub(crate) preview_secs: f64,
+    pub(crate) tick_ms: u64, // how often the interface is redrawn while something changes
+    pub(crate) scan_depth: usize,
+    pub(crate) scan_max_files: usize,
+    pub(crate) extra_extensions: Vec<String>, // listed along with the built in ones, without the dot
+    pub(crate) only_extensions: Option<Vec<String>>, // listed instead of the built in and extra ones
+    pub(crate) probe_content: bool, // files are listed by what their content probes as, not just their extension
+    pub(crate) ratings_in_dirs: bool, // ratings are saved in a file in each directory instead of the data directory
+    pub(crate) show_hidden: bool,
+    pub(crate) tree_layout: bool, // wide terminals show a directory tree beside the files
+    pub(crate) permanent_delete: bool,
+    pub(crate) trim_bit_depth: u16, // the bits per sample of trimmed wavs from lossy files
+    pub(crate) convert_bit_depth: u16, // the bits per sample of files converted from lossy ones
+    pub(crate) stream_threshold_mb: u64,
+    pub(crate) open_command: Option<Vec<String>>,
+    pub(crate) open_dir_command: Option<Vec<String>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            dir: None,
+            theme: ThemeKind::Dark,
+            volume: 100,
+            seek_step_secs: SEEK_STEP_SECS,
+            seek_long_step_secs: SEEK_LONG_STEP_SECS,
+            fade_ms: 250,
+            fade_in_ms: 0,
+            crossfade_ms: 0,
+            preview_secs: 3.0,
+            tick_ms: 66, // roughly 15fps
+            scan_depth: 8,
+            scan_max_files: 10000,
+            extra_extensions: vec![],
+            only_extensions: None,
+            probe_content: false,
+            ratings_in_dirs: false,
+            show_hidden: false,
+            tree_layout: true,
+            permanent_delete: false,
+            trim_bit_depth: 24,
+            convert_bit_depth: 16,
+            stream_threshold_mb: DEFAULT_STREAM_THRESHOLD / (1024 * 1024),
+            open_command: None,
+            open_dir_command: None,
+        }
+    }
+}
+
+impl Settings {
+    // takes the settings from the config file, returning a warning naming the key and
+    // its line for each one that can't be used. those keep their defaults.
+    pub(crate) fn apply_config(&mut self, config: &toml::Value, text: &str) -> Vec<String> {
+        let mut warnings = vec![];
+        let table = match config.as_table() {
+            Some(table) => table,
+            None => return warnings,
+        };
+        for (key, value) in table {
+            let result = match key.as_str() {
+                "keys" => Ok(()),
+                "dir" => value.as_str()
+                    .map(|dir| self.dir = Some(PathBuf::from(dir)))
+                    .ok_or("has to be a path"),
+                "theme" => value.as_str()
+                    .and_then(|name| name.parse().ok())
+                    .map(|theme| self.theme = theme)
+                    .ok_or("has to be dark, light or monochrome"),
+                "volume" => config_integer(value, 0, 200)
+                    .map(|volume| self.volume = volume as u32)
+                    .ok_or("has to be a whole number from 0 to 200"),
+                "seek_step_secs" => config_seconds(value)
+                    .map(|secs| self.seek_step_secs = secs)
+                    .ok_or("has to be a number of seconds up to an hour"),
+                "seek_long_step_secs" => config_seconds(value)
+                    .map(|secs| self.seek_long_step_secs = secs)
+                    .ok_or("has to be a number of seconds up to an hour"),
+                "fade_ms" => config_integer(value, 0, i64::MAX)
+                    .map(|ms| self.fade_ms = ms as u64)
+                    .ok_or("has to be a whole number of milliseconds"),
+                "fade_in_ms" => config_integer(value, 0, i64::MAX)
+                    .map(|ms| self.fade_in_ms = ms as u64)
+                    .ok_or("has to be a whole number of milliseconds"),
+                "crossfade_ms" => config_integer(value, 0, i64::MAX)
+                    .map(|ms| self.crossfade_ms = ms as u64)
+                    .ok_or("has to be a whole number of milliseconds"),
+                "preview_secs" => config_seconds(value)
+                    .map(|secs| self.preview_secs = secs)
+                    .ok_or("has to be a number of seconds up to an hour"),
+                "tick_ms" => config_integer(value, 1, 1000)
+                    .map(|ms| self.tick_ms = ms as u64)
+                    .ok_or("has to be a whole number of milliseconds from 1 to 1000"),
+                "scan_depth" => config_integer(value, 0, i64::MAX)
+                    .map(|depth| self.scan_depth = depth as usize)
+                    .ok_or("has to be a whole number"),
+                "scan_max_files" => config_integer(value, 0, i64::MAX)
+                    .map(|count| self.scan_max_files = count as usize)
+                    .ok_or("has to be a whole number"),
+                "extra_extensions" => parse_extensions_config(value)
+                    .map(|exts| self.extra_extensions = exts)
+                    .ok_or("has to be a list of extensions like [\"mka\", \"wv\"]"),
+                "only_extensions" => parse_extensions_config(value)
+                    .map(|exts| self.only_extensions = Some(exts))
+                    .ok_or("has to be a list of extensions like [\"wav\"]"),
+                "probe_content" => value.as_bool()
+                    .map(|probe| self.probe_content = probe)
+                    .ok_or("has to be true or false"),
+                "ratings_in_dirs" => value.as_bool()
+                    .map(|in_dirs| self.ratings_in_dirs = in_dirs)
+                    .ok_or("has to be true or false"),
+                "show_hidden" => value.as_bool()
+                    .map(|show| self.show_hidden = show)
+                    .ok_or("has to be true or false"),
+                "tree_layout" => value.as_bool()
+                    .map(|tree| self.tree_layout = tree)
+                    .ok_or("has to be true or false"),
+                "permanent_delete" => value.as_bool()
+                    .map(|permanent| self.permanent_delete = permanent)
+                    .ok_or("has to be true or false"),
+                "trim_bit_depth" => config_integer(value, 16, 24)
+                    .filter(|bits| EXPORT_BIT_DEPTHS.contains(&(*bits as u16)))
+                    .map(|bits| self.trim_bit_depth = bits as u16)
+                    .ok_or("has to be 16 or 24"),
+                "convert_bit_depth" => config_integer(value, 16, 24)
+                    .filter(|bits| EXPORT_BIT_DEPTHS.contains(&(*bits as u16)))
+                    .map(|bits| self.convert_bit_depth = bits as u16)
+                    .ok_or("has to be 16 or 24"),
+                "stream_threshold_mb" => config_integer(value, 0, i64::MAX)
+                    .map(|mb| self.stream_threshold_mb = mb as u64)
+                    .ok_or("has to be a whole number of megabytes"),
+                "open_command" => parse_command_config(value)
+                    .map(|command| self.open_command = Some(command))
+                    .ok_or("has to be a command line or a list of its arguments"),
+                "open_dir_command" => parse_command_config(value)
+                    .map(|command| self.open_dir_command = Some(command))
+                    .ok_or("has to be a command line or a list of its arguments"),
+                _ => Err("isn't a setting"),
+            };
+            if let Err(problem) = result {
+                warnings.push(format!("{}{} {}", key, key_line_label(text, key), problem));
+            }
+        }
+        warnings
+    }
+
+    // takes whatever was given on the command line over the config
+    pub(crate) fn apply_args(&mut self, args: &Args) {
+        if args.dir.is_some() {
+            self.dir = args.dir.clone();
+        }
+        self.theme = args.theme.unwrap_or(self.theme);
+        self.volume = args.volume.unwrap_or(self.volume);
+        self.fade_ms = args.fade_ms.unwrap_or(self.fade_ms);
+        self.fade_in_ms = args.fade_in_ms.unwrap_or(self.fade_in_ms);
+        self.crossfade_ms = args.crossfade_ms.unwrap_or(self.crossfade_ms);
+        self.preview_secs = args.preview_secs.unwrap_or(self.preview_secs);
+        self.scan_depth = args.scan_depth.unwrap_or(self.scan_depth);
+        self.scan_max_files = args.scan_max_files.unwrap_or(self.scan_max_files);
+        if let Some(exts) = &args.ext {
+            self.extra_extensions = split_extensions(exts);
+        }
+        if let Some(exts) = &args.only_ext {
+            self.only_extensions = Some(split_extensions(exts));
+        }
+        if args.probe_content {
+            self.probe_content = true;
+        }
+        if args.ratings_in_dirs {
+            self.ratings_in_dirs = true;
+        }
+    }
+
+    // writes the settings out the way the config file takes them, with the settings
+    // that aren't set commented out
+    pub(crate) fn to_config_text(&self) -> String {
+        let command_value = |command: &Option<Vec<String>>| command.as_ref()
+            .map(|args| toml::Value::Array(args.iter().cloned().map(toml::Value::String).collect()));
+        let values: [(&str, Option<toml::Value>); 24] = [
+            ("dir", self.dir.as_ref().map(|dir| toml::Value::String(dir.display().to_string()))),
+            ("theme", Some(toml::Value::String(self.theme.name().to_string()))),
+            ("volume", Some(toml::Value::Integer(self.volume as i64))),
+            ("seek_step_secs", Some(toml::Value::Float(self.seek_step_secs))),
+            ("seek_long_step_secs", Some(toml::Value::Float(self.seek_long_step_secs))),
+            ("fade_ms", Some(toml::Value::Integer(self.fade_ms as i64))),
+            ("fade_in_ms", Some(toml::Value::Integer(self.fade_in_ms as i64))),
+            ("crossfade_ms", Some(toml::Value::Integer(self.crossfade_ms as i64))),
+            ("preview_secs", Some(toml::Value::Float(self.preview_secs))),
+            ("tick_ms", Some(toml::Value::Integer(self.tick_ms as i64))),
+            ("scan_depth", Some(toml::Value::Integer(self.scan_depth as i64))),
+            ("scan_max_files", Some(toml::Value::Integer(self.scan_max_files as i64))),
+            ("extra_extensions", Some(toml::Value::Array(self.extra_extensions.iter().cloned().map(toml::Value::String).collect()))),
+            ("only_extensions", self.only_extensions.as_ref().map(|exts| toml::Value::Array(exts.iter().cloned().map(toml::Value::String).collect()))),
+            ("probe_content", Some(toml::Value::Boolean(self.probe_content))),
+            ("ratings_in_dirs", Some(toml::Value::Boolean(self.ratings_in_dirs))),
+            ("show_hidden", Some(toml::Value::Boolean(self.show_hidden))),
+            ("tree_layout", Some(toml::Value::Boolean(self.tree_layout))),
+            ("permanent_delete", Some(toml::Value::Boolean(self.permanent_delete))),
+            ("trim_bit_depth", Some(toml::Value::Integer(self.trim_bit_depth as i64))),
+            ("convert_bit_depth", Some(toml::Value::Integer(self.convert_bit_depth as i64))),
+            ("stream_threshold_mb", Some(toml::Value::Integer(self.stream_threshold_mb as i64))),
+            ("open_command", command_value(&self.open_command)),
+            ("open_dir_command", command_value(&self.open_dir_command)),
+        ];
+        values.iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{} = {}\n", name, value),
+                None => format!("# {} isn't set\n", name),
+            })
+            .collect()
+    }
+
+    // the extensions of the files that get listed, or None for the supported ones.
+    // the only extensions replace the others entirely.
+    pub(crate) fn listed_extensions(&self) -> Option<Vec<String>> {
+        if let Some(only) = &self.only_extensions {
+            return Some(only.clone());
+        }
+        if self.extra_extensions.is_empty() {
+            return None;
+        }
+        let mut extensions: Vec<String> = SUPPORTED_EXTS.iter().map(|ext| ext.to_string()).collect();
+        for extra in &self.extra_extensions {
+            if !extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extra)) {
+                extensions.push(extra.clone());
+            }
+        }
+        Some(extensions)
+    }
+}
+
+// splits a comma separated list of extensions from the command line, dropping a
+// leading dot from any of them
+pub(crate) fn split_extensions(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|ext| ext.trim().trim_start_matches('.'))
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// reads an integer setting that has to be from `min` to `max`
+fn config_integer(value: &toml::Value, min: i64, max: i64) -> Option<i64> {
+    value.as_integer().filter(|n| (min..=max).contains(n))
+}
+
+// reads a setting in seconds, which can be written as an integer or a float, and
+// can't be more than an hour
+fn config_seconds(value: &toml::Value) -> Option<f64> {
+    let secs = value.as_float().or_else(|| value.as_integer().map(|n| n as f64))?;
+    if (0.0..=MAX_SETTING_SECS).contains(&secs) { Some(secs) } else { None }
+}
+
+// reads a list of file extensions, dropping a leading dot from any of them
+pub(crate) fn parse_extensions_config(value: &toml::Value) -> Option<Vec<String>> {
+    value.as_array()?.iter()
+        .map(|item| item.as_str().map(|ext| ext.trim_start_matches('.').to_string()))
+        .filter(|ext| !matches!(ext, Some(ext) if ext.is_empty()))
+        .collect()
+}
+
+// returns " (line n)" for the line that sets the top level `key`, or nothing if
+// it can't be found
+fn key_line_label(text: &str, key: &str) -> String {
+    let line = text.lines().position(|line| {
+        let line = line.trim_start();
+        let rest = line.strip_prefix(key)
+            .or_else(|| line.strip_prefix(&format!("\"{}\"", key)));
+        matches!(rest, Some(rest) if rest.trim_start().starts_with('='))
+    });
+    match line {
+        Some(index) => format!(" (line {})", index + 1),
+        None => String::new(),
+    }
+}
+
+// loads the config file from `config_path`, or the usual place when it's None, and
+// merges the command line over it. problems with the file are returned as warnings
+// so a typo can't keep spinup from starting, and only the broken settings are left
+// at their defaults; a file that can't be parsed at all is ignored entirely.
+pub(crate) fn load_settings(args: &Args) -> (Settings, Option<toml::Value>, Vec<String>) {
+    let mut settings = Settings::default();
+    let mut warnings = vec![];
+    let config = match load_config(args.config.as_deref()) {
+        Ok(Some((config, text))) => {
+            warnings = settings.apply_config(&config, &text);
+            Some(config)
+        },
+        Ok(None) => None,
+        Err(err) => {
+            warnings.push(err.to_string());
+            None
+        },
+    };
+    settings.apply_args(args);
+    (settings, config, warnings)
+}
+
+// reads a command from the config, either a command line split on whitespace or a
+// list of the program and its arguments. returns None if it isn't either.
+pub(crate) fn parse_command_config(value: &toml::Value) -> Option<Vec<String>> {
+    let args: Vec<String> = match value {
+        toml::Value::String(line) => line.split_whitespace().map(str::to_string).collect(),
+        toml::Value::Array(items) => items.iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    if args.is_empty() { None } else { Some(args) }
+}
+
+// returns the directory spinup's config file is in, following the XDG base directory
+// spec and falling back to ~/.config
+pub(crate) fn app_config_dir() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(&std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("spinup"))
+}
+
+// reads the config file at `path`, or the one in the config directory when there's
+// no path, returning None if there isn't one. the text is returned along with the
+// parsed file for finding the lines settings are on.
+pub(crate) fn load_config(path: Option<&Path>) -> Result<Option<(toml::Value, String)>, Box<dyn Error>> {
+    let config_path = match (path, app_config_dir()) {
+        (Some(path), _) => path.to_path_buf(),
+        (None, Some(dir)) => dir.join(CONFIG_FILE_NAME),
+        (None, None) => return Ok(None),
+    };
+    let text = match fs::read_to_string(&config_path) {
+        // a config asked for by path has to be there
+        Err(err) if err.kind() == io::ErrorKind::NotFound && path.is_none() => return Ok(None),
+        Err(err) => return Err(format!("{}: {}", config_path.display(), err).into()),
+        Ok(text) => text,
+    };
+    let config = text.parse::<toml::Value>()
+        .map_err(|err| match err.line_col() {
+            Some((line, _)) => format!("{}: line {} ('{}'): {}, using the defaults", config_path.display(), line + 1, text.lines().nth(line).unwrap_or("").trim(), err),
+            None => format!("{}: {}, using the defaults", config_path.display(), err),
+        })?;
+    Ok(Some((config, text)))
+}
+
+// rebinds the actions named in the `[keys]` table of the config, where each one
+// maps to a key spec or a list of them. returns what couldn't be applied.
+pub(crate) fn apply_key_config(keymap: &mut keymap::Keymap, config: &toml::Value) -> Vec<String> {
+    let mut warnings = vec![];
+    let keys = match config.get("keys") {
+        Some(toml::Value::Table(keys)) => keys,
+        Some(_) => return vec!["[keys] has to be a table".to_string()],
+        None => return warnings,
+    };
+    for (action_name, value) in keys {
+        let specs: Vec<&str> = match value {
+            toml::Value::String(spec) => vec![spec.as_str()],
+            toml::Value::Array(specs) if specs.iter().all(|v| v.is_str()) => specs.iter().filter_map(|v| v.as_str()).collect(),
+            _ => {
+                warnings.push(format!("keys for '{}' have to be a string or a list of strings", action_name));
+                continue;
+            },
+        };
+        if let Err(err) = keymap.rebind(action_name, &specs) {
+            warnings.push(err);
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str) -> (Settings, Vec<String>) {
+        let mut settings = Settings::default();
+        let warnings = settings.apply_config(&text.parse().unwrap(), text);
+        (settings, warnings)
+    }
+
+    #[test]
+    fn seconds_can_be_integers_or_floats() {
+        let (settings, warnings) = apply("preview_secs = 10\nseek_step_secs = 2.5\n");
+        assert!(warnings.is_empty());
+        assert_eq!(settings.preview_secs, 10.0);
+        assert_eq!(settings.seek_step_secs, 2.5);
+    }
+
+    #[test]
+    fn seconds_past_an_hour_are_warned_about() {
+        let (settings, warnings) = apply("volume = 50\npreview_secs = 1e300\nseek_long_step_secs = inf\nseek_step_secs = -1\n");
+        assert_eq!(settings.preview_secs, Settings::default().preview_secs);
+        assert_eq!(settings.seek_long_step_secs, SEEK_LONG_STEP_SECS);
+        assert_eq!(settings.seek_step_secs, SEEK_STEP_SECS);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings.contains(&"preview_secs (line 2) has to be a number of seconds up to an hour".to_string()));
+        assert!(warnings.contains(&"seek_long_step_secs (line 3) has to be a number of seconds up to an hour".to_string()));
+        assert!(warnings.contains(&"seek_step_secs (line 4) has to be a number of seconds up to an hour".to_string()));
+    }
+}
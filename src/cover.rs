@@ -0,0 +1,137 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the cover art embedded in a sound file's tags, shrunk down to a thumbnail that the
+// info pane draws with half-block characters in the 256 color palette.
+
+use std::path::Path;
+
+use symphonia::core::meta::{MetadataRevision, StandardVisualKey, Visual};
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+
+// the thumbnail is shrunk to fit in a square this many pixels across, which is as
+// wide as the widest info pane
+pub(crate) const COVER_THUMBNAIL_SIZE: u32 = 48;
+
+// the steps of each channel in the 6x6x6 color cube of the 256 color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// a file's cover art, as far as it could be read
+pub(crate) struct CoverArt {
+    pub(crate) format: String, // the image format, like "jpeg" or "png"
+    pub(crate) size: Option<(u32, u32)>, // the full size of the picture, when it's known
+    pub(crate) thumbnail: Option<image::RgbImage>, // None when the picture couldn't be decoded
+}
+
+impl CoverArt {
+    // describes the picture for when there's no room or no colors to draw it
+    pub(crate) fn label(&self) -> String {
+        match self.size {
+            Some((width, height)) => format!("[cover art: {}x{} {}]", width, height, self.format),
+            None => format!("[cover art: {}]", self.format),
+        }
+    }
+
+    // draws the thumbnail `width` columns wide, two pixels to a character. returns
+    // nothing when the picture couldn't be decoded.
+    pub(crate) fn thumbnail_lines(&self, width: u16) -> Vec<Spans<'static>> {
+        let thumbnail = match &self.thumbnail {
+            Some(thumbnail) if thumbnail.width() > 0 && thumbnail.height() > 0 => thumbnail,
+            _ => return vec![],
+        };
+        let columns = (width as u32).min(thumbnail.width()).max(1);
+        let pixel_rows = (thumbnail.height() * columns / thumbnail.width()).max(2);
+        let pixel = |x: u32, y: u32| {
+            let px = (x * thumbnail.width() / columns).min(thumbnail.width() - 1);
+            let py = (y * thumbnail.height() / pixel_rows).min(thumbnail.height() - 1);
+            palette_color(thumbnail.get_pixel(px, py).0)
+        };
+        (0..pixel_rows / 2)
+            .map(|row| {
+                let spans: Vec<Span> = (0..columns)
+                    .map(|x| Span::styled("▀", Style::default().fg(pixel(x, row * 2)).bg(pixel(x, row * 2 + 1))))
+                    .collect();
+                Spans::from(spans)
+            })
+            .collect()
+    }
+}
+
+// reads the cover art out of a sound file and decodes it into a thumbnail, or None
+// if the file doesn't have any. this reads the whole picture, so it's done off the
+// main thread.
+pub(crate) fn read_cover_art(snd_path: &Path) -> Option<CoverArt> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path).ok()?), Default::default());
+    let mut probed = probe
+        .format(
+            &Default::default(),
+            mss,
+            &Default::default(),
+            &Default::default(),
+        )
+        .ok()?;
+
+    // the pictures inside the container win over ones in tags in front of it, like
+    // they do for the other tags
+    let mut visual = probed.format.metadata().current().and_then(front_cover);
+    if visual.is_none() {
+        if let Some(outer) = probed.metadata.get() {
+            visual = outer.current().and_then(front_cover);
+        }
+    }
+    let visual = visual?;
+
+    let format = visual.media_type.strip_prefix("image/").unwrap_or(&visual.media_type).to_string();
+    let hinted_size = visual.dimensions.map(|size| (size.width, size.height));
+    let cover = match image::load_from_memory(&visual.data) {
+        Ok(picture) => CoverArt {
+            format,
+            size: Some((picture.width(), picture.height())),
+            thumbnail: Some(picture.thumbnail(COVER_THUMBNAIL_SIZE, COVER_THUMBNAIL_SIZE).to_rgb8()),
+        },
+        Err(_) => CoverArt { format, size: hinted_size, thumbnail: None },
+    };
+    Some(cover)
+}
+
+// the picture to use from a set of tags: the front cover if it's marked, otherwise
+// the first one
+fn front_cover(revision: &MetadataRevision) -> Option<Visual> {
+    let visuals = revision.visuals();
+    visuals.iter()
+        .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+        .or_else(|| visuals.first())
+        .cloned()
+}
+
+// the closest color in the 256 color palette, from either the color cube or the
+// gray ramp, since plenty of terminals can't show any color
+fn palette_color(rgb: [u8; 3]) -> Color {
+    let nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - value as i32).abs())
+            .unwrap_or(0)
+    };
+    let cube = rgb.map(nearest_level);
+    let cube_rgb = cube.map(|i| CUBE_LEVELS[i]);
+    let cube_index = 16 + 36 * cube[0] + 6 * cube[1] + cube[2];
+
+    // the gray ramp runs from 8 to 238 in steps of 10
+    let average = rgb.iter().map(|&c| c as u32).sum::<u32>() / 3;
+    let gray_step = ((average as i32 - 8 + 5) / 10).clamp(0, 23);
+    let gray = (8 + gray_step * 10) as u8;
+    let gray_index = 232 + gray_step as usize;
+
+    let distance = |other: [u8; 3]| -> i32 {
+        rgb.iter().zip(other.iter())
+            .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+            .sum()
+    };
+    if distance([gray, gray, gray]) < distance(cube_rgb) {
+        Color::Indexed(gray_index as u8)
+    } else {
+        Color::Indexed(cube_index as u8)
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the audio output devices sounds can be played on. kira's own cpal backend only
+// ever opens the system default, so this is a backend of our own that opens the
+// device picked by name and otherwise works the same way.
+
+use std::error::Error;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, Stream, StreamConfig};
+use kira::manager::backend::{Backend, Renderer};
+
+// returns the names of every output device the audio host knows about
+pub(crate) fn output_device_names() -> Result<Vec<String>, Box<dyn Error>> {
+    let names = cpal::default_host().output_devices()?
+        .filter_map(|device| device.name().ok())
+        .collect();
+    Ok(names)
+}
+
+// returns the name of the device sounds play on when none was picked
+pub(crate) fn default_device_name() -> Option<String> {
+    cpal::default_host().default_output_device().and_then(|device| device.name().ok())
+}
+
+// describes every output device of every audio host, grouped by host, with the
+// sample rate and channels each opens with by default. hosts without output
+// devices are left out.
+pub(crate) fn describe_output_devices() -> Vec<String> {
+    let mut lines = vec![];
+    for host_id in cpal::available_hosts() {
+        let host = match cpal::host_from_id(host_id) {
+            Ok(host) => host,
+            Err(_) => continue,
+        };
+        let devices = match host.output_devices() {
+            Ok(devices) => devices,
+            Err(_) => continue,
+        };
+        let default_name = host.default_output_device().and_then(|device| device.name().ok());
+        let mut device_lines = vec![];
+        for device in devices {
+            let name = match device.name() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let marker = if default_name.as_deref() == Some(name.as_str()) { " (default)" } else { "" };
+            let config = match device.default_output_config() {
+                Ok(config) => format!("{} Hz, {} channels", config.sample_rate().0, config.channels()),
+                Err(err) => format!("unusable: {}", err),
+            };
+            device_lines.push(format!("  {}{} - {}", name, marker, config));
+        }
+        if !device_lines.is_empty() {
+            lines.push(format!("{}:", host_id.name()));
+            lines.append(&mut device_lines);
+        }
+    }
+    lines
+}
+
+// finds the output device called `name`, ignoring case. the error lists the
+// devices there are so the right name can be picked from it.
+pub(crate) fn find_output_device(name: &str) -> Result<Device, Box<dyn Error>> {
+    let mut available = vec![];
+    for device in cpal::default_host().output_devices()? {
+        let device_name = match device.name() {
+            Ok(device_name) => device_name,
+            Err(_) => continue,
+        };
+        if device_name.to_lowercase() == name.to_lowercase() {
+            return Ok(device);
+        }
+        available.push(device_name);
+    }
+    if available.is_empty() {
+        return Err(format!("there's no output device named '{}', and no output devices were found", name).into());
+    }
+    Err(format!("there's no output device named '{}', the output devices are:\n  {}", name, available.join("\n  ")).into())
+}
+
+// a kira backend playing through the output device named in its settings, or the
+// default device when there's no name
+pub(crate) struct DeviceBackend {
+    device: Device,
+    config: StreamConfig,
+    stream: Option<Stream>,
+}
+
+impl Backend for DeviceBackend {
+    type Settings = Option<String>;
+
+    type Error = Box<dyn Error>;
+
+    fn setup(settings: Self::Settings) -> Result<(Self, u32), Self::Error> {
+        let device = match settings {
+            Some(name) => find_output_device(&name)?,
+            None => cpal::default_host().default_output_device().ok_or("Cannot find the default audio output device")?,
+        };
+        let config = device.default_output_config()?.config();
+        let sample_rate = config.sample_rate.0;
+        Ok((DeviceBackend { device, config, stream: None }, sample_rate))
+    }
+
+    fn start(&mut self, mut renderer: Renderer) -> Result<(), Self::Error> {
+        let channels = self.config.channels as usize;
+        let stream = self.device.build_output_stream(
+            &self.config,
+            move |data: &mut [f32], _| {
+                renderer.on_start_processing();
+                for frame in data.chunks_exact_mut(channels) {
+                    let out = renderer.process();
+                    if channels == 1 {
+                        frame[0] = (out.left + out.right) / 2.0;
+                    } else {
+                        frame[0] = out.left;
+                        frame[1] = out.right;
+                        // anything past stereo is left silent
+                        frame[2..].fill(0.0);
+                    }
+                }
+            },
+            // a device that goes away stops calling back, which the stall check
+            // notices, so there's nothing more to do with the error here
+            |_| {},
+        )?;
+        stream.play()?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+}
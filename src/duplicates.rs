@@ -0,0 +1,142 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// finding files with the same contents under different names: byte for byte, or
+// in deep mode by their decoded sound, so the same sound saved as a wav and a flac
+// is found too. the files are hashed on a pool of worker threads.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::player::audio_track;
+
+// hashes `files`, given with their sizes, and returns the groups of them that are
+// the same, each sorted and with the groups in order of their first file. `total`
+// is set to the number of files that need hashing and `done` counts them as they
+// are. returns None once the `cancel` flag gets set.
+pub(crate) fn find_duplicates(
+    files: Vec<(PathBuf, u64)>,
+    deep: bool,
+    cancel: &AtomicBool,
+    total: &AtomicUsize,
+    done: &AtomicUsize,
+) -> Option<Vec<Vec<PathBuf>>> {
+    // only files of the same size can be the same byte for byte, so the rest don't
+    // have to be read at all
+    let candidates: Vec<PathBuf> = if deep {
+        files.into_iter().map(|(path, _)| path).collect()
+    } else {
+        let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for (path, size) in files {
+            by_size.entry(size).or_default().push(path);
+        }
+        by_size.into_values().filter(|same_size| same_size.len() > 1).flatten().collect()
+    };
+    total.store(candidates.len(), Ordering::Relaxed);
+
+    let hashes = hash_files(&candidates, deep, cancel, done)?;
+    let mut by_hash: HashMap<blake3::Hash, Vec<PathBuf>> = HashMap::new();
+    for (path, hash) in candidates.into_iter().zip(hashes) {
+        // files that can't be read or decoded are left out
+        if let Some(hash) = hash {
+            by_hash.entry(hash).or_default().push(path);
+        }
+    }
+    let mut groups: Vec<Vec<PathBuf>> = by_hash.into_values().filter(|group| group.len() > 1).collect();
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    Some(groups)
+}
+
+// hashes every file on as many threads as there are cores, in the order given
+fn hash_files(paths: &[PathBuf], deep: bool, cancel: &AtomicBool, done: &AtomicUsize) -> Option<Vec<Option<blake3::Hash>>> {
+    let next = AtomicUsize::new(0);
+    let hashes = Mutex::new(vec![None; paths.len()]);
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4).min(paths.len().max(1));
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let path = match paths.get(index) {
+                    Some(path) => path,
+                    None => break,
+                };
+                let hash = if deep { hash_decoded(path, cancel) } else { hash_contents(path) };
+                if let Ok(mut hashes) = hashes.lock() {
+                    hashes[index] = hash.ok();
+                }
+                done.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+    });
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+    hashes.into_inner().ok()
+}
+
+// the hash of the bytes of a file
+fn hash_contents(path: &Path) -> Result<blake3::Hash, Box<dyn Error>> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize())
+}
+
+// the hash of the sound of a file once it's decoded, along with its sample rate and
+// channels, which comes out the same for the same sound in any lossless format
+fn hash_decoded(path: &Path, cancel: &AtomicBool) -> Result<blake3::Hash, Box<dyn Error>> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(path)?), Default::default());
+    let mut format_reader = probe
+        .format(&Default::default(), mss, &Default::default(), &Default::default())?
+        .format;
+    let track = audio_track(format_reader.as_ref()).ok_or("the file has no audio track")?;
+    let track_id = track.id;
+    let mut decoder = crate::opus::codecs().make(&track.codec_params, &Default::default())?;
+
+    let mut hasher = blake3::Hasher::new();
+    let mut spec_hashed = false;
+    let mut bytes = vec![];
+    let mut sample_buf: Option<symphonia::core::audio::SampleBuffer<f32>> = None;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("it was cancelled".into());
+        }
+        let packet = match format_reader.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = decoder.decode(&packet)?;
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        if !spec_hashed {
+            hasher.update(&spec.rate.to_le_bytes());
+            hasher.update(&(channels as u32).to_le_bytes());
+            spec_hashed = true;
+        }
+        if !matches!(&sample_buf, Some(buf) if buf.capacity() >= decoded.capacity() * channels) {
+            sample_buf = Some(symphonia::core::audio::SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buf = sample_buf.as_mut().ok_or("the sample buffer is missing")?;
+        buf.copy_interleaved_ref(decoded);
+        bytes.clear();
+        bytes.extend(buf.samples().iter().flat_map(|sample| sample.to_le_bytes()));
+        hasher.update(&bytes);
+    }
+    if !spec_hashed {
+        return Err("the file has no sound in it".into());
+    }
+    Ok(hasher.finalize())
+}
@@ -0,0 +1,129 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the errors that come out of playing and listing files, sorted by what went wrong
+// so callers can tell a file that won't decode from an audio output that went away.
+// they're only turned into text when they're shown.
+
+use std::path::PathBuf;
+
+use kira::manager::error::PlaySoundError;
+use kira::sound::FromFileError;
+use symphonia::core::errors::Error as SymphoniaError;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SpinupError {
+    // reading a file or directory failed
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    // the file isn't sound that can be read, or is damaged
+    #[error("{0}")]
+    Decode(String),
+    // the audio thread wouldn't take a sound or a change to one
+    #[error("{0}")]
+    Playback(String),
+    // the audio output couldn't be opened, or stopped taking sounds
+    #[error("{0}")]
+    AudioBackend(String),
+    // a path that isn't what it needs to be, like a file given for a directory
+    #[error("{} {reason}", path.display())]
+    InvalidPath { path: PathBuf, reason: String },
+    // files being deleted, copied or moved that didn't all go through
+    #[error("{0}")]
+    FileOperation(String),
+    // something asked for that can't be done with what's selected or going on, like
+    // loop markers too close together or an export while another one is running
+    #[error("{0}")]
+    InvalidRequest(String),
+}
+
+impl From<SymphoniaError> for SpinupError {
+    fn from(err: SymphoniaError) -> Self {
+        match err {
+            SymphoniaError::IoError(err) if err.kind() != std::io::ErrorKind::UnexpectedEof => SpinupError::Io(err),
+            err => SpinupError::Decode(err.to_string()),
+        }
+    }
+}
+
+impl From<FromFileError> for SpinupError {
+    fn from(err: FromFileError) -> Self {
+        match err {
+            FromFileError::IoError(err) => SpinupError::Io(err),
+            FromFileError::SymphoniaError(err) => err.into(),
+            err => SpinupError::Decode(err.to_string()),
+        }
+    }
+}
+
+// a full command queue means the audio thread stopped taking commands, which is
+// what happens when its device went away
+impl From<kira::CommandError> for SpinupError {
+    fn from(err: kira::CommandError) -> Self {
+        match err {
+            kira::CommandError::CommandQueueFull => SpinupError::AudioBackend(err.to_string()),
+            err => SpinupError::Playback(err.to_string()),
+        }
+    }
+}
+
+impl<E> From<PlaySoundError<E>> for SpinupError {
+    fn from(err: PlaySoundError<E>) -> Self {
+        match err {
+            PlaySoundError::CommandError(err) => err.into(),
+            // the audio thread stops freeing sounds along with taking new ones
+            PlaySoundError::SoundLimitReached => SpinupError::AudioBackend(err.to_string()),
+            err => SpinupError::Playback(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Error as IoError, ErrorKind};
+
+    #[test]
+    fn io_errors_stay_io_errors() {
+        assert!(matches!(IoError::from(ErrorKind::NotFound).into(), SpinupError::Io(_)));
+        assert!(matches!(SymphoniaError::IoError(IoError::from(ErrorKind::PermissionDenied)).into(), SpinupError::Io(_)));
+        assert!(matches!(FromFileError::IoError(IoError::from(ErrorKind::NotFound)).into(), SpinupError::Io(_)));
+    }
+
+    #[test]
+    fn unreadable_sound_is_a_decode_error() {
+        // symphonia reports a file cut short as running out of bytes to read
+        assert!(matches!(SymphoniaError::IoError(IoError::from(ErrorKind::UnexpectedEof)).into(), SpinupError::Decode(_)));
+        assert!(matches!(SymphoniaError::Unsupported("core (probe): no suitable format reader found").into(), SpinupError::Decode(_)));
+        assert!(matches!(SymphoniaError::DecodeError("invalid frame").into(), SpinupError::Decode(_)));
+        assert!(matches!(FromFileError::SymphoniaError(SymphoniaError::Unsupported("codec")).into(), SpinupError::Decode(_)));
+        assert!(matches!(FromFileError::NoDefaultTrack.into(), SpinupError::Decode(_)));
+        assert!(matches!(FromFileError::UnsupportedChannelConfiguration.into(), SpinupError::Decode(_)));
+    }
+
+    #[test]
+    fn a_stuck_audio_thread_is_a_backend_error() {
+        assert!(matches!(kira::CommandError::CommandQueueFull.into(), SpinupError::AudioBackend(_)));
+        assert!(matches!(kira::CommandError::MutexPoisoned.into(), SpinupError::Playback(_)));
+        assert!(matches!(PlaySoundError::<()>::SoundLimitReached.into(), SpinupError::AudioBackend(_)));
+        assert!(matches!(PlaySoundError::<()>::CommandError(kira::CommandError::CommandQueueFull).into(), SpinupError::AudioBackend(_)));
+        assert!(matches!(PlaySoundError::IntoSoundError(FromFileError::NoDefaultTrack).into(), SpinupError::Playback(_)));
+    }
+
+    #[test]
+    fn messages_are_kept_for_showing() {
+        let err = SpinupError::InvalidRequest("no file is selected".to_string());
+        assert_eq!(err.to_string(), "no file is selected");
+        let err = SpinupError::InvalidPath { path: PathBuf::from("/tmp/gone.wav"), reason: "no longer exists".to_string() };
+        assert_eq!(err.to_string(), "/tmp/gone.wav no longer exists");
+        let err: SpinupError = SymphoniaError::DecodeError("invalid frame").into();
+        assert_eq!(err.to_string(), "malformed stream: invalid frame");
+    }
+
+    #[test]
+    fn a_missing_playlist_is_an_io_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = crate::playlist::read_m3u(&dir.path().join("gone.m3u"));
+        assert!(matches!(result, Err(SpinupError::Io(err)) if err.kind() == ErrorKind::NotFound));
+    }
+}
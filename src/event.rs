@@ -0,0 +1,162 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the events the main loop waits on. terminal input is read on its own thread and
+// sent down the same channel as the results of background work, so the loop wakes
+// up as soon as any of them arrive instead of each having to be polled for.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent};
+
+use crate::fsutil::FileStamp;
+use crate::player::{SoundFileCodecData, SoundFileMetadata};
+
+pub(crate) enum AppEvent {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    Resize,
+    Tick, // nothing arrived before the timeout
+    InputError(std::io::Error), // reading the terminal failed and the input thread stopped
+    FsChanged(PathBuf), // a file in the watched directory was created, removed or renamed
+    ProbeResult(PathBuf, Option<FileStamp>, std::time::Duration), // the duration the background probe found for a file, and its stamp before probing
+    ProbeFinished(u64), // the probe with this id has gone through all of its files
+    FileInfo(PathBuf, Option<FileStamp>, Option<Box<(SoundFileCodecData, SoundFileMetadata)>>), // what the info worker found out about a file, None if it couldn't be probed
+    PlaybackFinished, // the playing sound reached its end, or is close enough to it to crossfade to the next
+    Quit(i32), // ctrl-c or a signal asked spinup to quit, with the exit code to quit with
+}
+
+// the exit code for quitting on ctrl-c or SIGINT, the way shells report an interrupt
+pub(crate) const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+// turns what's read from the terminal into the event the app acts on, or None
+// for the ones it doesn't care about. only key presses are kept since windows
+// reports the release of every key as well, which would do everything twice.
+// ctrl-c quits like an interrupt, since raw mode keeps it from being one.
+fn input_event(event: crossterm::event::Event) -> Option<AppEvent> {
+    match event {
+        crossterm::event::Event::Key(key) if key.kind != KeyEventKind::Press => None,
+        crossterm::event::Event::Key(key) if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL => {
+            Some(AppEvent::Quit(INTERRUPTED_EXIT_CODE))
+        },
+        // many terminals send ctrl-h for backspace, so it's taken as one everywhere
+        crossterm::event::Event::Key(key) if key.code == KeyCode::Char('h') && key.modifiers == KeyModifiers::CONTROL => {
+            Some(AppEvent::Key(KeyEvent { code: KeyCode::Backspace, modifiers: KeyModifiers::NONE, ..key }))
+        },
+        crossterm::event::Event::Key(key) => Some(AppEvent::Key(key)),
+        crossterm::event::Event::Mouse(mouse) => Some(AppEvent::Mouse(mouse)),
+        crossterm::event::Event::Resize(_, _) => Some(AppEvent::Resize),
+        _ => None,
+    }
+}
+
+// the sending and receiving ends of the event channel, with the input thread
+// feeding it from the start
+pub(crate) struct EventChannel {
+    sender: Sender<AppEvent>,
+    receiver: Receiver<AppEvent>,
+}
+
+impl EventChannel {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let input_sender = sender.clone();
+        std::thread::spawn(move || loop {
+            let event = match crossterm::event::read() {
+                Ok(event) => match input_event(event) {
+                    Some(event) => event,
+                    None => continue,
+                },
+                Err(err) => {
+                    _ = input_sender.send(AppEvent::InputError(err));
+                    return;
+                },
+            };
+            // the app is gone if nobody is listening anymore
+            if input_sender.send(event).is_err() {
+                return;
+            }
+        });
+        EventChannel { sender, receiver }
+    }
+
+    // sends SIGINT, SIGTERM and SIGHUP down the channel instead of letting them end
+    // spinup on the spot, so it can quit the same way as with 'q' and put the
+    // terminal back. a closed terminal sends SIGHUP, which would otherwise leave the
+    // sound playing on its own.
+    #[cfg(unix)]
+    pub(crate) fn watch_signals(&self) -> std::io::Result<()> {
+        use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+        let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                let exit_code = if signal == SIGINT { INTERRUPTED_EXIT_CODE } else { 0 };
+                if sender.send(AppEvent::Quit(exit_code)).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    // ctrl-c comes in as a key everywhere else
+    #[cfg(not(unix))]
+    pub(crate) fn watch_signals(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    // a sender for background work to report back through
+    pub(crate) fn sender(&self) -> Sender<AppEvent> {
+        self.sender.clone()
+    }
+
+    // waits up to `timeout` for the next event, returning a Tick if none came
+    pub(crate) fn next(&self, timeout: std::time::Duration) -> AppEvent {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(event) => event,
+            // the channel holds a sender of its own so it can't be disconnected
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => AppEvent::Tick,
+        }
+    }
+
+    // returns an event that's already waiting, if there is one
+    pub(crate) fn try_next(&self) -> Option<AppEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_event(code: KeyCode, modifiers: KeyModifiers, kind: KeyEventKind) -> crossterm::event::Event {
+        crossterm::event::Event::Key(KeyEvent { code, modifiers, kind, state: crossterm::event::KeyEventState::NONE })
+    }
+
+    #[test]
+    fn only_key_presses_are_kept() {
+        let press = input_event(key_event(KeyCode::Char('j'), KeyModifiers::NONE, KeyEventKind::Press));
+        assert!(matches!(press, Some(AppEvent::Key(key)) if key.code == KeyCode::Char('j')));
+        assert!(input_event(key_event(KeyCode::Char('j'), KeyModifiers::NONE, KeyEventKind::Release)).is_none());
+        assert!(input_event(key_event(KeyCode::Char('j'), KeyModifiers::NONE, KeyEventKind::Repeat)).is_none());
+        assert!(input_event(key_event(KeyCode::Char('c'), KeyModifiers::CONTROL, KeyEventKind::Release)).is_none());
+    }
+
+    #[test]
+    fn ctrl_c_quits_like_an_interrupt() {
+        let event = input_event(key_event(KeyCode::Char('c'), KeyModifiers::CONTROL, KeyEventKind::Press));
+        assert!(matches!(event, Some(AppEvent::Quit(INTERRUPTED_EXIT_CODE))));
+        assert_eq!(INTERRUPTED_EXIT_CODE, 130);
+        // a plain 'c' is only a key
+        let event = input_event(key_event(KeyCode::Char('c'), KeyModifiers::NONE, KeyEventKind::Press));
+        assert!(matches!(event, Some(AppEvent::Key(key)) if key.code == KeyCode::Char('c')));
+    }
+
+    #[test]
+    fn ctrl_h_is_taken_as_backspace() {
+        let event = input_event(key_event(KeyCode::Char('h'), KeyModifiers::CONTROL, KeyEventKind::Press));
+        assert!(matches!(event, Some(AppEvent::Key(key)) if key.code == KeyCode::Backspace && key.modifiers == KeyModifiers::NONE));
+    }
+}
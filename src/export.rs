@@ -0,0 +1,236 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// writing decoded sound back out to files, like a region of a file trimmed out as
+// its own wav or a whole file converted to wav or flac.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use flacenc::component::BitRepr;
+use flacenc::error::Verify;
+
+use crate::player::{CodecKind, audio_track};
+
+// the bit depths a file can be written with
+pub(crate) const EXPORT_BIT_DEPTHS: [u16; 2] = [16, 24];
+
+// the most channels a flac file can hold
+const FLAC_MAX_CHANNELS: usize = 8;
+
+// the kinds of file sound can be written out as
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ExportFormat {
+    Wav,
+    Flac,
+}
+
+impl ExportFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Wav => "wav",
+            ExportFormat::Flac => "flac",
+        }
+    }
+}
+
+// the part of a file to trim out
+#[derive(Clone, Copy)]
+pub(crate) struct TrimRegion {
+    pub(crate) start: std::time::Duration,
+    pub(crate) end: Option<std::time::Duration>, // None for the end of the file
+}
+
+impl TrimRegion {
+    // the whole file, for converting it
+    pub(crate) const ALL: TrimRegion = TrimRegion { start: std::time::Duration::ZERO, end: None };
+}
+
+// how a file gets written out
+#[derive(Clone, Copy)]
+pub(crate) struct ExportOptions {
+    pub(crate) format: ExportFormat,
+    pub(crate) region: TrimRegion,
+    pub(crate) track_id: Option<u32>, // the audio track of a file with several, otherwise the one `audio_track` finds
+    pub(crate) lossy_bits: u16, // the bit depth for sources that don't have one that can be kept
+}
+
+// where the trimmed region of `path` goes: next to it, as 'name.trim.wav'
+pub(crate) fn trim_dest(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!("{}.trim.wav", stem))
+}
+
+// where `path` goes when it's converted: next to it, with the new extension
+pub(crate) fn convert_dest(path: &Path, format: ExportFormat) -> PathBuf {
+    path.with_extension(format.extension())
+}
+
+// decodes `src` and writes the region of it in `options` to `dest` with the same
+// sample rate and channels. uncompressed and lossless sources keep their bit depth
+// when it's one that can be written, anything else gets `options.lossy_bits`.
+// `progress` is kept at the percent of the frames done, as far as the length is
+// known. returns the length written. a partly written file is left for the caller
+// to remove.
+pub(crate) fn export_sound(
+    src: &Path,
+    dest: &Path,
+    options: &ExportOptions,
+    cancel: &AtomicBool,
+    progress: &AtomicUsize,
+) -> Result<std::time::Duration, Box<dyn Error>> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(src)?), Default::default());
+    let mut format_reader = probe
+        .format(&Default::default(), mss, &Default::default(), &Default::default())?
+        .format;
+    let track = match options.track_id {
+        Some(track_id) => format_reader.tracks().iter().find(|track| track.id == track_id),
+        None => audio_track(format_reader.as_ref()),
+    };
+    let track = track.ok_or("the file has no audio track")?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let sample_rate = params.sample_rate.ok_or("the file doesn't have a sample rate")?;
+    let start_frame = (options.region.start.as_secs_f64() * sample_rate as f64) as u64;
+    // markers past the end of the file just trim to the end
+    let end_frame = options.region.end
+        .map(|end| (end.as_secs_f64() * sample_rate as f64) as u64)
+        .map(|end| crate::opus::n_frames(&params).map_or(end, |n_frames| end.min(n_frames)));
+    if matches!(crate::opus::n_frames(&params), Some(n_frames) if start_frame >= n_frames) {
+        return Err("the region starts past the end of the file".into());
+    }
+    if matches!(end_frame, Some(end_frame) if end_frame <= start_frame) {
+        return Err("the region ends before it starts".into());
+    }
+    let bits = match (CodecKind::of(params.codec), params.bits_per_sample) {
+        (Some(CodecKind::Uncompressed | CodecKind::Lossless), Some(source_bits)) if EXPORT_BIT_DEPTHS.contains(&(source_bits as u16)) => source_bits as u16,
+        _ if EXPORT_BIT_DEPTHS.contains(&options.lossy_bits) => options.lossy_bits,
+        _ => 24,
+    };
+    let total_frames = end_frame.or(crate::opus::n_frames(&params)).unwrap_or(0);
+    let mut decoder = crate::opus::codecs().make(&params, &Default::default())?;
+
+    // symphonia scales integer samples by 2^(bits-1), so this gives them back exactly
+    let scale = (1i64 << (bits - 1)) as f32;
+    let (min_sample, max_sample) = (-scale, scale - 1.0);
+    // the file is only started once the first frames of the region are decoded and
+    // the channels are known for sure
+    let mut sink: Option<SampleSink> = None;
+    let mut frame = 0u64; // the frame that the next decoded packet starts at
+    let mut written = 0u64;
+    let mut sample_buf: Option<symphonia::core::audio::SampleBuffer<f32>> = None;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("it was cancelled".into());
+        }
+        if matches!(end_frame, Some(end_frame) if frame >= end_frame) {
+            break;
+        }
+        let packet = match format_reader.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // a damaged packet is skipped, the same as when it plays
+            Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let frames = decoded.frames() as u64;
+        if !matches!(&sample_buf, Some(buf) if buf.capacity() >= decoded.capacity() * channels) {
+            sample_buf = Some(symphonia::core::audio::SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buf = sample_buf.as_mut().ok_or("the sample buffer is missing")?;
+        buf.copy_interleaved_ref(decoded);
+
+        // the frames of this packet that are inside the region
+        let first = start_frame.saturating_sub(frame).min(frames);
+        let last = end_frame.map_or(frames, |end_frame| end_frame.saturating_sub(frame).min(frames));
+        frame += frames;
+        if let Some(percent) = (frame.min(total_frames) * 100).checked_div(total_frames) {
+            progress.store(percent as usize, Ordering::Relaxed);
+        }
+        if first >= last {
+            continue;
+        }
+        if sink.is_none() {
+            sink = Some(SampleSink::start(dest, options.format, channels, spec.rate, bits)?);
+        }
+        let sink = sink.as_mut().ok_or("the file wasn't started")?;
+        let samples = buf.samples()[first as usize * channels..last as usize * channels].iter()
+            .map(|&sample| (sample * scale).round().clamp(min_sample, max_sample) as i32);
+        sink.write(samples)?;
+        written += last - first;
+    }
+    let sink = sink.ok_or("the region starts past the end of the file")?;
+    sink.finish(dest)?;
+    Ok(std::time::Duration::from_secs_f64(written as f64 / sample_rate as f64))
+}
+
+// where the decoded samples go. a wav is written as they come, while a flac is
+// encoded all at once at the end since the encoder wants the whole sound.
+enum SampleSink {
+    Wav(hound::WavWriter<std::io::BufWriter<std::fs::File>>),
+    Flac { samples: Vec<i32>, channels: usize, sample_rate: u32, bits: u16 },
+}
+
+impl SampleSink {
+    fn start(dest: &Path, format: ExportFormat, channels: usize, sample_rate: u32, bits: u16) -> Result<Self, Box<dyn Error>> {
+        match format {
+            ExportFormat::Wav => {
+                let spec = hound::WavSpec {
+                    channels: channels as u16,
+                    sample_rate,
+                    bits_per_sample: bits,
+                    sample_format: hound::SampleFormat::Int,
+                };
+                Ok(SampleSink::Wav(hound::WavWriter::create(dest, spec)?))
+            },
+            ExportFormat::Flac => {
+                if channels > FLAC_MAX_CHANNELS {
+                    return Err(format!("flac can't hold {} channels, only up to {}", channels, FLAC_MAX_CHANNELS).into());
+                }
+                Ok(SampleSink::Flac { samples: vec![], channels, sample_rate, bits })
+            },
+        }
+    }
+
+    fn write(&mut self, samples: impl Iterator<Item = i32>) -> Result<(), Box<dyn Error>> {
+        match self {
+            SampleSink::Wav(writer) => {
+                for sample in samples {
+                    writer.write_sample(sample)?;
+                }
+            },
+            SampleSink::Flac { samples: kept, .. } => kept.extend(samples),
+        }
+        Ok(())
+    }
+
+    fn finish(self, dest: &Path) -> Result<(), Box<dyn Error>> {
+        match self {
+            SampleSink::Wav(writer) => writer.finalize()?,
+            SampleSink::Flac { samples, channels, sample_rate, bits } => {
+                let config = flacenc::config::Encoder::default()
+                    .into_verified()
+                    .map_err(|(_, err)| format!("the flac encoder settings are bad: {:?}", err))?;
+                let source = flacenc::source::MemSource::from_samples(&samples, channels, bits as usize, sample_rate as usize);
+                let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+                    .map_err(|err| format!("couldn't encode the flac: {:?}", err))?;
+                let mut sink = flacenc::bitsink::ByteSink::new();
+                stream.write(&mut sink).map_err(|err| format!("couldn't encode the flac: {:?}", err))?;
+                std::fs::write(dest, sink.as_slice())?;
+            },
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,692 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// everything that touches the filesystem outside of playing sounds: listing
+// directories, resolving typed paths, copying and moving files, watching for
+// changes and the files spinup keeps in the data directory.
+
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::app::{BrowserEntry, EntryKind, SUPPORTED_EXTS};
+use crate::error::SpinupError;
+use crate::event::AppEvent;
+use crate::player::probe_start_is_audio;
+use crate::playlist::is_playlist;
+
+// how much of a file is copied at a time between progress updates
+pub(crate) const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+// the file in the data directory that bookmarked directories are saved to, one per line
+pub(crate) const BOOKMARKS_FILE_NAME: &str = "bookmarks";
+
+// the file in the data directory that recently played files are saved to, newest first
+pub(crate) const RECENT_FILE_NAME: &str = "recent";
+
+// the most recently played files that are remembered
+pub(crate) const RECENT_MAX: usize = 200;
+
+// the file in the data directory that probed durations are saved to, one file per line
+pub(crate) const DURATIONS_FILE_NAME: &str = "durations";
+
+// the most probed durations kept before the cache gets emptied
+pub(crate) const DURATION_CACHE_SIZE: usize = 65536;
+
+// how much of the start of a file is read to tell whether it's audio by its content
+pub(crate) const CONTENT_PROBE_BYTES: usize = 64 * 1024;
+
+// the most files whose content probe is remembered before the cache gets emptied
+pub(crate) const CONTENT_PROBE_CACHE_SIZE: usize = 65536;
+
+// whether each file probed by its content turned out to be audio, by full path, so
+// listing a directory again doesn't read every file again. the recursive scan
+// thread shares it, so it lives out here rather than in the app state.
+static CONTENT_PROBES: Mutex<Option<std::collections::HashMap<PathBuf, (FileStamp, bool)>>> = Mutex::new(None);
+
+// how far the recursive view looks for files
+#[derive(Clone)]
+pub(crate) struct ScanOptions {
+    pub(crate) show_hidden: bool, // hidden directories are only searched when hidden entries are shown
+    pub(crate) listed_extensions: Option<Vec<String>>, // the extensions of the files listed, when they aren't `SUPPORTED_EXTS`
+    pub(crate) probe_content: bool, // files with other extensions are listed too when their content is audio
+    pub(crate) max_depth: usize, // how many directories below the current one are searched
+    pub(crate) max_files: usize, // the scan stops once it has found this many files
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            show_hidden: false,
+            listed_extensions: None,
+            probe_content: false,
+            max_depth: 8,
+            max_files: 10000,
+        }
+    }
+}
+
+// a recursive scan for files running on a background thread
+pub(crate) struct RecursiveScan {
+    pub(crate) receiver: std::sync::mpsc::Receiver<ScanMessage>,
+    pub(crate) files_found: usize, // how many files the scan had found when it last reported in
+}
+
+// what the recursive scan thread sends back to the interface
+pub(crate) enum ScanMessage {
+    Progress(usize), // the number of files found so far
+    Done(Vec<BrowserEntry>), // every file found, named relative to the scanned directory
+}
+
+// a watch on the listed directory for files being created, removed or renamed
+pub(crate) struct DirWatch {
+    pub(crate) path: PathBuf,
+    pub(crate) _watcher: notify::RecommendedWatcher, // the watch stops when this is dropped
+    pub(crate) last_event: Option<std::time::Instant>, // when the latest change not refreshed yet happened
+}
+
+impl DirWatch {
+    // starts watching `path`, sending an event through `sender` for every change to
+    // its listing. returns None if it can't be watched.
+    pub(crate) fn new(path: &Path, sender: std::sync::mpsc::Sender<AppEvent>) -> Option<Self> {
+        use notify::Watcher;
+        let watched_path = path.to_path_buf();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let changes_listing = matches!(event, Ok(event) if matches!(event.kind,
+                notify::EventKind::Create(_) | notify::EventKind::Remove(_) | notify::EventKind::Modify(notify::event::ModifyKind::Name(_))));
+            if changes_listing {
+                _ = sender.send(AppEvent::FsChanged(watched_path.clone()));
+            }
+        }).ok()?;
+        watcher.watch(path, notify::RecursiveMode::NonRecursive).ok()?;
+        Some(DirWatch { path: path.to_path_buf(), _watcher: watcher, last_event: None })
+    }
+}
+
+// how far along a copy or move is
+#[derive(Clone, Copy)]
+pub(crate) struct FileOpProgress {
+    pub(crate) file: usize, // the index of the file being worked on
+    pub(crate) file_count: usize,
+    pub(crate) bytes_done: u64,
+    pub(crate) bytes_total: u64,
+}
+
+// what the copy or move thread sends back to the interface
+pub(crate) enum FileOpMessage {
+    Progress(FileOpProgress),
+    Collision(PathBuf), // the destination file exists, and the thread waits on a `CollisionChoice`
+    Done { transferred: Vec<(PathBuf, PathBuf)>, failures: Vec<String> }, // the source and destination of each file done
+}
+
+// the ways to deal with a file that's already at the destination
+pub(crate) enum CollisionChoice {
+    Skip,
+    Overwrite,
+    Rename, // a number gets added to the name, like "kick (2).wav"
+}
+
+// a file that was played and when
+#[derive(Clone)]
+pub(crate) struct RecentPlay {
+    pub(crate) path: PathBuf,
+    pub(crate) played_at: std::time::SystemTime,
+}
+
+// when a file was last modified and how big it was, which tells whether what was
+// read from it before is still good
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct FileStamp {
+    pub(crate) modified: Option<std::time::SystemTime>,
+    pub(crate) size: u64,
+}
+
+// returns the stamp of the file at `path`, or None if it can't be read
+pub(crate) fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(FileStamp { modified: metadata.modified().ok(), size: metadata.len() })
+}
+
+// turns a typed path into a full path, expanding a leading '~' to the home directory
+// and treating relative paths as relative to `current_dir`
+pub(crate) fn expand_path(current_dir: &Path, text: &str) -> PathBuf {
+    if let Some(rest) = text.strip_prefix('~') {
+        if rest.is_empty() || rest.starts_with(std::path::is_separator) {
+            if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+                return Path::new(&home).join(rest.trim_start_matches(std::path::is_separator));
+            }
+        }
+    }
+    current_dir.join(text)
+}
+
+// checks that the typed path is a directory, returning its canonical path
+pub(crate) fn resolve_directory(current_dir: &Path, text: &str) -> Result<PathBuf, SpinupError> {
+    let path = expand_path(current_dir, text.trim());
+    if !fs::metadata(&path)?.is_dir() {
+        return Err(SpinupError::InvalidPath { path, reason: "is not a directory".to_string() });
+    }
+    Ok(fs::canonicalize(&path)?)
+}
+
+// completes the last component of a typed path against the directories on disk, as
+// far as all of the matching names agree. a single match gets a trailing separator
+// so the next component can be completed straight away.
+pub(crate) fn complete_directory_path(current_dir: &Path, text: &str, show_hidden: bool) -> Option<String> {
+    let split = text.rfind(std::path::is_separator).map(|i| i + 1).unwrap_or(0);
+    let (parent_text, partial) = text.split_at(split);
+    let mut names: Vec<String> = fs::read_dir(expand_path(current_dir, parent_text)).ok()?
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| name.starts_with(partial))
+        .filter(|name| show_hidden || partial.starts_with('.') || !name.starts_with('.'))
+        .collect();
+    names.sort();
+
+    let first = names.first()?;
+    let common_len = names.iter().fold(first.len(), |len, name| {
+        first[..len].char_indices()
+            .zip(name.chars())
+            .find(|((_, a), b)| a != b)
+            .map(|((i, _), _)| i)
+            .unwrap_or_else(|| len.min(name.len()))
+    });
+    let mut completed = format!("{}{}", parent_text, &first[..common_len]);
+    if names.len() == 1 {
+        completed.push(std::path::MAIN_SEPARATOR);
+    }
+    Some(completed)
+}
+
+// starts listing the supported files beneath `root` on a background thread so big
+// trees don't hold up the interface
+pub(crate) fn start_recursive_scan(root: PathBuf, options: ScanOptions) -> RecursiveScan {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut entries = Vec::new();
+        scan_dir_recursive(&root, &root, 0, &options, &mut entries, &sender);
+        // nobody may be waiting on the results anymore
+        _ = sender.send(ScanMessage::Done(entries));
+    });
+    RecursiveScan { receiver, files_found: 0 }
+}
+
+// adds the supported files in `dir` and its subdirectories to `entries`, named relative
+// to `root`. returns false once the scan should stop, either because the file cap was
+// hit or because the interface stopped listening for the results.
+pub(crate) fn scan_dir_recursive(root: &Path, dir: &Path, depth: usize, options: &ScanOptions, entries: &mut Vec<BrowserEntry>, sender: &std::sync::mpsc::Sender<ScanMessage>) -> bool {
+    // directories that can't be read are skipped instead of failing the whole scan
+    let mut paths = get_supported_files_in_dir(dir, options.show_hidden, options.listed_extensions.as_deref(), options.probe_content).unwrap_or_default();
+    paths.sort();
+    for path in paths {
+        if entries.len() >= options.max_files {
+            return false;
+        }
+        if let Ok(relative_path) = path.strip_prefix(root) {
+            let mut entry = BrowserEntry::new(relative_path.as_os_str().to_os_string(), path.clone(), EntryKind::File);
+            entry.probe_failed = options.probe_content && !probed_as_audio(&path);
+            entries.push(entry);
+        }
+    }
+    if sender.send(ScanMessage::Progress(entries.len())).is_err() {
+        return false;
+    }
+
+    if depth >= options.max_depth {
+        return true;
+    }
+    let mut subdirs: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir.filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .filter(|p| options.show_hidden || !p.file_name().map(|f| f.to_string_lossy().starts_with('.')).unwrap_or(true))
+            .collect(),
+        Err(_) => return true,
+    };
+    subdirs.sort();
+    for subdir in subdirs {
+        if !scan_dir_recursive(root, &subdir, depth + 1, options, entries, sender) {
+            return false;
+        }
+    }
+    true
+}
+
+// copies or moves each file in `paths` into `dest_dir`, sending progress along the
+// way and asking what to do about files already there. moves that can't be done
+// by renaming, like across filesystems, are copied and then deleted. once the
+// `cancel` flag gets set it stops, removing the part of the file it was copying.
+pub(crate) fn run_file_operation(
+    paths: Vec<PathBuf>,
+    dest_dir: &Path,
+    moving: bool,
+    sender: &std::sync::mpsc::Sender<FileOpMessage>,
+    answers: &std::sync::mpsc::Receiver<CollisionChoice>,
+    cancel: &std::sync::atomic::AtomicBool,
+) {
+    let mut progress = FileOpProgress {
+        file: 0,
+        file_count: paths.len(),
+        bytes_done: 0,
+        bytes_total: paths.iter().filter_map(|p| fs::metadata(p).ok()).map(|m| m.len()).sum(),
+    };
+    let mut transferred = vec![];
+    let mut failures = vec![];
+    for (i, src) in paths.iter().enumerate() {
+        progress.file = i;
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) || sender.send(FileOpMessage::Progress(progress)).is_err() {
+            return;
+        }
+        let file_name = match src.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut dest = dest_dir.join(file_name);
+        if dest.exists() {
+            if sender.send(FileOpMessage::Collision(dest.clone())).is_err() {
+                return;
+            }
+            match answers.recv() {
+                Ok(CollisionChoice::Skip) => continue,
+                Ok(CollisionChoice::Overwrite) if same_file(src, &dest) => {
+                    failures.push(format!("{}: can't overwrite a file with itself", src.display()));
+                    continue;
+                },
+                Ok(CollisionChoice::Overwrite) => {},
+                Ok(CollisionChoice::Rename) => dest = unused_path(&dest),
+                Err(_) => return,
+            }
+        }
+
+        let file_size = fs::metadata(src).map(|m| m.len()).unwrap_or(0);
+        let result = if moving && fs::rename(src, &dest).is_ok() {
+            progress.bytes_done += file_size;
+            Ok(())
+        } else {
+            copy_with_progress(src, &dest, &mut progress, sender, cancel)
+                .and_then(|()| if moving { fs::remove_file(src) } else { Ok(()) })
+        };
+        match result {
+            Ok(()) => transferred.push((src.clone(), dest)),
+            Err(err) => failures.push(format!("{}: {}", src.display(), err)),
+        }
+    }
+    _ = sender.send(FileOpMessage::Done { transferred, failures });
+}
+
+// copies a file a chunk at a time so progress can be sent while it goes. a copy
+// that's cancelled or fails partway doesn't leave half a file behind.
+pub(crate) fn copy_with_progress(
+    src: &Path,
+    dest: &Path,
+    progress: &mut FileOpProgress,
+    sender: &std::sync::mpsc::Sender<FileOpMessage>,
+    cancel: &std::sync::atomic::AtomicBool,
+) -> io::Result<()> {
+    let mut reader = fs::File::open(src)?;
+    let mut writer = fs::File::create(dest)?;
+    if let Err(err) = copy_chunks(&mut reader, &mut writer, progress, sender, cancel) {
+        drop(writer);
+        _ = fs::remove_file(dest);
+        return Err(err);
+    }
+    // keep the permissions the way they were, like fs::copy does
+    if let Ok(metadata) = fs::metadata(src) {
+        _ = fs::set_permissions(dest, metadata.permissions());
+    }
+    Ok(())
+}
+
+// the chunk by chunk copying of `copy_with_progress`, giving up with an error once
+// the `cancel` flag gets set or nobody's listening for the progress anymore
+fn copy_chunks(
+    reader: &mut fs::File,
+    writer: &mut fs::File,
+    progress: &mut FileOpProgress,
+    sender: &std::sync::mpsc::Sender<FileOpMessage>,
+    cancel: &std::sync::atomic::AtomicBool,
+) -> io::Result<()> {
+    use std::io::{Read, Write};
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read])?;
+        progress.bytes_done += read as u64;
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) || sender.send(FileOpMessage::Progress(*progress)).is_err() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+    }
+    writer.flush()
+}
+
+// the program that opens files and directories in whatever the desktop uses for them
+pub(crate) fn default_open_command() -> Vec<String> {
+    if cfg!(target_os = "macos") {
+        vec!["open".to_string()]
+    } else if cfg!(windows) {
+        // start is built into cmd, and takes its first quoted argument as a window title
+        vec!["cmd".to_string(), "/C".to_string(), "start".to_string(), "".to_string()]
+    } else {
+        vec!["xdg-open".to_string()]
+    }
+}
+
+// encodes bytes as standard, padded base64, which OSC 52 sequences carry their text in
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// returns true if both paths lead to the same file
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+// adds the first number that makes `path` a file that doesn't exist yet, like
+// "kick (2).wav" for "kick.wav"
+pub(crate) fn unused_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut n = 2;
+    loop {
+        let candidate = path.with_file_name(format!("{} ({}){}", stem, n, ext));
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+// returns the directory spinup keeps its data in, following the XDG base directory
+// spec and falling back to ~/.local/share
+pub(crate) fn app_data_dir() -> Option<PathBuf> {
+    let data_home = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(&std::env::var_os("HOME")?).join(".local").join("share"),
+    };
+    Some(data_home.join("spinup"))
+}
+
+// reads the bookmarked directories, which is an empty list before any are saved
+pub(crate) fn load_bookmarks() -> io::Result<Vec<PathBuf>> {
+    let bookmarks_path = match app_data_dir() {
+        Some(dir) => dir.join(BOOKMARKS_FILE_NAME),
+        None => return Ok(Vec::new()),
+    };
+    match fs::read_to_string(bookmarks_path) {
+        Ok(text) => Ok(text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(PathBuf::from)
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) fn save_bookmarks(bookmarks: &[PathBuf]) -> io::Result<()> {
+    let dir = app_data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "couldn't find a data directory"))?;
+    fs::create_dir_all(&dir)?;
+    let text: String = bookmarks.iter()
+        .map(|b| format!("{}\n", b.display()))
+        .collect();
+    fs::write(dir.join(BOOKMARKS_FILE_NAME), text)
+}
+
+// reads the recently played files, which is an empty list before any are saved. a
+// line that doesn't make sense is skipped.
+pub(crate) fn load_recent() -> io::Result<Vec<RecentPlay>> {
+    let recent_path = match app_data_dir() {
+        Some(dir) => dir.join(RECENT_FILE_NAME),
+        None => return Ok(Vec::new()),
+    };
+    let text = match fs::read_to_string(recent_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    // each line is when the file was played in seconds and then its path
+    let parse_line = |line: &str| -> Option<RecentPlay> {
+        let (secs, path) = line.split_once('\t')?;
+        let played_at = std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs.parse().ok()?);
+        Some(RecentPlay { path: PathBuf::from(path), played_at })
+    };
+    Ok(text.lines().filter(|line| !line.trim().is_empty()).filter_map(parse_line).take(RECENT_MAX).collect())
+}
+
+pub(crate) fn save_recent(recent: &[RecentPlay]) -> io::Result<()> {
+    let dir = app_data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "couldn't find a data directory"))?;
+    fs::create_dir_all(&dir)?;
+    let text: String = recent.iter()
+        .map(|play| {
+            let secs = play.played_at.duration_since(std::time::UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+            format!("{}\t{}\n", secs, play.path.display())
+        })
+        .collect();
+    fs::write(dir.join(RECENT_FILE_NAME), text)
+}
+
+// reads the durations saved by earlier runs, by full path along with the stamp of the
+// file they were probed from. a line that doesn't make sense is skipped.
+pub(crate) fn load_duration_cache() -> io::Result<std::collections::HashMap<PathBuf, (FileStamp, std::time::Duration)>> {
+    let durations_path = match app_data_dir() {
+        Some(dir) => dir.join(DURATIONS_FILE_NAME),
+        None => return Ok(std::collections::HashMap::new()),
+    };
+    let text = match fs::read_to_string(durations_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(std::collections::HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    // each line is the size, the modified time in nanoseconds (or '-'), the duration in
+    // microseconds and then the path, which goes last since it can hold tabs itself
+    let parse_line = |line: &str| -> Option<(PathBuf, (FileStamp, std::time::Duration))> {
+        let mut fields = line.splitn(4, '\t');
+        let size = fields.next()?.parse().ok()?;
+        let modified = match fields.next()? {
+            "-" => None,
+            nanos => Some(std::time::UNIX_EPOCH + std::time::Duration::from_nanos(nanos.parse().ok()?)),
+        };
+        let duration = std::time::Duration::from_micros(fields.next()?.parse().ok()?);
+        let path = PathBuf::from(fields.next().filter(|p| !p.is_empty())?);
+        Some((path, (FileStamp { modified, size }, duration)))
+    };
+    Ok(text.lines().filter_map(parse_line).collect())
+}
+
+pub(crate) fn save_duration_cache(durations: &std::collections::HashMap<PathBuf, (FileStamp, std::time::Duration)>) -> io::Result<()> {
+    let dir = app_data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "couldn't find a data directory"))?;
+    fs::create_dir_all(&dir)?;
+    let text: String = durations.iter()
+        // a path that can't be written as one line couldn't be read back
+        .filter(|(path, _)| !path.to_string_lossy().contains('\n'))
+        .map(|(path, (stamp, duration))| {
+            let modified = stamp.modified
+                .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|since| since.as_nanos().to_string())
+                .unwrap_or_else(|| "-".to_string());
+            format!("{}\t{}\t{}\t{}\n", stamp.size, modified, duration.as_micros(), path.display())
+        })
+        .collect();
+    fs::write(dir.join(DURATIONS_FILE_NAME), text)
+}
+
+// opens `dir_path` to list it, telling a directory that went away apart from one
+// that can't be read
+fn read_listed_dir(dir_path: &Path) -> Result<fs::ReadDir, SpinupError> {
+    fs::read_dir(dir_path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => SpinupError::InvalidPath { path: dir_path.to_path_buf(), reason: "doesn't exist".to_string() },
+        _ => SpinupError::Io(err),
+    })
+}
+
+// returns the subdirectories of `dir_path`
+pub(crate) fn get_directories_in_dir(dir_path: &Path, show_hidden: bool) -> Result<Vec<PathBuf>, SpinupError> {
+    let dir = read_listed_dir(dir_path)?;
+    let filtered_paths: Vec<PathBuf> = dir.filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|e| e.is_dir())
+        .filter(|e| match e.file_name() {
+            Some(os_fn) => show_hidden || !os_fn.to_string_lossy().starts_with('.'),
+            None => false,
+        })
+        .collect();
+
+    Ok(filtered_paths)
+}
+
+// returns the files in `dir_path` with one of the `extensions`, ignoring case, or one
+// of the supported extensions when there aren't any given. with `probe_content` set,
+// files with any other extension (or none) are returned too if their content
+// probes as audio.
+pub(crate) fn get_supported_files_in_dir(dir_path: &Path, show_hidden: bool, extensions: Option<&[String]>, probe_content: bool) -> Result<Vec<PathBuf>, SpinupError> {
+    let dir = read_listed_dir(dir_path)?;
+    let filtered_paths = dir.filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|e| e.is_file())
+        .filter(|e| { 
+            if let Some(os_fn) = e.file_name() {
+                if !show_hidden && os_fn.to_string_lossy().starts_with('.') {
+                    return false;
+                } 
+            } else {
+                return false;
+            }
+            if has_listed_extension(e, extensions) {
+                return true;
+            }
+            probe_content && probed_as_audio(e)
+        })
+        .collect();
+    
+    Ok(filtered_paths)
+}
+
+// returns the m3u playlists in `dir_path`
+pub(crate) fn get_playlists_in_dir(dir_path: &Path, show_hidden: bool) -> Result<Vec<PathBuf>, SpinupError> {
+    let dir = read_listed_dir(dir_path)?;
+    let playlists = dir.filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|e| e.is_file() && is_playlist(e))
+        .filter(|e| show_hidden || !e.file_name().map(|f| f.to_string_lossy().starts_with('.')).unwrap_or(true))
+        .collect();
+    Ok(playlists)
+}
+
+// returns true if the file has one of the `extensions`, ignoring case, or one of
+// the supported extensions when there aren't any given
+pub(crate) fn has_listed_extension(path: &Path, extensions: Option<&[String]>) -> bool {
+    match path.extension() {
+        Some(ext) => match extensions {
+            Some(extensions) => extensions.iter().any(|listed| ext.eq_ignore_ascii_case(listed)),
+            None => SUPPORTED_EXTS.iter().any(|supported| ext.eq_ignore_ascii_case(supported)),
+        },
+        None => false,
+    }
+}
+
+// returns true if the start of the file probes as audio, going by what was found
+// the last time if the file hasn't changed since
+pub(crate) fn probed_as_audio(path: &Path) -> bool {
+    let stamp = match file_stamp(path) {
+        Some(stamp) => stamp,
+        None => return false,
+    };
+    if let Ok(cache) = CONTENT_PROBES.lock() {
+        if let Some((cached_stamp, is_audio)) = cache.as_ref().and_then(|cache| cache.get(path)) {
+            if *cached_stamp == stamp {
+                return *is_audio;
+            }
+        }
+    }
+    let is_audio = probe_start_is_audio(path, CONTENT_PROBE_BYTES);
+    if let Ok(mut cache) = CONTENT_PROBES.lock() {
+        let cache = cache.get_or_insert_with(std::collections::HashMap::new);
+        if cache.len() >= CONTENT_PROBE_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(path.to_path_buf(), (stamp, is_audio));
+    }
+    is_audio
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a directory with a few sound files, a hidden one, a file that isn't sound,
+    // a playlist and a visible and a hidden subdirectory
+    fn fixture_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["kick.wav", "Snare.MP3", "pad.flac", ".hidden.wav", "notes.txt", "set.m3u"] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        fs::create_dir(dir.path().join("loops")).unwrap();
+        fs::create_dir(dir.path().join(".cache")).unwrap();
+        dir
+    }
+
+    fn names(mut paths: Vec<PathBuf>) -> Vec<String> {
+        paths.sort();
+        paths.iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn supported_files_are_listed_ignoring_case() {
+        let dir = fixture_dir();
+        let files = get_supported_files_in_dir(dir.path(), false, None, false).unwrap();
+        assert_eq!(names(files), ["Snare.MP3", "kick.wav", "pad.flac"]);
+    }
+
+    #[test]
+    fn hidden_files_are_only_listed_when_shown() {
+        let dir = fixture_dir();
+        let files = get_supported_files_in_dir(dir.path(), true, None, false).unwrap();
+        assert_eq!(names(files), [".hidden.wav", "Snare.MP3", "kick.wav", "pad.flac"]);
+        let dirs = get_directories_in_dir(dir.path(), false).unwrap();
+        assert_eq!(names(dirs), ["loops"]);
+        let dirs = get_directories_in_dir(dir.path(), true).unwrap();
+        assert_eq!(names(dirs), [".cache", "loops"]);
+    }
+
+    #[test]
+    fn listed_extensions_replace_the_supported_ones() {
+        let dir = fixture_dir();
+        let extensions = vec!["txt".to_string(), "WAV".to_string()];
+        let files = get_supported_files_in_dir(dir.path(), false, Some(&extensions), false).unwrap();
+        assert_eq!(names(files), ["kick.wav", "notes.txt"]);
+    }
+
+    #[test]
+    fn playlists_are_listed() {
+        let dir = fixture_dir();
+        let playlists = get_playlists_in_dir(dir.path(), false).unwrap();
+        assert_eq!(names(playlists), ["set.m3u"]);
+    }
+
+    #[test]
+    fn listing_a_missing_directory_is_an_invalid_path() {
+        let dir = fixture_dir();
+        let missing = dir.path().join("gone");
+        assert!(matches!(get_directories_in_dir(&missing, false), Err(SpinupError::InvalidPath { .. })));
+        assert!(matches!(resolve_directory(dir.path(), "kick.wav"), Err(SpinupError::InvalidPath { .. })));
+        assert_eq!(resolve_directory(dir.path(), "loops").unwrap(), fs::canonicalize(dir.path().join("loops")).unwrap());
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// fzf-style fuzzy matching: every character of the query has to appear in the
+// candidate in order, and the matches are scored so that consecutive characters
+// and characters at the start of words rank higher than scattered ones.
+
+// points for every matched character
+const SCORE_MATCH: i64 = 16;
+
+// extra points when a character matches right after the previous one
+const BONUS_CONSECUTIVE: i64 = 16;
+
+// extra points when a character matches at the start of the candidate or a word
+const BONUS_BOUNDARY: i64 = 24;
+
+// extra points for a camelCase hump, which is a slightly weaker word boundary
+const BONUS_CAMEL: i64 = 16;
+
+// points lost for every candidate character skipped between two matches
+const PENALTY_GAP: i64 = 1;
+
+// marks a query character that can't be matched at a given candidate position
+const NO_SCORE: i64 = i64::MIN;
+
+// the result of fuzzy matching a query against a candidate string
+pub(crate) struct FuzzyMatch {
+    pub(crate) score: i64, // higher scores are better matches
+    pub(crate) positions: Vec<usize>, // the char indices in the candidate that matched the query characters
+}
+
+// matches `query` against `candidate` ignoring case and whitespace in the query,
+// returning None if the query characters don't all appear in order. the match
+// returned is the best scoring way to line the query up with the candidate.
+pub(crate) fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lowercase_char)
+        .collect();
+    if query.is_empty() {
+        return Some(FuzzyMatch { score: 0, positions: Vec::new() });
+    }
+
+    let cand_chars: Vec<char> = candidate.chars().collect();
+    let cand_lower: Vec<char> = cand_chars.iter().map(|c| lowercase_char(*c)).collect();
+    let (m, n) = (query.len(), cand_chars.len());
+    if m > n {
+        return None;
+    }
+
+    // scores[i * n + j] is the best score for matching query[..=i] with query[i]
+    // landing on candidate[j]; parents remembers where query[i - 1] landed.
+    let mut scores = vec![NO_SCORE; m * n];
+    let mut parents = vec![0usize; m * n];
+    for (j, c) in cand_lower.iter().enumerate() {
+        if *c == query[0] {
+            scores[j] = SCORE_MATCH + boundary_bonus(&cand_chars, j);
+        }
+    }
+
+    for i in 1..m {
+        let prev_row = (i - 1) * n;
+
+        // the best previous score for a gapped match, stored as (score + gap * k, k)
+        // so it can be carried along the row without rescanning
+        let mut best_gapped: Option<(i64, usize)> = None;
+        for j in i..n {
+            if j >= 2 {
+                let k = j - 2;
+                if scores[prev_row + k] != NO_SCORE {
+                    let carried = scores[prev_row + k] + PENALTY_GAP * k as i64;
+                    if !matches!(best_gapped, Some((best, _)) if best >= carried) {
+                        best_gapped = Some((carried, k));
+                    }
+                }
+            }
+            if cand_lower[j] != query[i] {
+                continue;
+            }
+
+            let mut best: Option<(i64, usize)> = None;
+            if scores[prev_row + j - 1] != NO_SCORE {
+                best = Some((scores[prev_row + j - 1] + BONUS_CONSECUTIVE, j - 1));
+            }
+            if let Some((carried, k)) = best_gapped {
+                let gapped = carried - PENALTY_GAP * (j as i64 - 1);
+                if !matches!(best, Some((b, _)) if b >= gapped) {
+                    best = Some((gapped, k));
+                }
+            }
+            if let Some((score, k)) = best {
+                scores[i * n + j] = score + SCORE_MATCH + boundary_bonus(&cand_chars, j);
+                parents[i * n + j] = k;
+            }
+        }
+    }
+
+    // pick the best place for the last query character and walk back from it
+    let last_row = (m - 1) * n;
+    let (mut j, score) = (0..n)
+        .filter(|j| scores[last_row + j] != NO_SCORE)
+        .map(|j| (j, scores[last_row + j]))
+        .max_by_key(|(_, score)| *score)?;
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = j;
+        if i > 0 {
+            j = parents[i * n + j];
+        }
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+fn lowercase_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// returns the bonus for a match at `j`, which is higher at the start of words
+fn boundary_bonus(chars: &[char], j: usize) -> i64 {
+    if j == 0 {
+        return BONUS_BOUNDARY;
+    }
+    let (prev, cur) = (chars[j - 1], chars[j]);
+    if matches!(prev, ' ' | '_' | '-' | '.' | '/' | '\\') {
+        BONUS_BOUNDARY
+    } else if prev.is_lowercase() && cur.is_uppercase() {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
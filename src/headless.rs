@@ -0,0 +1,83 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// playing a single file without the terminal interface, for using spinup as a
+// plain command line player in scripts. the sound is started the same way the
+// interface starts it, only the waiting and the progress are done here.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+use crate::app::AppState;
+use crate::player::{AudioOutput, PlayOptions, start_file};
+use crate::ui::format_duration;
+
+// how often the progress line is redrawn and the sound checked on
+const HEADLESS_TICK: std::time::Duration = std::time::Duration::from_millis(100);
+
+// how the file is played when there's no interface to change it with
+pub(crate) struct HeadlessOptions {
+    pub(crate) device: Option<String>,
+    pub(crate) volume: f64, // an amplitude factor like `SoundState::volume`
+    pub(crate) start_position: std::time::Duration,
+    pub(crate) quiet: bool, // leaves out the progress line
+}
+
+// plays the file at `path` until it ends, returning an error if it can't be
+// decoded or the audio output stops partway through
+pub(crate) fn play_file_headless(path: &Path, options: HeadlessOptions) -> Result<(), Box<dyn Error>> {
+    let (mut audio, audio_err) = AudioOutput::open(options.device);
+    if let Some(err) = audio_err {
+        return Err(err.into());
+    }
+
+    let mut app_state = AppState::default();
+    app_state.sound_state.volume = options.volume;
+    let play_options = PlayOptions {
+        fade_out: std::time::Duration::ZERO,
+        start_position: options.start_position,
+        ..app_state.sound_state.play_options()
+    };
+    start_file(&mut app_state, &mut audio, path.to_path_buf(), play_options)?;
+    // there's no picker to show without the interface, so a file with several audio
+    // tracks plays the first one
+    if let Some(picker) = app_state.track_picker.take() {
+        if let Some(track) = picker.tracks.first() {
+            app_state.choose_track(&picker.path, track.id);
+        }
+        start_file(&mut app_state, &mut audio, picker.path, picker.options)?;
+    }
+
+    let duration = app_state.sound_state.play_duration;
+    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+    println!("{} ({})", name, format_duration(duration));
+
+    let mut stdout = std::io::stdout();
+    let mut last_tick = std::time::Instant::now();
+    loop {
+        std::thread::sleep(HEADLESS_TICK);
+        let current_tick = std::time::Instant::now();
+        app_state.sound_state.update_play_time(current_tick.duration_since(last_tick));
+        last_tick = current_tick;
+
+        if app_state.sound_state.check_finished() {
+            break;
+        }
+        if app_state.sound_state.output_stalled() {
+            if !options.quiet {
+                println!();
+            }
+            return Err("the audio output stopped playing".into());
+        }
+        if !options.quiet {
+            // the spaces cover up the end of a longer line drawn before
+            print!("\r{} / {}    ", format_duration(app_state.sound_state.play_time), format_duration(duration));
+            stdout.flush()?;
+        }
+    }
+    if !options.quiet {
+        println!("\r{} / {}    ", format_duration(duration), format_duration(duration));
+    }
+    Ok(())
+}
@@ -0,0 +1,924 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// turns keys and mouse events into changes to the AppState and playback.
+
+use tui::layout::Rect;
+
+use crate::keymap;
+use crate::app::{AppState, DevicePicker, Focus, FuzzyFinder, Prompt, Scrub, TextInput, TextInputKind, TrackPicker, parse_timestamp, parse_trim_range};
+use crate::export::{ExportFormat, TrimRegion};
+use crate::fsutil::{CollisionChoice, complete_directory_path, expand_path, resolve_directory};
+use crate::player::{AudioOutput, PLAYBACK_RATE_STEP, VOLUME_STEP, advance_to_next_file, list_audio_tracks, play_selected_file, play_selected_file_at, play_selected_playlist, play_selected_queue_item, play_selected_recent, play_selected_sample_loop, reset_audio, restart_sound, start_file, start_play_all, switch_audio_device, toggle_mono_check};
+use crate::ui::{INFO_PANE_WIDTH_STEP, MAX_INFO_PANE_WIDTH, MIN_INFO_PANE_WIDTH};
+
+// how far the arrow keys seek in the playing sound by default, in seconds; shift uses the long step
+pub(crate) const SEEK_STEP_SECS: f64 = 5.0;
+
+pub(crate) const SEEK_LONG_STEP_SECS: f64 = 30.0;
+
+// panning moves by this much of kira's 0.0 (left) to 1.0 (right) range per keypress,
+// which is 10% of the way to either side
+pub(crate) const PANNING_STEP: f64 = 0.05;
+
+// two clicks on the same row of the list within this time count as a double click
+pub(crate) const DOUBLE_CLICK_TIME: std::time::Duration = std::time::Duration::from_millis(400);
+
+// dragging along the progress gauge seeks at most this often, so kira isn't sent a
+// seek for every column the mouse crosses
+pub(crate) const SCRUB_SEEK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+// does what a key bound in the keymap asks for
+pub(crate) fn perform_action(app_state: &mut AppState, audio: &mut AudioOutput, action: keymap::Action, key_code: crossterm::event::KeyCode) {
+    use keymap::Action;
+    // a count or 'g' typed beforehand only applies to the very next action
+    let count = app_state.pending_count.take();
+    let pending_g = std::mem::take(&mut app_state.pending_g);
+    match action {
+        Action::MoveDown if app_state.focus == Focus::Queue => (0..count.unwrap_or(1)).for_each(|_| app_state.next_queue_item()),
+        Action::MoveUp if app_state.focus == Focus::Queue => (0..count.unwrap_or(1)).for_each(|_| app_state.previous_queue_item()),
+        // the tree stops at its ends instead of wrapping around
+        Action::MoveDown if app_state.focus == Focus::Tree => app_state.move_tree_selection(count.unwrap_or(1) as isize),
+        Action::MoveUp if app_state.focus == Focus::Tree => app_state.move_tree_selection(-(count.unwrap_or(1) as isize)),
+        Action::PageDown if app_state.focus == Focus::Tree => {
+            let pages = count.unwrap_or(1) as isize;
+            app_state.move_tree_selection((app_state.tree_area.height as isize).saturating_mul(pages));
+        },
+        Action::PageUp if app_state.focus == Focus::Tree => {
+            let pages = count.unwrap_or(1) as isize;
+            app_state.move_tree_selection((app_state.tree_area.height as isize).saturating_mul(-pages));
+        },
+        // a counted move stops at the ends of the list instead of wrapping around
+        Action::MoveDown if count.is_some() => {
+            app_state.move_list_selection_by(count.unwrap_or(1) as isize);
+            app_state.schedule_preview();
+        },
+        Action::MoveUp if count.is_some() => {
+            app_state.move_list_selection_by(-(count.unwrap_or(1) as isize));
+            app_state.schedule_preview();
+        },
+        Action::MoveDown => {
+            app_state.next_list_item();
+            if let Err(err) = app_state.update_selected_file_info() {
+                app_state.last_error_msg = format!("File Info Error: {}", err);
+            }
+            app_state.schedule_preview();
+        },
+        Action::MoveUp => {
+            app_state.previous_list_item();
+            if let Err(err) = app_state.update_selected_file_info() {
+                app_state.last_error_msg = format!("File Info Error: {}", err);
+            }
+            app_state.schedule_preview();
+        },
+        Action::PageDown if app_state.focus == Focus::Files => {
+            let pages = count.unwrap_or(1) as isize;
+            app_state.move_list_selection_by((app_state.list_viewport_height as isize).saturating_mul(pages));
+            app_state.schedule_preview();
+        },
+        Action::PageUp if app_state.focus == Focus::Files => {
+            let pages = count.unwrap_or(1) as isize;
+            app_state.move_list_selection_by((app_state.list_viewport_height as isize).saturating_mul(-pages));
+            app_state.schedule_preview();
+        },
+        // with a count, 'gg' and 'G' both go to that row of the list like vim does
+        Action::GoPrefix if !pending_g => {
+            app_state.pending_g = true;
+            app_state.pending_count = count;
+        },
+        Action::First | Action::Last | Action::GoPrefix if app_state.focus == Focus::Files && count.is_some() => {
+            app_state.select_row_clamped(count.unwrap_or(1) - 1);
+            app_state.schedule_preview();
+        },
+        Action::First | Action::GoPrefix if app_state.focus == Focus::Files => {
+            app_state.move_list_selection_by(isize::MIN);
+            app_state.schedule_preview();
+        },
+        Action::Last if app_state.focus == Focus::Files => {
+            app_state.move_list_selection_by(isize::MAX);
+            app_state.schedule_preview();
+        },
+        Action::First | Action::GoPrefix if app_state.focus == Focus::Tree => app_state.move_tree_selection(isize::MIN),
+        Action::Last if app_state.focus == Focus::Tree => app_state.move_tree_selection(isize::MAX),
+        Action::PageDown | Action::PageUp | Action::First | Action::Last | Action::GoPrefix => {},
+        Action::Filter if app_state.focus != Focus::Queue => {
+            app_state.text_input = Some(TextInput {
+                kind: TextInputKind::Filter,
+                text: app_state.filter.clone(),
+            });
+        },
+        Action::Filter => {},
+        Action::ClearFilter => {
+            if !app_state.filter.is_empty() {
+                app_state.set_filter("");
+            }
+        },
+        Action::Find => {
+            app_state.focus = Focus::Files;
+            app_state.finder = Some(FuzzyFinder::default());
+            app_state.update_finder_results();
+        },
+        Action::ChangeDir => app_state.text_input = Some(TextInput::new(TextInputKind::ChangeDir)),
+        Action::Back => app_state.go_back(),
+        Action::Forward => app_state.go_forward(),
+        Action::Bookmark => {
+            if let Err(err) = app_state.bookmark_current_directory() {
+                app_state.last_error_msg = format!("Bookmark Error: {}", err);
+            }
+        },
+        Action::Bookmarks => app_state.open_bookmarks(),
+        Action::Recent => app_state.open_recent(),
+        Action::Sort => app_state.cycle_sort_mode(),
+        Action::ToggleHidden => app_state.toggle_hidden(),
+        Action::ToggleRecursive => app_state.toggle_recursive(),
+        Action::CycleExtFilter if app_state.focus != Focus::Queue => app_state.cycle_ext_filter(),
+        Action::CycleExtFilter => {},
+        Action::CycleRatingFilter if app_state.focus != Focus::Queue => app_state.cycle_rating_filter(),
+        Action::CycleRatingFilter => {},
+        Action::RatingMode => app_state.rating_mode = !app_state.rating_mode,
+        Action::Rate => {
+            if let crossterm::event::KeyCode::Char(digit) = key_code {
+                let rating = digit.to_digit(10).unwrap_or(0) as u8;
+                if let Err(err) = app_state.rate_files(rating) {
+                    app_state.last_error_msg = format!("Rating Error: {}", err);
+                }
+            }
+        },
+        Action::CycleTheme => app_state.theme_kind = app_state.theme_kind.next(),
+        Action::ToggleInfoPane => app_state.info_pane_mode = app_state.info_pane_mode.next(),
+        Action::GrowInfoPane => app_state.info_pane_width = (app_state.info_pane_width + INFO_PANE_WIDTH_STEP).min(MAX_INFO_PANE_WIDTH),
+        Action::ShrinkInfoPane => app_state.info_pane_width = app_state.info_pane_width.saturating_sub(INFO_PANE_WIDTH_STEP).max(MIN_INFO_PANE_WIDTH),
+        Action::ToggleMark => {
+            if let Err(err) = app_state.toggle_mark_selected() {
+                app_state.last_error_msg = format!("Mark Error: {}", err);
+            }
+        },
+        Action::ClearMarks => app_state.marked.clear(),
+        Action::Delete => {
+            if let Err(err) = app_state.confirm_delete() {
+                app_state.last_error_msg = format!("Delete Error: {}", err);
+            }
+        },
+        Action::CopyTo | Action::MoveTo if app_state.focus != Focus::Queue => {
+            app_state.text_input = Some(TextInput::new(if action == Action::MoveTo { TextInputKind::MoveTo } else { TextInputKind::CopyTo }));
+        },
+        Action::CopyTo | Action::MoveTo => {},
+        Action::OpenExternal => {
+            let result = match app_state.selected_file_path() {
+                Some(path) => app_state.open_externally(&path, false),
+                None => Ok(()),
+            };
+            if let Err(err) = result {
+                app_state.last_error_msg = format!("Open Error: {}", err);
+            }
+        },
+        Action::OpenDirExternal => {
+            let dir = app_state.current_directory_path.clone();
+            if let Err(err) = app_state.open_externally(&dir, true) {
+                app_state.last_error_msg = format!("Open Error: {}", err);
+            }
+        },
+        Action::Refresh => app_state.refresh_listing(),
+        Action::CopyPath => {
+            if let Err(err) = app_state.copy_selected_path() {
+                app_state.last_error_msg = format!("Clipboard Error: {}", err);
+            }
+        },
+        Action::Duplicates => {
+            if let Err(err) = app_state.find_duplicates(false) {
+                app_state.last_error_msg = format!("Duplicates Error: {}", err);
+            }
+        },
+        Action::Loudness => {
+            if let Err(err) = app_state.measure_selected_loudness() {
+                app_state.last_error_msg = format!("Loudness Error: {}", err);
+            }
+        },
+        Action::Convert => {
+            match app_state.selected_file_path() {
+                Some(path) => app_state.prompt = Some(Prompt::ConvertFormat(path)),
+                None => app_state.last_error_msg = "Convert Error: select a file to convert".to_string(),
+            }
+        },
+        Action::ExportMarked => {
+            if app_state.export_files().is_empty() {
+                app_state.last_error_msg = "Export Error: no files are marked or listed".to_string();
+            } else {
+                app_state.text_input = Some(TextInput {
+                    kind: TextInputKind::ExportMarked,
+                    text: if app_state.marked.is_empty() { "files.txt" } else { "marked.txt" }.to_string(),
+                });
+            }
+        },
+        Action::SavePlaylist => {
+            if app_state.playlist_files().is_empty() {
+                app_state.last_error_msg = "Playlist Error: the queue is empty and no files are marked".to_string();
+            } else {
+                app_state.text_input = Some(TextInput {
+                    kind: TextInputKind::SavePlaylist,
+                    text: "playlist.m3u8".to_string(),
+                });
+            }
+        },
+        Action::QueueFile => app_state.queue_selected_file(),
+        Action::Unqueue => {
+            if app_state.focus == Focus::Queue {
+                app_state.remove_selected_queue_item();
+            }
+        },
+        Action::ToggleFocus => app_state.toggle_focus(),
+        Action::Next => {
+            if let Err(err) = advance_to_next_file(app_state, audio) {
+                app_state.last_error_msg = format!("Playback Error: {}", err);
+            }
+        },
+        Action::AdvanceMode => app_state.advance_mode = app_state.advance_mode.next(),
+        Action::Shuffle => app_state.toggle_shuffle(),
+        Action::PlayAll => {
+            if let Err(err) = start_play_all(app_state, audio) {
+                app_state.play_all = None;
+                app_state.last_error_msg = format!("Play All Error: {}", err);
+            }
+        },
+        Action::PlayOrEnter if app_state.focus == Focus::Queue => {
+            if let Err(err) = play_selected_queue_item(app_state, audio) {
+                app_state.last_error_msg = format!("Playback Error: {}", err);
+            }
+        },
+        Action::PlayOrEnter if app_state.focus == Focus::Tree => app_state.toggle_tree_selected(),
+        Action::PlayOrEnter => {
+            if app_state.is_file_selected() {
+                match play_selected_file(app_state, audio) {
+                    Ok(()) => {
+                        if let Some(snd_path) = app_state.selected_file_path() {
+                            app_state.remember_played(&snd_path);
+                        }
+                    },
+                    Err(err) => app_state.last_error_msg = format!("Playback Error: {}", err),
+                }
+            } else if app_state.is_playlist_selected() {
+                if let Err(err) = play_selected_playlist(app_state, audio) {
+                    app_state.last_error_msg = format!("Playlist Error: {}", err);
+                }
+            } else if app_state.is_dir_selected() { 
+                if let Some(selected_dir) = app_state.selected_entry_path() {
+                    // going up to the parent selects the directory we came out of
+                    let came_from = if selected_dir.ends_with("..") {
+                        app_state.current_directory_path.file_name().map(|f| f.to_os_string())
+                    } else {
+                        None
+                    };
+                    match selected_dir.canonicalize() {
+                        Ok(new_dir) => {
+                            app_state.enter_directory(&new_dir);
+                            if let Some(child_name) = came_from {
+                                app_state.select_directory_named(&child_name);
+                            }
+                        },
+                        Err(err) => app_state.last_error_msg = format!("Couldn't build path to selection: {}", err),
+                    }
+                }
+            }
+        },
+        Action::Restart => {
+            if let Err(err) = restart_sound(app_state, audio) {
+                app_state.last_error_msg = format!("Restart Error: {}", err);
+            }
+        },
+        Action::Stop => {
+            // stopping cancels a whole play-all run, not just the current file
+            app_state.play_all = None;
+            if let Err(err) = app_state.sound_state.stop_sound(app_state.sound_state.stop_fade) {
+                app_state.last_error_msg = format!("Playback Stop Error: {}", err);
+            }
+        },
+        Action::PlayFrom => {
+            if app_state.is_file_selected() {
+                app_state.text_input = Some(TextInput::new(TextInputKind::StartOffset));
+            }
+        },
+        Action::PlayLoop => {
+            if let Err(err) = play_selected_sample_loop(app_state, audio) {
+                app_state.last_error_msg = format!("Loop Error: {}", err);
+            }
+        },
+        Action::Preview => app_state.toggle_preview(),
+        Action::SeekBack | Action::SeekForward | Action::SeekBackLong | Action::SeekForwardLong => {
+            let step = match action {
+                Action::SeekBack => -app_state.sound_state.seek_step,
+                Action::SeekForward => app_state.sound_state.seek_step,
+                Action::SeekBackLong => -app_state.sound_state.seek_long_step,
+                _ => app_state.sound_state.seek_long_step,
+            };
+            if let Err(err) = app_state.sound_state.seek_by(step) {
+                app_state.last_error_msg = format!("Seek Error: {}", err);
+            }
+        },
+        Action::JumpToTenth => {
+            // each digit jumps to that tenth of the sound, so '3' goes to 30%
+            if let crossterm::event::KeyCode::Char(digit) = key_code {
+                if app_state.sound_state.is_playing() {
+                    let fraction = digit.to_digit(10).unwrap_or(0) as f64 / 10.0;
+                    if let Err(err) = app_state.sound_state.seek_to_fraction(fraction) {
+                        app_state.last_error_msg = format!("Seek Error: {}", err);
+                    }
+                }
+            }
+        },
+        Action::VolumeUp | Action::VolumeDown => {
+            let step = if action == Action::VolumeUp { VOLUME_STEP } else { -VOLUME_STEP };
+            if let Err(err) = app_state.sound_state.adjust_volume(step) {
+                app_state.last_error_msg = format!("Volume Error: {}", err);
+            }
+        },
+        Action::Mute => {
+            if let Err(err) = app_state.sound_state.toggle_mute() {
+                app_state.last_error_msg = format!("Mute Error: {}", err);
+            }
+        },
+        Action::MonoCheck => {
+            if let Err(err) = toggle_mono_check(app_state, audio) {
+                app_state.last_error_msg = format!("Mono Check Error: {}", err);
+            }
+        },
+        Action::ReplayGain => {
+            if let Err(err) = app_state.sound_state.toggle_replay_gain() {
+                app_state.last_error_msg = format!("Volume Error: {}", err);
+            }
+        },
+        Action::ResetAudio => {
+            if let Err(err) = reset_audio(app_state, audio) {
+                app_state.last_error_msg = format!("Audio Error: {}", err);
+            }
+        },
+        Action::PickDevice => match DevicePicker::new(audio.device.as_deref()) {
+            Ok(picker) => app_state.device_picker = Some(picker),
+            Err(err) => app_state.last_error_msg = format!("Audio Error: {}", err),
+        },
+        Action::PickTrack => {
+            if let Some(snd_path) = app_state.selected_file_path() {
+                match list_audio_tracks(&snd_path) {
+                    Ok(tracks) if tracks.len() > 1 => {
+                        let options = app_state.sound_state.play_options();
+                        app_state.track_picker = Some(TrackPicker::new(snd_path, tracks, options));
+                    },
+                    Ok(_) => app_state.last_info_msg = "This file only has the one audio track".to_string(),
+                    Err(err) => app_state.last_error_msg = format!("Track Error: {}", err),
+                }
+            }
+        },
+        Action::PanLeft | Action::PanRight | Action::PanCenter => {
+            let panning = match action {
+                Action::PanLeft => app_state.sound_state.panning - PANNING_STEP,
+                Action::PanRight => app_state.sound_state.panning + PANNING_STEP,
+                _ => 0.5,
+            };
+            if let Err(err) = app_state.sound_state.set_panning(panning) {
+                app_state.last_error_msg = format!("Panning Error: {}", err);
+            }
+        },
+        Action::SpeedDown | Action::SpeedUp | Action::SpeedReset => {
+            let rate = match action {
+                Action::SpeedDown => app_state.sound_state.playback_rate - PLAYBACK_RATE_STEP,
+                Action::SpeedUp => app_state.sound_state.playback_rate + PLAYBACK_RATE_STEP,
+                _ => 1.0,
+            };
+            if let Err(err) = app_state.sound_state.set_playback_rate(rate) {
+                app_state.last_error_msg = format!("Playback Rate Error: {}", err);
+            }
+        },
+        Action::LoopStart => {
+            if let Err(err) = app_state.sound_state.set_loop_start() {
+                app_state.last_error_msg = format!("Loop Error: {}", err);
+            }
+        },
+        Action::LoopEnd => {
+            if let Err(err) = app_state.sound_state.set_loop_end() {
+                app_state.last_error_msg = format!("Loop Error: {}", err);
+            }
+        },
+        Action::ClearLoop => app_state.sound_state.clear_loop_region(),
+        Action::Trim => {
+            let sound_state = &app_state.sound_state;
+            match (sound_state.playing_path.clone(), sound_state.loop_start, sound_state.loop_end) {
+                (Some(path), Some(start), Some(end)) => {
+                    let region = TrimRegion { start: start.min(end), end: Some(start.max(end)) };
+                    if let Err(err) = app_state.request_trim(path, region) {
+                        app_state.last_error_msg = format!("Trim Error: {}", err);
+                    }
+                },
+                // without a loop region the range of the selected file is typed in
+                _ if app_state.is_file_selected() => app_state.text_input = Some(TextInput::new(TextInputKind::TrimRange)),
+                _ => app_state.last_error_msg = "Trim Error: set a loop region while playing, or select a file".to_string(),
+            }
+        },
+        Action::Help => app_state.help_scroll = Some(0),
+        Action::ErrorLog => app_state.error_log_scroll = Some(0),
+        // quitting is handled by the event loop
+        Action::Quit => {},
+    }
+}
+
+// handles the mouse in the main view: clicking selects a row in the file list and
+// double clicking plays it, the wheel moves the selection, and clicking the
+// progress gauge seeks
+pub(crate) fn handle_mouse_event(app_state: &mut AppState, audio: &mut AudioOutput, mouse: crossterm::event::MouseEvent) {
+    // the mouse is ignored while anything is drawn over the list
+    if app_state.prompt.is_some() || app_state.text_input.is_some() || app_state.finder.is_some()
+        || app_state.bookmark_list.is_some() || app_state.device_picker.is_some() || app_state.track_picker.is_some()
+        || app_state.help_scroll.is_some() {
+        return;
+    }
+    let in_area = |area: Rect| {
+        mouse.column >= area.x && mouse.column < area.x + area.width
+            && mouse.row >= area.y && mouse.row < area.y + area.height
+    };
+
+    match mouse.kind {
+        crossterm::event::MouseEventKind::ScrollDown => perform_action(app_state, audio, keymap::Action::MoveDown, crossterm::event::KeyCode::Null),
+        crossterm::event::MouseEventKind::ScrollUp => perform_action(app_state, audio, keymap::Action::MoveUp, crossterm::event::KeyCode::Null),
+        crossterm::event::MouseEventKind::Down(crossterm::event::MouseButton::Left) => {
+            if let Some(gauge_area) = app_state.gauge_area.filter(|area| in_area(*area)) {
+                app_state.clear_error();
+                scrub_to(app_state, gauge_fraction(gauge_area, mouse.column));
+                // holding the button down and dragging keeps seeking
+                app_state.scrub = Some(Scrub { area: gauge_area, last_seek: std::time::Instant::now(), pending: None });
+                return;
+            }
+            if app_state.tree_shown && in_area(app_state.tree_area) {
+                app_state.clear_error();
+                let offset = app_state.tree.as_ref().map_or(0, |tree| tree.offset);
+                app_state.click_tree_row(offset + (mouse.row - app_state.tree_area.y) as usize);
+                return;
+            }
+            if app_state.focus == Focus::Queue || !in_area(app_state.list_area) {
+                return;
+            }
+            app_state.focus = Focus::Files;
+            let row = app_state.list_offset + (mouse.row - app_state.list_area.y) as usize;
+            if row >= app_state.visible_entries.len() {
+                return;
+            }
+            app_state.clear_error();
+            let now = std::time::Instant::now();
+            let double_click = matches!(app_state.last_click, Some((at, last_row)) if last_row == row && now.duration_since(at) <= DOUBLE_CLICK_TIME);
+            if double_click {
+                app_state.last_click = None;
+                perform_action(app_state, audio, keymap::Action::PlayOrEnter, crossterm::event::KeyCode::Null);
+            } else {
+                app_state.last_click = Some((now, row));
+                app_state.select_list_item(row);
+                app_state.schedule_preview();
+            }
+        },
+        crossterm::event::MouseEventKind::Drag(crossterm::event::MouseButton::Left) => {
+            let scrub = match &mut app_state.scrub {
+                Some(scrub) => scrub,
+                None => return,
+            };
+            let fraction = gauge_fraction(scrub.area, mouse.column);
+            if scrub.last_seek.elapsed() < SCRUB_SEEK_INTERVAL {
+                scrub.pending = Some(fraction);
+                return;
+            }
+            scrub.last_seek = std::time::Instant::now();
+            scrub.pending = None;
+            scrub_to(app_state, fraction);
+        },
+        // the drag ends where the button was let go, even if that was too soon after
+        // the last seek to go there
+        crossterm::event::MouseEventKind::Up(crossterm::event::MouseButton::Left) => {
+            if let Some(fraction) = app_state.scrub.take().and_then(|scrub| scrub.pending) {
+                scrub_to(app_state, fraction);
+            }
+        },
+        _ => {},
+    }
+}
+
+// how far along the gauge drawn in `area` the mouse is at `column`, from 0.0 to
+// 1.0, with columns past either end counting as that end
+fn gauge_fraction(area: Rect, column: u16) -> f64 {
+    column.saturating_sub(area.x).min(area.width) as f64 / area.width.max(1) as f64
+}
+
+// seeks the playing sound to `fraction` of the way through it
+fn scrub_to(app_state: &mut AppState, fraction: f64) {
+    if let Err(err) = app_state.sound_state.seek_to_fraction(fraction) {
+        app_state.last_error_msg = format!("Seek Error: {}", err);
+    }
+}
+
+// handles a key press while the help or error log overlay is open. the scrolling
+// keys scroll it and any other key closes it.
+pub(crate) fn handle_scroll_key(overlay_scroll: &mut Option<u16>, key_code: crossterm::event::KeyCode) {
+    let scroll = match overlay_scroll {
+        Some(scroll) => scroll,
+        None => return,
+    };
+    match key_code {
+        crossterm::event::KeyCode::Char('j') | crossterm::event::KeyCode::Down => *scroll = scroll.saturating_add(1),
+        crossterm::event::KeyCode::Char('k') | crossterm::event::KeyCode::Up => *scroll = scroll.saturating_sub(1),
+        crossterm::event::KeyCode::PageDown => *scroll = scroll.saturating_add(10),
+        crossterm::event::KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+        _ => *overlay_scroll = None,
+    }
+}
+
+// handles a key press while a prompt is waiting for a yes or no answer
+pub(crate) fn handle_prompt_key(app_state: &mut AppState, key_code: crossterm::event::KeyCode) {
+    // a file in the way of a copy or move has its own choices
+    if let Some(Prompt::ConvertFormat(_)) = &app_state.prompt {
+        let format = match key_code {
+            crossterm::event::KeyCode::Char('w') => Some(ExportFormat::Wav),
+            crossterm::event::KeyCode::Char('f') => Some(ExportFormat::Flac),
+            crossterm::event::KeyCode::Esc => None,
+            _ => return,
+        };
+        if let (Some(Prompt::ConvertFormat(path)), Some(format)) = (app_state.prompt.take(), format) {
+            if let Err(err) = app_state.request_convert(path, format) {
+                app_state.last_error_msg = format!("Convert Error: {}", err);
+            }
+        }
+        return;
+    }
+
+    if let Some(Prompt::Collision(_)) = &app_state.prompt {
+        let choice = match key_code {
+            crossterm::event::KeyCode::Char('s') | crossterm::event::KeyCode::Esc => CollisionChoice::Skip,
+            crossterm::event::KeyCode::Char('o') => CollisionChoice::Overwrite,
+            crossterm::event::KeyCode::Char('r') => CollisionChoice::Rename,
+            _ => return,
+        };
+        app_state.prompt = None;
+        if let Some(op) = &app_state.file_operation {
+            // the operation finished or gave up if nobody's listening for the answer
+            _ = op.answers.send(choice);
+        }
+        return;
+    }
+
+    let answer = match key_code {
+        crossterm::event::KeyCode::Char('y') => true,
+        crossterm::event::KeyCode::Char('n') | crossterm::event::KeyCode::Esc => false,
+        _ => return,
+    };
+    if let Some(prompt) = app_state.prompt.take() {
+        match prompt {
+            Prompt::KeepPlayAll => {
+                if !answer {
+                    app_state.play_all = None;
+                }
+            },
+            Prompt::Collision(_) | Prompt::ConvertFormat(_) => {},
+            Prompt::QuitWithJobs(_) => app_state.quit_confirmed = answer,
+            Prompt::OverwritePlaylist(path, files) => {
+                if answer {
+                    if let Err(err) = app_state.save_playlist(&path, &files) {
+                        app_state.last_error_msg = format!("Playlist Error: {}", err);
+                    }
+                }
+            },
+            Prompt::OverwriteTrim(path, region) => {
+                if answer {
+                    if let Err(err) = app_state.start_trim(path, region) {
+                        app_state.last_error_msg = format!("Trim Error: {}", err);
+                    }
+                }
+            },
+            Prompt::OverwriteConvert(path, format) => {
+                if answer {
+                    if let Err(err) = app_state.start_convert(path, format) {
+                        app_state.last_error_msg = format!("Convert Error: {}", err);
+                    }
+                }
+            },
+            Prompt::Delete { paths, permanent } => {
+                if answer {
+                    if let Err(err) = app_state.delete_files(&paths, permanent) {
+                        app_state.last_error_msg = format!("Delete Error: {}", err);
+                    }
+                }
+            },
+        }
+    }
+}
+
+// handles a key press while the user is typing into the text input line
+pub(crate) fn handle_text_input_key(app_state: &mut AppState, audio: &mut AudioOutput, key_code: crossterm::event::KeyCode) {
+    let input = match &mut app_state.text_input {
+        Some(input) => input,
+        None => return,
+    };
+    match key_code {
+        crossterm::event::KeyCode::Char(c) => input.text.push(c),
+        crossterm::event::KeyCode::Backspace => _ = input.text.pop(),
+        crossterm::event::KeyCode::Esc => {
+            // cancelling the filter input clears the filter too
+            if let Some(TextInputKind::Filter) = app_state.text_input.take().map(|input| input.kind) {
+                app_state.set_filter("");
+            }
+            return;
+        },
+        crossterm::event::KeyCode::Enter => {
+            if let Some(input) = app_state.text_input.take() {
+                submit_text_input(app_state, audio, input);
+            }
+            return;
+        },
+        crossterm::event::KeyCode::Tab if matches!(input.kind, TextInputKind::ChangeDir | TextInputKind::CopyTo | TextInputKind::MoveTo) => {
+            match complete_directory_path(&app_state.current_directory_path, &input.text, app_state.show_hidden) {
+                Some(completed) => input.text = completed,
+                None => app_state.last_error_msg = "No matching directories".to_string(),
+            }
+            return;
+        },
+        _ => return,
+    }
+
+    // the filter updates the list live as it is typed
+    if let Some(TextInput { kind: TextInputKind::Filter, text }) = &app_state.text_input {
+        let filter = text.clone();
+        app_state.set_filter(&filter);
+    }
+}
+
+// handles a key press while the fuzzy finder overlay is open
+pub(crate) fn handle_finder_key(app_state: &mut AppState, key_code: crossterm::event::KeyCode) {
+    let finder = match &mut app_state.finder {
+        Some(finder) => finder,
+        None => return,
+    };
+    match key_code {
+        crossterm::event::KeyCode::Esc => app_state.finder = None,
+        crossterm::event::KeyCode::Enter => {
+            let entry = finder.selected_entry();
+            app_state.finder = None;
+            if let Some(entry) = entry {
+                // the finder searches everything, so a filter may be hiding the entry
+                if !app_state.visible_entries.contains(&entry) {
+                    app_state.set_filter("");
+                }
+                app_state.select_entry(entry);
+                app_state.schedule_preview();
+            }
+        },
+        crossterm::event::KeyCode::Down => finder.move_selection(1),
+        crossterm::event::KeyCode::Up => finder.move_selection(-1),
+        crossterm::event::KeyCode::Char(c) => {
+            finder.query.push(c);
+            app_state.update_finder_results();
+        },
+        crossterm::event::KeyCode::Backspace => {
+            finder.query.pop();
+            app_state.update_finder_results();
+        },
+        _ => {},
+    }
+}
+
+// handles a key press while the bookmarks overlay is open
+pub(crate) fn handle_bookmarks_key(app_state: &mut AppState, key_code: crossterm::event::KeyCode) {
+    let list_state = match &mut app_state.bookmark_list {
+        Some(list_state) => list_state,
+        None => return,
+    };
+    let count = app_state.bookmarks.len();
+    let selected = list_state.selected();
+    let result = match key_code {
+        crossterm::event::KeyCode::Esc | crossterm::event::KeyCode::Char('B') => {
+            app_state.bookmark_list = None;
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('j') | crossterm::event::KeyCode::Down if count > 0 => {
+            list_state.select(Some(selected.map(|i| (i + 1) % count).unwrap_or(0)));
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('k') | crossterm::event::KeyCode::Up if count > 0 => {
+            list_state.select(Some(selected.map(|i| (i + count - 1) % count).unwrap_or(0)));
+            Ok(())
+        },
+        crossterm::event::KeyCode::Enter | crossterm::event::KeyCode::Char(' ') => match selected {
+            Some(index) => app_state.jump_to_bookmark(index),
+            None => Ok(()),
+        },
+        crossterm::event::KeyCode::Char(c @ '1'..='9') => app_state.jump_to_bookmark(c as usize - '1' as usize),
+        crossterm::event::KeyCode::Char('x') => app_state.remove_selected_bookmark().map_err(|e| e.into()),
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        app_state.last_error_msg = format!("Bookmark Error: {}", err);
+    }
+}
+
+// handles a key press while the recently played overlay is open. enter goes to the
+// file and space plays it right away.
+pub(crate) fn handle_recent_key(app_state: &mut AppState, audio: &mut AudioOutput, key_code: crossterm::event::KeyCode) {
+    let list_state = match &mut app_state.recent_list {
+        Some(list_state) => list_state,
+        None => return,
+    };
+    let count = app_state.recent.len();
+    let selected = list_state.selected();
+    let result = match key_code {
+        crossterm::event::KeyCode::Esc | crossterm::event::KeyCode::Char('U') => {
+            app_state.recent_list = None;
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('j') | crossterm::event::KeyCode::Down if count > 0 => {
+            list_state.select(Some(selected.map(|i| (i + 1) % count).unwrap_or(0)));
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('k') | crossterm::event::KeyCode::Up if count > 0 => {
+            list_state.select(Some(selected.map(|i| (i + count - 1) % count).unwrap_or(0)));
+            Ok(())
+        },
+        crossterm::event::KeyCode::Enter => app_state.jump_to_recent(),
+        crossterm::event::KeyCode::Char(' ') => play_selected_recent(app_state, audio),
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        app_state.last_error_msg = format!("Recent Error: {}", err);
+    }
+}
+
+// handles a key press while the duplicates overlay is open. closing it while the
+// files are being hashed gives up on the hashing.
+pub(crate) fn handle_duplicates_key(app_state: &mut AppState, key_code: crossterm::event::KeyCode) {
+    let list = match &mut app_state.duplicates {
+        Some(list) => list,
+        None => return,
+    };
+    let count = list.row_count();
+    let selected = list.list_state.selected();
+    let result = match key_code {
+        crossterm::event::KeyCode::Esc | crossterm::event::KeyCode::Char('Z') => {
+            app_state.duplicates = None;
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('j') | crossterm::event::KeyCode::Down if count > 0 => {
+            list.list_state.select(Some(selected.map(|i| (i + 1) % count).unwrap_or(0)));
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('k') | crossterm::event::KeyCode::Up if count > 0 => {
+            list.list_state.select(Some(selected.map(|i| (i + count - 1) % count).unwrap_or(0)));
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char(' ') | crossterm::event::KeyCode::Char('m') => {
+            app_state.toggle_duplicate_mark();
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('a') => {
+            app_state.mark_duplicate_copies();
+            Ok(())
+        },
+        crossterm::event::KeyCode::Char('d') => app_state.confirm_delete_duplicates(),
+        // hashes again, switching between comparing bytes and decoded sound
+        crossterm::event::KeyCode::Char('D') => {
+            let deep = !list.deep;
+            app_state.find_duplicates(deep)
+        },
+        crossterm::event::KeyCode::Enter => {
+            app_state.jump_to_duplicate();
+            Ok(())
+        },
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        app_state.last_error_msg = format!("Duplicates Error: {}", err);
+    }
+}
+
+// handles a key press while the output device overlay is open
+pub(crate) fn handle_device_key(app_state: &mut AppState, audio: &mut AudioOutput, key_code: crossterm::event::KeyCode) {
+    let picker = match &mut app_state.device_picker {
+        Some(picker) => picker,
+        None => return,
+    };
+    // the default device is the extra row at the top
+    let count = picker.names.len() + 1;
+    let selected = picker.list_state.selected();
+    match key_code {
+        crossterm::event::KeyCode::Esc | crossterm::event::KeyCode::Char('D') => app_state.device_picker = None,
+        crossterm::event::KeyCode::Char('j') | crossterm::event::KeyCode::Down => {
+            picker.list_state.select(Some(selected.map(|i| (i + 1) % count).unwrap_or(0)));
+        },
+        crossterm::event::KeyCode::Char('k') | crossterm::event::KeyCode::Up => {
+            picker.list_state.select(Some(selected.map(|i| (i + count - 1) % count).unwrap_or(0)));
+        },
+        crossterm::event::KeyCode::Enter | crossterm::event::KeyCode::Char(' ') => {
+            let device = picker.selected_device();
+            app_state.device_picker = None;
+            if let Err(err) = switch_audio_device(app_state, audio, device) {
+                app_state.last_error_msg = format!("Audio Error: {}", err);
+            }
+        },
+        _ => {},
+    }
+}
+
+// handles a key press while the audio track overlay is open
+pub(crate) fn handle_track_key(app_state: &mut AppState, audio: &mut AudioOutput, key_code: crossterm::event::KeyCode) {
+    let picker = match &mut app_state.track_picker {
+        Some(picker) => picker,
+        None => return,
+    };
+    let count = picker.tracks.len().max(1);
+    let selected = picker.list_state.selected();
+    match key_code {
+        crossterm::event::KeyCode::Esc | crossterm::event::KeyCode::Char('K') => app_state.track_picker = None,
+        crossterm::event::KeyCode::Char('j') | crossterm::event::KeyCode::Down => {
+            picker.list_state.select(Some(selected.map(|i| (i + 1) % count).unwrap_or(0)));
+        },
+        crossterm::event::KeyCode::Char('k') | crossterm::event::KeyCode::Up => {
+            picker.list_state.select(Some(selected.map(|i| (i + count - 1) % count).unwrap_or(0)));
+        },
+        crossterm::event::KeyCode::Enter | crossterm::event::KeyCode::Char(' ') => {
+            let picker = match app_state.track_picker.take() {
+                Some(picker) => picker,
+                None => return,
+            };
+            let track = picker.list_state.selected().and_then(|index| picker.tracks.get(index));
+            if let Some(track) = track {
+                app_state.choose_track(&picker.path, track.id);
+                if let Err(err) = start_file(app_state, audio, picker.path, picker.options) {
+                    app_state.last_error_msg = format!("Playback Error: {}", err);
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+// acts on the text the user entered once they press enter
+pub(crate) fn submit_text_input(app_state: &mut AppState, audio: &mut AudioOutput, input: TextInput) {
+    match input.kind {
+        TextInputKind::StartOffset => {
+            match parse_timestamp(&input.text) {
+                Some(offset) => {
+                    if let Err(err) = play_selected_file_at(app_state, audio, offset) {
+                        app_state.last_error_msg = format!("Playback Error: {}", err);
+                    }
+                },
+                None => app_state.last_error_msg = format!("Couldn't read '{}' as a time, use mm:ss", input.text),
+            }
+        },
+        // the filter was already applied while typing and stays until cleared with escape
+        TextInputKind::Filter => app_state.set_filter(&input.text),
+        TextInputKind::ExportMarked => {
+            let file_path = expand_path(&app_state.current_directory_path, input.text.trim());
+            match app_state.export_manifest(&file_path) {
+                Ok(count) => app_state.last_info_msg = format!("Exported {} files to {}", count, file_path.display()),
+                Err(err) => app_state.last_error_msg = format!("Export Error: {}", err),
+            }
+        },
+        TextInputKind::SavePlaylist => {
+            let mut file_path = expand_path(&app_state.current_directory_path, input.text.trim());
+            if file_path.extension().is_none() {
+                file_path.set_extension("m3u8");
+            }
+            let files = app_state.playlist_files();
+            if file_path.exists() {
+                app_state.prompt = Some(Prompt::OverwritePlaylist(file_path, files));
+            } else if let Err(err) = app_state.save_playlist(&file_path, &files) {
+                app_state.last_error_msg = format!("Playlist Error: {}", err);
+            }
+        },
+        TextInputKind::TrimRange => {
+            let path = match app_state.selected_file_path() {
+                Some(path) => path,
+                None => return,
+            };
+            match parse_trim_range(&input.text) {
+                Some(region) => {
+                    if let Err(err) = app_state.request_trim(path, region) {
+                        app_state.last_error_msg = format!("Trim Error: {}", err);
+                    }
+                },
+                None => {
+                    app_state.last_error_msg = format!("Couldn't read '{}' as a range, use mm:ss-mm:ss", input.text);
+                    app_state.text_input = Some(input);
+                },
+            }
+        },
+        TextInputKind::CopyTo | TextInputKind::MoveTo => {
+            let moving = input.kind == TextInputKind::MoveTo;
+            let result = resolve_directory(&app_state.current_directory_path, &input.text)
+                .and_then(|dest_dir| app_state.start_file_operation(dest_dir, moving));
+            if let Err(err) = result {
+                app_state.last_error_msg = format!("{} Error: {}", if moving { "Move" } else { "Copy" }, err);
+                app_state.text_input = Some(input);
+            }
+        },
+        TextInputKind::ChangeDir => {
+            match resolve_directory(&app_state.current_directory_path, &input.text) {
+                Ok(dir) => app_state.enter_directory(&dir),
+                Err(err) => {
+                    app_state.last_error_msg = format!("Change Dir Error: {}", err);
+                    // keep the prompt open so the path can be fixed up
+                    app_state.text_input = Some(input);
+                },
+            }
+        },
+    }
+}
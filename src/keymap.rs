@@ -0,0 +1,550 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the table of keys the main view responds to. the key handling and the help
+// overlay are both built from it so they can't drift apart. the defaults can
+// be changed with the `[keys]` table of the config file.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Everything a key can do in the main view.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    MoveDown,
+    MoveUp,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    GoPrefix, // 'gg' is the vim way to get to the top
+    Filter,
+    ClearFilter,
+    Find,
+    ChangeDir,
+    Back,
+    Forward,
+    Bookmark,
+    Bookmarks,
+    Recent,
+    Sort,
+    ToggleHidden,
+    ToggleRecursive,
+    CycleExtFilter,
+    CycleRatingFilter,
+    RatingMode,
+    Rate, // the digit pressed is the rating
+    ToggleMark,
+    ClearMarks,
+    ExportMarked,
+    Convert,
+    Loudness,
+    Duplicates,
+    Delete,
+    CopyTo,
+    CopyPath,
+    Refresh,
+    OpenExternal,
+    OpenDirExternal,
+    MoveTo,
+    QueueFile,
+    Unqueue,
+    SavePlaylist,
+    ToggleFocus,
+    Next,
+    AdvanceMode,
+    Shuffle,
+    PlayAll,
+    PlayOrEnter,
+    Restart,
+    Stop,
+    PlayFrom,
+    PlayLoop,
+    Preview,
+    SeekBack,
+    SeekForward,
+    SeekBackLong,
+    SeekForwardLong,
+    JumpToTenth, // the digit pressed picks the tenth
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    MonoCheck,
+    ReplayGain,
+    PanLeft,
+    PanRight,
+    PanCenter,
+    SpeedDown,
+    SpeedUp,
+    SpeedReset,
+    LoopStart,
+    LoopEnd,
+    ClearLoop,
+    Trim,
+    ResetAudio,
+    PickDevice,
+    PickTrack,
+    CycleTheme,
+    ToggleInfoPane,
+    GrowInfoPane,
+    ShrinkInfoPane,
+    ErrorLog,
+    Help,
+    Quit,
+}
+
+impl Action {
+    /// The name the action goes by in the `[keys]` table of the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveDown => "down",
+            Action::MoveUp => "up",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::GoPrefix => "go_prefix",
+            Action::Filter => "filter",
+            Action::ClearFilter => "clear_filter",
+            Action::Find => "find",
+            Action::ChangeDir => "change_dir",
+            Action::Back => "back",
+            Action::Forward => "forward",
+            Action::Bookmark => "bookmark",
+            Action::Bookmarks => "bookmarks",
+            Action::Recent => "recent",
+            Action::Sort => "sort",
+            Action::ToggleHidden => "toggle_hidden",
+            Action::ToggleRecursive => "toggle_recursive",
+            Action::CycleExtFilter => "ext_filter",
+            Action::CycleRatingFilter => "rating_filter",
+            Action::RatingMode => "rating_mode",
+            Action::Rate => "rate",
+            Action::ToggleMark => "mark",
+            Action::ClearMarks => "clear_marks",
+            Action::ExportMarked => "export_marked",
+            Action::Convert => "convert",
+            Action::Loudness => "loudness",
+            Action::Duplicates => "duplicates",
+            Action::Delete => "delete",
+            Action::CopyTo => "copy",
+            Action::CopyPath => "copy_path",
+            Action::Refresh => "refresh",
+            Action::OpenExternal => "open",
+            Action::OpenDirExternal => "open_dir",
+            Action::MoveTo => "move",
+            Action::QueueFile => "queue",
+            Action::Unqueue => "unqueue",
+            Action::SavePlaylist => "save_playlist",
+            Action::ToggleFocus => "toggle_focus",
+            Action::Next => "next",
+            Action::AdvanceMode => "advance_mode",
+            Action::Shuffle => "shuffle",
+            Action::PlayAll => "play_all",
+            Action::PlayOrEnter => "play",
+            Action::Restart => "restart",
+            Action::Stop => "stop",
+            Action::PlayFrom => "play_from",
+            Action::PlayLoop => "play_loop",
+            Action::Preview => "preview",
+            Action::SeekBack => "seek_back",
+            Action::SeekForward => "seek_forward",
+            Action::SeekBackLong => "seek_back_long",
+            Action::SeekForwardLong => "seek_forward_long",
+            Action::JumpToTenth => "jump_to_tenth",
+            Action::VolumeUp => "volume_up",
+            Action::VolumeDown => "volume_down",
+            Action::Mute => "mute",
+            Action::MonoCheck => "mono_check",
+            Action::ReplayGain => "replay_gain",
+            Action::PanLeft => "pan_left",
+            Action::PanRight => "pan_right",
+            Action::PanCenter => "pan_center",
+            Action::SpeedDown => "speed_down",
+            Action::SpeedUp => "speed_up",
+            Action::SpeedReset => "speed_reset",
+            Action::LoopStart => "loop_start",
+            Action::LoopEnd => "loop_end",
+            Action::ClearLoop => "clear_loop",
+            Action::Trim => "trim",
+            Action::ResetAudio => "reset_audio",
+            Action::PickDevice => "pick_device",
+            Action::PickTrack => "pick_track",
+            Action::CycleTheme => "theme",
+            Action::ToggleInfoPane => "info_pane",
+            Action::GrowInfoPane => "grow_info_pane",
+            Action::ShrinkInfoPane => "shrink_info_pane",
+            Action::ErrorLog => "error_log",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+}
+
+/// The groups the help overlay lists the bindings under.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Category {
+    Navigation,
+    Files,
+    Queue,
+    Playback,
+    General,
+}
+
+impl Category {
+    /// Every category in the order the help overlay shows them.
+    pub const ALL: [Category; 5] = [
+        Category::Navigation,
+        Category::Files,
+        Category::Queue,
+        Category::Playback,
+        Category::General,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Category::Navigation => "Navigation",
+            Category::Files => "Files",
+            Category::Queue => "Queue and Auto-advance",
+            Category::Playback => "Playback",
+            Category::General => "General",
+        }
+    }
+}
+
+/// A key along with the modifiers that have to be held down with it.
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyPress {
+    const fn plain(code: KeyCode) -> Self {
+        KeyPress { code, modifiers: KeyModifiers::NONE }
+    }
+
+    const fn ch(c: char) -> Self {
+        KeyPress::plain(KeyCode::Char(c))
+    }
+
+    const fn ctrl(c: char) -> Self {
+        KeyPress { code: KeyCode::Char(c), modifiers: KeyModifiers::CONTROL }
+    }
+
+    const fn alt(code: KeyCode) -> Self {
+        KeyPress { code, modifiers: KeyModifiers::ALT }
+    }
+
+    const fn shift(code: KeyCode) -> Self {
+        KeyPress { code, modifiers: KeyModifiers::SHIFT }
+    }
+
+    /// Returns whether the key event is this key press. Shift is ignored for characters
+    /// since it's already part of the character and terminals differ on reporting it.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        let mut modifiers = event.modifiers;
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        self.code == event.code && self.modifiers == modifiers
+    }
+
+    /// Parses a key spec from the config file, like "space", "ctrl-n" or "F5".
+    /// Modifiers come first and the key is either a single character or the name
+    /// of a key as `label()` writes it.
+    pub fn parse(spec: &str) -> Result<KeyPress, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec.trim();
+        loop {
+            let lower = rest.to_lowercase();
+            let (modifier, prefix_len) = if lower.starts_with("ctrl-") {
+                (KeyModifiers::CONTROL, "ctrl-".len())
+            } else if lower.starts_with("alt-") {
+                (KeyModifiers::ALT, "alt-".len())
+            } else if lower.starts_with("shift-") {
+                (KeyModifiers::SHIFT, "shift-".len())
+            } else {
+                break;
+            };
+            // a lone '-' after a modifier is the minus key itself
+            if rest.len() == prefix_len {
+                break;
+            }
+            modifiers.insert(modifier);
+            rest = &rest[prefix_len..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=24).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{}'", spec)),
+                },
+            },
+        };
+
+        // shifted characters arrive as the shifted character, so that's what gets bound
+        if let KeyCode::Char(c) = code {
+            if modifiers.contains(KeyModifiers::SHIFT) {
+                modifiers.remove(KeyModifiers::SHIFT);
+                return Ok(KeyPress { code: KeyCode::Char(c.to_ascii_uppercase()), modifiers });
+            }
+        }
+        Ok(KeyPress { code, modifiers })
+    }
+
+    /// The name of the key press as the help overlay shows it, like "ctrl-f".
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push_str("ctrl-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("alt-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            label.push_str("shift-");
+        }
+        match self.code {
+            KeyCode::Char(' ') => label.push_str("space"),
+            KeyCode::Char(c) => label.push(c),
+            KeyCode::Up => label.push_str("up"),
+            KeyCode::Down => label.push_str("down"),
+            KeyCode::Left => label.push_str("left"),
+            KeyCode::Right => label.push_str("right"),
+            KeyCode::PageUp => label.push_str("pageup"),
+            KeyCode::PageDown => label.push_str("pagedown"),
+            KeyCode::Home => label.push_str("home"),
+            KeyCode::End => label.push_str("end"),
+            KeyCode::Enter => label.push_str("enter"),
+            KeyCode::Esc => label.push_str("esc"),
+            KeyCode::Tab => label.push_str("tab"),
+            KeyCode::Backspace => label.push_str("backspace"),
+            KeyCode::F(n) => label.push_str(&format!("f{}", n)),
+            other => label.push_str(&format!("{:?}", other).to_lowercase()),
+        }
+        label
+    }
+}
+
+/// One or more keys that do the same thing.
+pub struct KeyBinding {
+    pub keys: &'static [KeyPress],
+    pub action: Action,
+    pub category: Category,
+    pub description: &'static str,
+    /// Shown in place of the list of keys when that would be too long.
+    pub keys_label: Option<&'static str>,
+}
+
+const fn bind(keys: &'static [KeyPress], action: Action, category: Category, description: &'static str) -> KeyBinding {
+    KeyBinding { keys, action, category, description, keys_label: None }
+}
+
+/// The default key bindings of the main view.
+pub const KEYMAP: &[KeyBinding] = &[
+    bind(&[KeyPress::ch('j'), KeyPress::plain(KeyCode::Down)], Action::MoveDown, Category::Navigation, "moves down in the list (or the queue), typing a count first like '5j' moves that many"),
+    bind(&[KeyPress::ch('k'), KeyPress::plain(KeyCode::Up)], Action::MoveUp, Category::Navigation, "moves up in the list (or the queue)"),
+    bind(&[KeyPress::plain(KeyCode::PageDown)], Action::PageDown, Category::Navigation, "moves down a page in the list"),
+    bind(&[KeyPress::plain(KeyCode::PageUp)], Action::PageUp, Category::Navigation, "moves up a page in the list"),
+    bind(&[KeyPress::plain(KeyCode::Home)], Action::First, Category::Navigation, "jumps to the first entry in the list"),
+    bind(&[KeyPress::plain(KeyCode::End), KeyPress::ch('G')], Action::Last, Category::Navigation, "jumps to the last entry in the list ('5G' goes to the 5th)"),
+    KeyBinding {
+        keys: &[KeyPress::ch('g')],
+        action: Action::GoPrefix,
+        category: Category::Navigation,
+        description: "jumps to the first entry in the list ('5gg' goes to the 5th)",
+        keys_label: Some("gg"),
+    },
+    bind(&[KeyPress::ch('/')], Action::Filter, Category::Navigation, "filters the list to names containing the typed text"),
+    bind(&[KeyPress::plain(KeyCode::Esc)], Action::ClearFilter, Category::Navigation, "clears the filter"),
+    bind(&[KeyPress::ctrl('f')], Action::Find, Category::Navigation, "fuzzy finds an entry in the directory to jump to"),
+    bind(&[KeyPress::ch('c')], Action::ChangeDir, Category::Navigation, "prompts for a directory to go to ('tab' completes names)"),
+    bind(&[KeyPress::ch('h'), KeyPress::alt(KeyCode::Left)], Action::Back, Category::Navigation, "goes back to the previous directory"),
+    bind(&[KeyPress::ch('H'), KeyPress::alt(KeyCode::Right)], Action::Forward, Category::Navigation, "goes forward again after going back"),
+    bind(&[KeyPress::ch('b')], Action::Bookmark, Category::Navigation, "bookmarks the current directory"),
+    bind(&[KeyPress::ch('B')], Action::Bookmarks, Category::Navigation, "lists the bookmarks to jump to"),
+    bind(&[KeyPress::ch('U')], Action::Recent, Category::Navigation, "lists the recently played files to jump to or play again"),
+
+    bind(&[KeyPress::ch('S')], Action::Sort, Category::Files, "cycles the sort order of the list"),
+    bind(&[KeyPress::alt(KeyCode::Char('.'))], Action::ToggleHidden, Category::Files, "shows / hides hidden files and directories"),
+    bind(&[KeyPress::plain(KeyCode::F(5)), KeyPress::ctrl('r')], Action::Refresh, Category::Files, "lists the directory again, keeping the selection"),
+    bind(&[KeyPress::ch('R')], Action::ToggleRecursive, Category::Files, "toggles listing every file beneath the directory"),
+    bind(&[KeyPress::ch('e')], Action::CycleExtFilter, Category::Files, "cycles listing only the files of one extension: all, wav, ogg, mp3, flac, aif, aiff, m4a, mp4, aac, opus, or the ones set with --ext"),
+    bind(&[KeyPress::ch('F')], Action::CycleRatingFilter, Category::Files, "cycles listing only the files rated at least 1 to 5 stars"),
+    bind(&[KeyPress::ch('#')], Action::RatingMode, Category::Files, "toggles rating mode, where 1 - 5 rate the selected file (or every marked file) and 0 takes the rating away"),
+    KeyBinding {
+        keys: &[
+            KeyPress::ctrl('0'), KeyPress::ctrl('1'), KeyPress::ctrl('2'), KeyPress::ctrl('3'), KeyPress::ctrl('4'), KeyPress::ctrl('5'),
+        ],
+        action: Action::Rate,
+        category: Category::Files,
+        description: "rates the selected file (or every marked file) that many stars, 0 takes the rating away",
+        keys_label: Some("ctrl-0 - ctrl-5"),
+    },
+    bind(&[KeyPress::ch('t')], Action::ToggleMark, Category::Files, "marks / unmarks the selected file"),
+    bind(&[KeyPress::ch('T')], Action::ClearMarks, Category::Files, "clears all of the marks"),
+    bind(&[KeyPress::ch('d')], Action::Delete, Category::Files, "moves the selected file (or every marked file) to the trash, after asking"),
+    bind(&[KeyPress::ch('y')], Action::CopyTo, Category::Files, "copies the selected file (or every marked file) to a typed directory"),
+    bind(&[KeyPress::ch('Y')], Action::MoveTo, Category::Files, "moves the selected file (or every marked file) to a typed directory"),
+    bind(&[KeyPress::ctrl('y')], Action::CopyPath, Category::Files, "copies the full path of the selected entry to the clipboard"),
+    bind(&[KeyPress::ch('O')], Action::OpenExternal, Category::Files, "opens the selected file in another program"),
+    bind(&[KeyPress::ctrl('o')], Action::OpenDirExternal, Category::Files, "opens the current directory in the file manager"),
+    bind(&[KeyPress::ch('V')], Action::Convert, Category::Files, "converts the selected file to a wav or flac next to it"),
+    bind(&[KeyPress::ch('I')], Action::Loudness, Category::Files, "measures the loudness, peak and rms of the selected file for the info pane"),
+    bind(&[KeyPress::ch('Z')], Action::Duplicates, Category::Files, "finds the listed files with the same contents, to mark and delete the copies"),
+    bind(&[KeyPress::ch('E')], Action::ExportMarked, Category::Files, "writes the marked files (or the listed ones) to a text file, or a .csv with their size, duration, sample rate and channels"),
+
+    bind(&[KeyPress::ch('a')], Action::QueueFile, Category::Queue, "adds the selected file (or every marked file) to the end of the queue"),
+    bind(&[KeyPress::ch('x')], Action::Unqueue, Category::Queue, "removes the selected entry from the queue (in the queue view)"),
+    bind(&[KeyPress::ch('W')], Action::SavePlaylist, Category::Queue, "saves the queue (or the marked files) as an m3u8 playlist"),
+    bind(&[KeyPress::plain(KeyCode::Tab)], Action::ToggleFocus, Category::Queue, "switches between the directory tree (when it's shown), the file list and the queue"),
+    bind(&[KeyPress::ch('n')], Action::Next, Category::Queue, "skips to the next file in the queue (or the next auto-advance file)"),
+    bind(&[KeyPress::ch('A')], Action::AdvanceMode, Category::Queue, "cycles auto-advance: off, stop at the end, wrap around, or repeat the file"),
+    bind(&[KeyPress::ch('s')], Action::Shuffle, Category::Queue, "toggles shuffling the auto-advance order"),
+    bind(&[KeyPress::ch('P')], Action::PlayAll, Category::Queue, "plays every file in the directory, starting with the selection"),
+
+    bind(&[KeyPress::ch(' ')], Action::PlayOrEnter, Category::Playback, "plays the selected file or playlist, or enters the selected directory"),
+    bind(&[KeyPress::ch('r'), KeyPress::plain(KeyCode::Enter)], Action::Restart, Category::Playback, "restarts the sound from the beginning"),
+    bind(&[KeyPress::plain(KeyCode::Backspace)], Action::Stop, Category::Playback, "stops playback and cancels a play-all run"),
+    bind(&[KeyPress::ch('o')], Action::PlayFrom, Category::Playback, "plays the selected file from a typed time (mm:ss)"),
+    bind(&[KeyPress::ch('l')], Action::PlayLoop, Category::Playback, "plays the selected wav holding on the loop points in its smpl chunk"),
+    bind(&[KeyPress::ch('v')], Action::Preview, Category::Playback, "toggles preview mode"),
+    bind(&[KeyPress::plain(KeyCode::Left)], Action::SeekBack, Category::Playback, "seeks backward 5 seconds"),
+    bind(&[KeyPress::plain(KeyCode::Right)], Action::SeekForward, Category::Playback, "seeks forward 5 seconds"),
+    bind(&[KeyPress::shift(KeyCode::Left)], Action::SeekBackLong, Category::Playback, "seeks backward 30 seconds"),
+    bind(&[KeyPress::shift(KeyCode::Right)], Action::SeekForwardLong, Category::Playback, "seeks forward 30 seconds"),
+    KeyBinding {
+        keys: &[
+            KeyPress::ch('0'), KeyPress::ch('1'), KeyPress::ch('2'), KeyPress::ch('3'), KeyPress::ch('4'),
+            KeyPress::ch('5'), KeyPress::ch('6'), KeyPress::ch('7'), KeyPress::ch('8'), KeyPress::ch('9'),
+        ],
+        action: Action::JumpToTenth,
+        category: Category::Playback,
+        description: "jumps to that tenth of the playing sound ('3' jumps to 30%), otherwise types a count for the next move",
+        keys_label: Some("0 - 9"),
+    },
+    bind(&[KeyPress::ch('+'), KeyPress::ch('=')], Action::VolumeUp, Category::Playback, "raises the volume"),
+    bind(&[KeyPress::ch('-')], Action::VolumeDown, Category::Playback, "lowers the volume"),
+    bind(&[KeyPress::ch('m')], Action::Mute, Category::Playback, "mutes / unmutes playback"),
+    bind(&[KeyPress::ch('M')], Action::MonoCheck, Category::Playback, "toggles a mono downmix for checking mono compatibility"),
+    bind(&[KeyPress::ctrl('g')], Action::ReplayGain, Category::Playback, "toggles playing files at the gain in their ReplayGain or R128 tags"),
+    bind(&[KeyPress::ch(',')], Action::PanLeft, Category::Playback, "pans playback left"),
+    bind(&[KeyPress::ch('.')], Action::PanRight, Category::Playback, "pans playback right"),
+    bind(&[KeyPress::ch('|')], Action::PanCenter, Category::Playback, "centers the panning"),
+    bind(&[KeyPress::ch('<')], Action::SpeedDown, Category::Playback, "slows down playback"),
+    bind(&[KeyPress::ch('>')], Action::SpeedUp, Category::Playback, "speeds up playback"),
+    bind(&[KeyPress::ch('*')], Action::SpeedReset, Category::Playback, "resets the playback speed"),
+    bind(&[KeyPress::ch('[')], Action::LoopStart, Category::Playback, "sets the start (A) of the loop region"),
+    bind(&[KeyPress::ch(']')], Action::LoopEnd, Category::Playback, "sets the end (B) of the loop region"),
+    bind(&[KeyPress::ch('C')], Action::ClearLoop, Category::Playback, "clears the loop region"),
+    bind(&[KeyPress::ch('X')], Action::Trim, Category::Playback, "trims the loop region (or a typed range) out to name.trim.wav"),
+    bind(&[KeyPress::ctrl('a')], Action::ResetAudio, Category::Playback, "reopens the audio output, for when the device went away"),
+    bind(&[KeyPress::ch('D')], Action::PickDevice, Category::Playback, "lists the output devices to play on"),
+    bind(&[KeyPress::ch('K')], Action::PickTrack, Category::Playback, "lists the audio tracks of the selected file to pick one to play"),
+
+    bind(&[KeyPress::ch('i')], Action::ToggleInfoPane, Category::General, "cycles the info pane between showing for files, hidden and always shown"),
+    bind(&[KeyPress::ch(')')], Action::GrowInfoPane, Category::General, "widens the info pane"),
+    bind(&[KeyPress::ch('(')], Action::ShrinkInfoPane, Category::General, "narrows the info pane"),
+    bind(&[KeyPress::ctrl('t')], Action::CycleTheme, Category::General, "cycles the color theme: dark, light or monochrome"),
+    bind(&[KeyPress::ch('L')], Action::ErrorLog, Category::General, "lists the last errors shown, newest first"),
+    bind(&[KeyPress::ch('?')], Action::Help, Category::General, "shows this help"),
+    bind(&[KeyPress::ch('q')], Action::Quit, Category::General, "quits"),
+];
+
+/// The key bindings in use, which start out as `KEYMAP`.
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+/// A key binding whose keys can be changed.
+pub struct Binding {
+    pub keys: Vec<KeyPress>,
+    pub action: Action,
+    pub category: Category,
+    pub description: &'static str,
+    keys_label: Option<&'static str>, // dropped once the keys are changed
+}
+
+impl Binding {
+    /// The keys as the help overlay shows them, like "j / down".
+    pub fn keys_label(&self) -> String {
+        match self.keys_label {
+            Some(label) => label.to_string(),
+            None if self.keys.is_empty() => "(unbound)".to_string(),
+            None => self.keys.iter().map(|k| k.label()).collect::<Vec<_>>().join(" / "),
+        }
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = KEYMAP.iter()
+            .map(|b| Binding {
+                keys: b.keys.to_vec(),
+                action: b.action,
+                category: b.category,
+                description: b.description,
+                keys_label: b.keys_label,
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Returns the action bound to the key event, if there is one.
+    pub fn action_for_key(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings.iter()
+            .find(|binding| binding.keys.iter().any(|k| k.matches(event)))
+            .map(|binding| binding.action)
+    }
+
+    /// Binds the action named `action_name` to the keys in `specs` in place of its
+    /// defaults, taking the keys away from any other action they were bound to.
+    /// Nothing changes if the action or any of the keys can't be made sense of.
+    pub fn rebind(&mut self, action_name: &str, specs: &[&str]) -> Result<(), String> {
+        let index = self.bindings.iter()
+            .position(|b| b.action.name() == action_name)
+            .ok_or_else(|| format!("unknown action '{}'", action_name))?;
+        // the digit pressed picks the tenth or the rating, so those keys can't be moved
+        if matches!(self.bindings[index].action, Action::JumpToTenth | Action::Rate) {
+            return Err(format!("'{}' can't be rebound", action_name));
+        }
+        let keys = specs.iter()
+            .map(|spec| KeyPress::parse(spec))
+            .collect::<Result<Vec<KeyPress>, String>>()?;
+
+        for binding in &mut self.bindings {
+            if binding.keys.iter().any(|k| keys.contains(k)) {
+                binding.keys.retain(|k| !keys.contains(k));
+                binding.keys_label = None;
+            }
+        }
+        let binding = &mut self.bindings[index];
+        binding.keys = keys;
+        binding.keys_label = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,651 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+mod app;
+mod config;
+mod cover;
+mod device;
+mod duplicates;
+mod error;
+mod event;
+mod export;
+mod fsutil;
+mod fuzzy;
+mod headless;
+mod input;
+mod keymap;
+mod loudness;
+mod opus;
+mod player;
+mod playlist;
+mod ratings;
+mod session;
+mod theme;
+mod tree;
+mod ui;
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::app::{AdvanceMode, AppState, EntryKind, Focus, Prompt, parse_timestamp};
+use crate::device::find_output_device;
+use crate::error::SpinupError;
+use crate::event::{AppEvent, EventChannel};
+use crate::config::{apply_key_config, load_settings};
+use crate::fsutil::{load_bookmarks, load_duration_cache, load_recent};
+use crate::input::{handle_bookmarks_key, handle_device_key, handle_finder_key, handle_mouse_event, handle_prompt_key, handle_recent_key, handle_duplicates_key, handle_scroll_key, handle_text_input_key, handle_track_key, perform_action};
+use crate::ratings::Ratings;
+use crate::player::{AudioOutput, MAX_START_FADE, auto_advance, play_selected_file, reset_audio};
+use crate::session::{Session, install_panic_save, load_session, remember_session, save_session_text};
+use crate::ui::{DEFAULT_INFO_PANE_WIDTH, ui};
+
+/// Simple program to greet a person
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// A directory to browse, or a file to select and start playing in its directory
+    #[clap(parse(from_os_str))]
+    pub(crate) path: Option<PathBuf>,
+
+    /// The starting directory to browse
+    #[clap(short, long, parse(from_os_str))]
+    pub(crate) dir: Option<PathBuf>,
+
+    /// The fade out time in milliseconds used when stopping playback (default 250); 0 stops instantly
+    #[clap(long)]
+    pub(crate) fade_ms: Option<u64>,
+
+    /// The fade in time in milliseconds used when starting playback, up to 5000 (default 0); 0 starts at full volume
+    #[clap(long)]
+    pub(crate) fade_in_ms: Option<u64>,
+
+    /// The crossfade time in milliseconds between files when auto-advancing (default 0); 0 disables it
+    #[clap(long)]
+    pub(crate) crossfade_ms: Option<u64>,
+
+    /// How many seconds of each file preview mode plays (default 3)
+    #[clap(long, parse(try_from_str = parse_seconds))]
+    pub(crate) preview_secs: Option<f64>,
+
+    /// How many directories deep the recursive view searches for files (default 8)
+    #[clap(long)]
+    pub(crate) scan_depth: Option<usize>,
+
+    /// The most files the recursive view lists (default 10000)
+    #[clap(long)]
+    pub(crate) scan_max_files: Option<usize>,
+
+    /// The color theme: dark (default), light or monochrome
+    #[clap(long)]
+    pub(crate) theme: Option<theme::ThemeKind>,
+
+    /// The output device to play on, by name ignoring case; the default device when left out
+    #[clap(long)]
+    pub(crate) device: Option<String>,
+
+    /// Prints the audio output devices and exits
+    #[clap(long)]
+    pub list_devices: bool,
+
+    /// A file to play, the same as giving it as the path
+    #[clap(long, parse(from_os_str))]
+    pub(crate) play: Option<PathBuf>,
+
+    /// Plays the file to its end without the terminal interface, then exits
+    #[clap(long)]
+    pub no_ui: bool,
+
+    /// Leaves out the progress line when playing without the interface
+    #[clap(long)]
+    pub(crate) quiet: bool,
+
+    /// The volume to start at, in percent from 0 to 200 (default 100)
+    #[clap(long, parse(try_from_str = parse_volume_percent))]
+    pub(crate) volume: Option<u32>,
+
+    /// More file extensions to list along with the usual ones, like 'mka,wv'
+    #[clap(long)]
+    pub(crate) ext: Option<String>,
+
+    /// The only file extensions to list, like 'wav', instead of the usual ones
+    #[clap(long)]
+    pub(crate) only_ext: Option<String>,
+
+    /// Lists files of any extension whose content probes as audio, and flags listed files that don't
+    #[clap(long)]
+    pub(crate) probe_content: bool,
+
+    /// Saves ratings in a .spinup-ratings file in each directory so they travel with the files
+    #[clap(long)]
+    pub(crate) ratings_in_dirs: bool,
+
+    /// Starts with auto-advance repeating the file that finished
+    #[clap(long = "loop")]
+    pub(crate) repeat: bool,
+
+    /// Starts with auto-advance shuffled
+    #[clap(long)]
+    pub(crate) shuffle: bool,
+
+    /// How far into the file to start playing without the interface, as mm:ss
+    #[clap(long, parse(try_from_str = parse_seek))]
+    pub(crate) seek: Option<std::time::Duration>,
+
+    /// The config file to read instead of the one in the config directory
+    #[clap(long, parse(from_os_str))]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Prints the settings from the config file and the command line together, then exits
+    #[clap(long)]
+    pub dump_config: bool,
+
+    /// Starts fresh instead of where the last run was left
+    #[clap(long)]
+    pub(crate) no_restore: bool,
+}
+
+fn parse_volume_percent(text: &str) -> Result<u32, String> {
+    match text.parse::<u32>() {
+        Ok(percent) if percent <= 200 => Ok(percent),
+        _ => Err(format!("'{}' isn't a volume from 0 to 200", text)),
+    }
+}
+
+// the longest time a setting in seconds can be, which keeps it well inside what a
+// duration can hold
+pub(crate) const MAX_SETTING_SECS: f64 = 60.0 * 60.0;
+
+fn parse_seconds(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(secs) if (0.0..=MAX_SETTING_SECS).contains(&secs) => Ok(secs),
+        _ => Err(format!("'{}' isn't a number of seconds from 0 to {}", text, MAX_SETTING_SECS)),
+    }
+}
+
+fn parse_seek(text: &str) -> Result<std::time::Duration, String> {
+    parse_timestamp(text).ok_or_else(|| format!("'{}' isn't a time like 1:30 or 0:05.5", text))
+}
+
+impl Args {
+    /// Checks the arguments that can be checked before the terminal is taken over,
+    /// so a mistyped path is reported on a normal screen.
+    pub fn validate(&self) -> Result<(), String> {
+        for path in [&self.path, &self.play].into_iter().flatten() {
+            if !path.exists() {
+                return Err(format!("{} doesn't exist", path.display()));
+            }
+        }
+        if let Some(play) = &self.play {
+            if !play.is_file() {
+                return Err(format!("{} isn't a file", play.display()));
+            }
+        }
+        if self.no_ui && self.file_to_play().is_none() {
+            return Err("--no-ui needs a file to play, given with --play or as the path".to_string());
+        }
+        Ok(())
+    }
+
+    // the file to start playing, given with `--play` or as the path
+    fn file_to_play(&self) -> Option<&PathBuf> {
+        self.play.as_ref().or(self.path.as_ref().filter(|path| path.is_file()))
+    }
+}
+
+/// Prints the settings in effect after merging the command line over the config
+/// file, in the config file's format, with any problems in the file on stderr.
+pub fn print_config(args: &Args) {
+    let (settings, config, warnings) = load_settings(args);
+    for warning in warnings {
+        eprintln!("Config Warning: {}", warning);
+    }
+    print!("{}", settings.to_config_text());
+    if let Some(keys) = config.as_ref().and_then(|config| config.get("keys")) {
+        println!("\n[keys]\n{}", keys);
+    }
+}
+
+/// Plays the file from the arguments to its end without the terminal interface,
+/// printing its name, duration and progress to stdout unless `--quiet` is given.
+pub fn run_headless(args: Args) -> Result<(), Box<dyn Error>> {
+    let path = args.file_to_play().ok_or("there's no file to play")?;
+    let (settings, _, warnings) = load_settings(&args);
+    for warning in warnings {
+        eprintln!("Config Warning: {}", warning);
+    }
+    let options = headless::HeadlessOptions {
+        device: args.device.clone(),
+        volume: settings.volume as f64 / 100.0,
+        start_position: args.seek.unwrap_or_default(),
+        quiet: args.quiet,
+    };
+    headless::play_file_headless(path, options)
+}
+
+/// Prints the audio output devices of every host to stdout, returning false if
+/// there weren't any. Nothing of the terminal interface is set up for this.
+pub fn print_output_devices() -> bool {
+    let lines = device::describe_output_devices();
+    if lines.is_empty() {
+        println!("no devices found");
+        return false;
+    }
+    for line in lines {
+        println!("{}", line);
+    }
+    true
+}
+
+/// Runs the browser in `terminal` until it's quit, returning the code to exit
+/// with: 0, or 130 when it was interrupted with ctrl-c or SIGINT. The terminal has
+/// to be set up for raw mode and the alternate screen by the caller, and restored
+/// afterwards.
+pub fn run_app<B: tui::backend::Backend>(args: Args, terminal: &mut tui::Terminal<B>) -> Result<i32, Box<dyn Error>> {
+    install_panic_save();
+
+    // a device asked for by name has to be there, unlike a missing default device
+    if let Some(name) = &args.device {
+        find_output_device(name)?;
+    }
+
+    // initialize the audio system. spinup still starts without one so files can be
+    // browsed, and it's tried again when something is played.
+    let (mut audio, audio_err) = AudioOutput::open(args.device.clone());
+    
+    // input and the results of background work all come in through one channel,
+    // along with the signals asking spinup to quit
+    let events = EventChannel::new();
+    events.watch_signals()?;
+    let playback_sender = events.sender();
+
+    // the config file is read first so the command line can override it. problems
+    // with it are only warned about so a typo can't keep spinup from starting
+    let (settings, config, mut config_warnings) = load_settings(&args);
+
+    // build the initial application state
+    let mut app_state = AppState::default();
+    app_state.sound_state.stop_fade = std::time::Duration::from_millis(settings.fade_ms);
+    app_state.sound_state.start_fade = std::time::Duration::from_millis(settings.fade_in_ms).min(MAX_START_FADE);
+    app_state.sound_state.crossfade = std::time::Duration::from_millis(settings.crossfade_ms);
+    app_state.sound_state.volume = settings.volume as f64 / 100.0;
+    app_state.sound_state.preview_length = std::time::Duration::from_secs_f64(settings.preview_secs.max(0.0));
+    app_state.sound_state.seek_step = settings.seek_step_secs;
+    app_state.sound_state.seek_long_step = settings.seek_long_step_secs;
+    app_state.sound_state.stream_threshold = settings.stream_threshold_mb * 1024 * 1024;
+    app_state.scan_options.max_depth = settings.scan_depth;
+    app_state.scan_options.max_files = settings.scan_max_files;
+    app_state.theme_kind = settings.theme;
+    app_state.show_hidden = settings.show_hidden;
+    app_state.listed_extensions = settings.listed_extensions();
+    app_state.probe_content = settings.probe_content;
+    app_state.permanent_delete = settings.permanent_delete;
+    app_state.trim_bit_depth = settings.trim_bit_depth;
+    app_state.convert_bit_depth = settings.convert_bit_depth;
+    app_state.tree_layout = settings.tree_layout;
+    app_state.open_command = settings.open_command.clone();
+    app_state.open_dir_command = settings.open_dir_command.clone();
+    app_state.info_pane_width = DEFAULT_INFO_PANE_WIDTH;
+    app_state.event_sender = Some(events.sender());
+    if let Some(config) = &config {
+        config_warnings.extend(apply_key_config(&mut app_state.keymap, config));
+    }
+
+    // the last run is picked up where it was left when there's no place to start
+    // given on the command line, with the options that were given still winning
+    let mut session = None;
+    if !args.no_restore && args.play.is_none() && args.path.is_none() && args.dir.is_none() {
+        match load_session() {
+            Ok(loaded) => session = loaded,
+            Err(err) => app_state.last_error_msg = format!("Session Error: {}", err),
+        }
+    }
+    if let Some(session) = &session {
+        session.apply_toggles(&mut app_state);
+    }
+    if let Some(volume) = args.volume {
+        app_state.sound_state.volume = volume as f64 / 100.0;
+    }
+    if let Some(theme) = args.theme {
+        app_state.theme_kind = theme;
+    }
+    // the startup flags only pick where the keys that toggle these start from
+    if args.repeat {
+        app_state.advance_mode = AdvanceMode::RepeatOne;
+    }
+    if args.shuffle {
+        app_state.shuffle = true;
+    }
+    let restored_dir = match &session {
+        Some(session) if session.dir.is_dir() => Some(session.dir.clone()),
+        Some(session) => {
+            app_state.last_info_msg = format!("{} no longer exists, starting in the current directory", session.dir.display());
+            None
+        },
+        None => None,
+    };
+
+    // a file given to start with opens in its directory, which takes the place of
+    // the optional starting directory, otherwise default to the current directory
+    let mut start_file = None;
+    let restoring = restored_dir.is_some();
+    let starting_dir = match args.play.or(args.path).or(restored_dir).or(settings.dir) {
+        Some(path) if path.is_file() => {
+            start_file = path.file_name().map(|name| name.to_os_string());
+            match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => std::env::current_dir()?,
+            }
+        },
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    // the ratings are needed before the first listing shows them
+    match Ratings::load(settings.ratings_in_dirs) {
+        Ok(ratings) => app_state.ratings = ratings,
+        Err(err) => {
+            app_state.ratings.in_dirs = settings.ratings_in_dirs;
+            app_state.last_error_msg = format!("Rating Error: {}", err);
+        },
+    }
+    // the cached durations are needed before the first listing probes for them
+    match load_duration_cache() {
+        Ok(durations) => app_state.duration_cache = durations,
+        Err(err) => app_state.last_error_msg = format!("Duration Cache Error: {}", err),
+    }
+    app_state.set_current_directory(&starting_dir);
+    app_state.update_file_names();
+    match session {
+        Some(session) if restoring => app_state.select_entry_key(session.selected),
+        _ => app_state.select_list_item(0),
+    }
+    match load_bookmarks() {
+        Ok(bookmarks) => app_state.bookmarks = bookmarks,
+        Err(err) => app_state.last_error_msg = format!("Bookmark Error: {}", err),
+    }
+    match load_recent() {
+        Ok(recent) => app_state.recent = recent,
+        Err(err) => app_state.last_error_msg = format!("Recent Error: {}", err),
+    }
+    if !config_warnings.is_empty() {
+        app_state.last_error_msg = format!("Config Warning: {}", config_warnings.join("; "));
+    }
+    if let Some(err) = audio_err {
+        app_state.last_error_msg = format!("Audio Error: {}, only browsing until one can be opened", err);
+    }
+
+    // the file given to start with plays once everything it depends on is set up
+    if let Some(name) = start_file {
+        match app_state.view_index_of_key(Some((EntryKind::File, name.clone()))) {
+            Some(view_index) => {
+                app_state.select_list_item(view_index);
+                match play_selected_file(&mut app_state, &mut audio) {
+                    Ok(()) => {
+                        if let Some(snd_path) = app_state.selected_file_path() {
+                            app_state.remember_played(&snd_path);
+                        }
+                    },
+                    Err(err) => app_state.last_error_msg = format!("Playback Error: {}", err),
+                }
+            },
+            None => app_state.last_error_msg = format!("{} isn't a file spinup can play", name.to_string_lossy()),
+        }
+    }
+
+    
+    let tick_rate = std::time::Duration::from_millis(settings.tick_ms);
+    let mut last_tick = std::time::Instant::now();
+    loop {
+        let current_tick = std::time::Instant::now();
+        let tick_interval = current_tick.duration_since(last_tick);
+
+        // update the played time of the sound, if currently playing or fading out
+        app_state.sound_state.update_play_time(tick_interval);
+        if app_state.sound_state.is_playing() {
+            if let Err(err) = app_state.sound_state.update_loop_region() {
+                app_state.last_error_msg = format!("Loop Error: {}", err);
+            }
+        }
+        app_state.sound_state.update_fading_sound();
+        if app_state.sound_state.output_stalled() {
+            // without a device to move to, it's opened again the next time something plays
+            match reset_audio(&mut app_state, &mut audio) {
+                Err(SpinupError::AudioBackend(err)) => app_state.last_error_msg = format!("Audio Error: {}, only browsing until one can be opened", err),
+                Err(err) => app_state.last_error_msg = format!("Audio Error: {}", err),
+                Ok(()) => {},
+            }
+        }
+        app_state.update_recursive_scan();
+        app_state.update_duration_probe();
+        app_state.update_waveform_job();
+        app_state.update_cover_job();
+        app_state.update_duration_count();
+        app_state.update_file_operation();
+        app_state.update_export_job();
+        app_state.update_loudness_job();
+        app_state.update_duplicate_scan();
+        app_state.update_external_errors();
+        app_state.check_directory_changed();
+        app_state.update_dir_watch();
+        if let Err(err) = app_state.sound_state.update_stop_at() {
+            app_state.last_error_msg = format!("Playback Stop Error: {}", err);
+        }
+
+        // preview the newly selected file once the selection has settled
+        if app_state.preview_ready() {
+            if let Err(err) = play_selected_file(&mut app_state, &mut audio) {
+                app_state.last_error_msg = format!("Preview Error: {}", err);
+            }
+        }
+
+        // kira can only be asked whether a sound has ended, so that's checked here and
+        // sent on as an event when the sound reached its end on its own, or is close
+        // enough to the end to start crossfading
+        if app_state.sound_state.check_finished() || app_state.sound_state.crossfade_due() {
+            _ = playback_sender.send(AppEvent::PlaybackFinished);
+        }
+
+        // keep any error from the last key press or this tick before it gets cleared
+        app_state.record_error();
+
+        // keep the session up to date for saving if spinup panics
+        remember_session(&app_state);
+
+        // draw the interface, with the directory tree if there's room for it
+        app_state.update_tree_layout(terminal.size()?.width);
+        terminal.draw(|f| ui(&mut app_state, f))?;
+
+        // wait for an event based on our tick_rate while something is going on, otherwise 1s
+        let timeout = if app_state.has_background_work() { tick_rate } else { std::time::Duration::from_secs(1) };
+        let mut event = events.next(timeout);
+        // handle everything that's already waiting before drawing again
+        loop {
+            if let Some(exit_code) = handle_event(&mut app_state, &mut audio, event)? {
+                return Ok(exit_code);
+            }
+            if app_state.quit_confirmed {
+                quit(&mut app_state);
+                return Ok(0);
+            }
+            match events.try_next() {
+                Some(next) => event = next,
+                None => break,
+            }
+        }
+        last_tick = current_tick;
+    }
+}
+
+// acts on one event from the channel, returning the exit code once it quits spinup
+fn handle_event(app_state: &mut AppState, audio: &mut AudioOutput, event: AppEvent) -> Result<Option<i32>, Box<dyn Error>> {
+    match event {
+        AppEvent::Key(key) => {
+            // clear the error message before we do the next event.
+            app_state.clear_error();
+
+            match key.code {
+                // pressing quit again while it asks about the running jobs quits anyway
+                _ if matches!(app_state.prompt, Some(Prompt::QuitWithJobs(_))) && app_state.keymap.action_for_key(&key) == Some(keymap::Action::Quit) => {
+                    app_state.prompt = None;
+                    app_state.quit_confirmed = true;
+                },
+                // a pending prompt takes every key until it is answered
+                _ if app_state.prompt.is_some() => handle_prompt_key(app_state, key.code),
+                _ if app_state.text_input.is_some() => handle_text_input_key(app_state, audio, key.code),
+                _ if app_state.finder.is_some() => handle_finder_key(app_state, key.code),
+                _ if app_state.bookmark_list.is_some() => handle_bookmarks_key(app_state, key.code),
+                _ if app_state.recent_list.is_some() => handle_recent_key(app_state, audio, key.code),
+                _ if app_state.duplicates.is_some() => handle_duplicates_key(app_state, key.code),
+                _ if app_state.device_picker.is_some() => handle_device_key(app_state, audio, key.code),
+                _ if app_state.track_picker.is_some() => handle_track_key(app_state, audio, key.code),
+                _ if app_state.help_scroll.is_some() => handle_scroll_key(&mut app_state.help_scroll, key.code),
+                _ if app_state.error_log_scroll.is_some() => handle_scroll_key(&mut app_state.error_log_scroll, key.code),
+                // digits typed ahead of a motion build up a count for it, and esc
+                // gives up on a count or 'g' that's been started
+                // in rating mode the digits rate the selected file instead, and esc leaves it
+                crossterm::event::KeyCode::Char('0'..='5') if app_state.rating_mode && key.modifiers.is_empty() => {
+                    perform_action(app_state, audio, keymap::Action::Rate, key.code);
+                },
+                crossterm::event::KeyCode::Esc if app_state.rating_mode => app_state.rating_mode = false,
+                _ if app_state.push_count_digit(&key) => {},
+                // enter expands and collapses the tree along with space, instead of restarting the sound
+                crossterm::event::KeyCode::Enter if app_state.focus == Focus::Tree && key.modifiers.is_empty() => app_state.toggle_tree_selected(),
+                crossterm::event::KeyCode::Esc if app_state.pending_count.is_some() || app_state.pending_g => {
+                    app_state.pending_count = None;
+                    app_state.pending_g = false;
+                },
+                _ => match app_state.keymap.action_for_key(&key) {
+                    // quitting would cut off files being written, so it's asked about first
+                    Some(keymap::Action::Quit) if app_state.running_jobs() > 0 => {
+                        app_state.prompt = Some(Prompt::QuitWithJobs(app_state.running_jobs()));
+                    },
+                    Some(keymap::Action::Quit) => {
+                        quit(app_state);
+                        return Ok(Some(0));
+                    },
+                    Some(action) => perform_action(app_state, audio, action, key.code),
+                    None => {},
+                },
+            }
+        },
+        AppEvent::Mouse(mouse) => handle_mouse_event(app_state, audio, mouse),
+        AppEvent::FsChanged(dir) => app_state.note_dir_change(&dir),
+        AppEvent::ProbeResult(path, stamp, duration) => app_state.probe_result(path, stamp, duration),
+        AppEvent::ProbeFinished(id) => app_state.probe_finished(id),
+        AppEvent::FileInfo(path, stamp, info) => app_state.file_info_probed(path, stamp, info.map(|info| *info)),
+        AppEvent::PlaybackFinished => {
+            if let Err(err) = auto_advance(app_state, audio) {
+                app_state.play_all = None;
+                app_state.last_error_msg = format!("Auto-advance Error: {}", err);
+            }
+        },
+        AppEvent::InputError(err) => return Err(err.into()),
+        AppEvent::Quit(exit_code) => {
+            quit(app_state);
+            return Ok(Some(exit_code));
+        },
+        AppEvent::Resize | AppEvent::Tick => {},
+    }
+    Ok(None)
+}
+
+// how long whatever is playing takes to fade out when spinup quits
+const QUIT_FADE: std::time::Duration = std::time::Duration::from_millis(60);
+
+// the longest quitting waits for the jobs writing files to clean up after themselves
+const QUIT_CLEANUP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// saves what's worth keeping for next time, stops the jobs writing files and fades
+// out the sound that's playing, so quitting doesn't end it with a click
+fn quit(app_state: &mut AppState) {
+    app_state.cancel_jobs(QUIT_CLEANUP_TIMEOUT);
+    // a session that can't be saved only means starting fresh next time
+    _ = save_session_text(&Session::capture(app_state).to_text());
+    // durations that aren't saved only get probed again next time
+    _ = app_state.save_durations();
+    if app_state.sound_state.is_audible() && app_state.sound_state.stop_sound(QUIT_FADE).is_ok() {
+        std::thread::sleep(QUIT_FADE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    // a listing of a directory with three files, and an audio output that never
+    // opens so nothing actually plays
+    fn event_fixture() -> (tempfile::TempDir, AppState, AudioOutput) {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.wav", "b.wav", "c.wav"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let mut app_state = AppState::default();
+        app_state.set_current_directory(dir.path());
+        app_state.update_file_names();
+        app_state.select_list_item(1);
+        // the empty files can't be probed for the info pane, which these tests aren't about
+        app_state.last_error_msg.clear();
+        let audio = AudioOutput { manager: None, last_attempt: std::time::Instant::now(), device: None };
+        (dir, app_state, audio)
+    }
+
+    fn selected_name(app_state: &AppState) -> String {
+        app_state.selected_file_path().unwrap().file_name().unwrap().to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn key_events_move_the_selection() {
+        let (_dir, mut app_state, mut audio) = event_fixture();
+        assert_eq!(selected_name(&app_state), "a.wav");
+        let down = AppEvent::Key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert!(matches!(handle_event(&mut app_state, &mut audio, down), Ok(None)));
+        assert_eq!(selected_name(&app_state), "b.wav");
+        let count = AppEvent::Key(KeyEvent::new(KeyCode::Char('2'), KeyModifiers::NONE));
+        let up = AppEvent::Key(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE));
+        assert!(matches!(handle_event(&mut app_state, &mut audio, count), Ok(None)));
+        assert!(matches!(handle_event(&mut app_state, &mut audio, up), Ok(None)));
+        assert_eq!(app_state.file_list_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn finished_playback_advances_to_the_queued_file() {
+        let (dir, mut app_state, mut audio) = event_fixture();
+        app_state.queue.push(dir.path().join("c.wav"));
+        assert!(matches!(handle_event(&mut app_state, &mut audio, AppEvent::PlaybackFinished), Ok(None)));
+        assert!(app_state.queue.is_empty());
+        assert_eq!(selected_name(&app_state), "c.wav");
+        // without an audio output it can't be started, which stops advancing
+        assert_eq!(app_state.last_error_msg, "Auto-advance Error: No audio output device available");
+    }
+
+    #[test]
+    fn finished_playback_without_a_next_file_changes_nothing() {
+        let (_dir, mut app_state, mut audio) = event_fixture();
+        assert!(matches!(handle_event(&mut app_state, &mut audio, AppEvent::PlaybackFinished), Ok(None)));
+        assert!(matches!(handle_event(&mut app_state, &mut audio, AppEvent::Tick), Ok(None)));
+        assert_eq!(selected_name(&app_state), "a.wav");
+        assert!(app_state.last_error_msg.is_empty());
+    }
+
+    #[test]
+    fn seek_times_too_long_are_rejected() {
+        assert_eq!(parse_seek("1:30"), Ok(std::time::Duration::from_secs(90)));
+        assert!(parse_seek("1e30").is_err());
+        assert!(parse_seek("18446744073709551615:0").is_err());
+    }
+
+    #[test]
+    fn preview_seconds_are_bounded() {
+        assert_eq!(parse_seconds("3"), Ok(3.0));
+        assert_eq!(parse_seconds("0.5"), Ok(0.5));
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("NaN").is_err());
+        assert!(parse_seconds("1e300").is_err());
+        assert!(Args::try_parse_from(["spinup", "--preview-secs", "1e300"]).is_err());
+        assert!(Args::try_parse_from(["spinup", "--preview-secs", "inf"]).is_err());
+    }
+}
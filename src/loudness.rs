@@ -0,0 +1,109 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// measuring how loud a file is: the ebu r128 integrated loudness along with its
+// sample peak and rms level. it takes decoding the whole file, so it's only done
+// when asked for, a packet at a time so long files don't fill up memory.
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ebur128::{EbuR128, Mode};
+
+use crate::player::audio_track;
+
+// the loudness of a file, in dB
+#[derive(Clone, Copy)]
+pub(crate) struct Loudness {
+    pub(crate) lufs: Option<f64>, // the integrated loudness, None when it's all too quiet to count
+    pub(crate) peak_dbfs: f64, // the loudest sample of any channel
+    pub(crate) rms_dbfs: f64, // over every sample of every channel
+}
+
+impl Loudness {
+    // the line for the info pane, like "LUFS: -16.3, Peak: -0.8 dBFS"
+    pub(crate) fn label(&self) -> String {
+        let lufs = match self.lufs {
+            Some(lufs) => format!("{:.1}", lufs),
+            None => "silent".to_string(),
+        };
+        format!("LUFS: {}, Peak: {} dBFS", lufs, format_db(self.peak_dbfs))
+    }
+}
+
+// a level in dB to a tenth, or "-inf" for silence
+pub(crate) fn format_db(db: f64) -> String {
+    if db.is_finite() { format!("{:.1}", db) } else { "-inf".to_string() }
+}
+
+// decodes all of `snd_path` to measure its loudness. `track_id` picks the audio
+// track of a file with more than one, otherwise it's the one `audio_track` finds.
+// gives up with an error once the `cancel` flag gets set.
+pub(crate) fn measure_loudness(snd_path: &Path, track_id: Option<u32>, cancel: &AtomicBool) -> Result<Loudness, Box<dyn Error>> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path)?), Default::default());
+    let mut format_reader = probe
+        .format(&Default::default(), mss, &Default::default(), &Default::default())?
+        .format;
+    let track = match track_id {
+        Some(track_id) => format_reader.tracks().iter().find(|track| track.id == track_id),
+        None => audio_track(format_reader.as_ref()),
+    };
+    let track = track.ok_or("the file has no audio track")?;
+    let track_id = track.id;
+    let mut decoder = crate::opus::codecs().make(&track.codec_params, &Default::default())?;
+
+    // the meter is made once the first packet says how many channels there are
+    let mut meter: Option<EbuR128> = None;
+    let mut peak = 0.0f32;
+    let mut sum_squares = 0.0f64;
+    let mut sample_count = 0u64;
+    let mut sample_buf: Option<symphonia::core::audio::SampleBuffer<f32>> = None;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("it was cancelled".into());
+        }
+        let packet = match format_reader.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // a damaged packet is skipped, the same as when it plays
+            Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        if !matches!(&sample_buf, Some(buf) if buf.capacity() >= decoded.capacity() * channels) {
+            sample_buf = Some(symphonia::core::audio::SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buf = sample_buf.as_mut().ok_or("the sample buffer is missing")?;
+        buf.copy_interleaved_ref(decoded);
+
+        if meter.is_none() {
+            meter = Some(EbuR128::new(channels as u32, spec.rate, Mode::I)?);
+        }
+        let meter = meter.as_mut().ok_or("the loudness meter is missing")?;
+        meter.add_frames_f32(buf.samples())?;
+        for &sample in buf.samples() {
+            peak = peak.max(sample.abs());
+            sum_squares += sample as f64 * sample as f64;
+        }
+        sample_count += buf.samples().len() as u64;
+    }
+    let meter = meter.ok_or("the file has no sound in it")?;
+    let lufs = meter.loudness_global()?;
+    let rms = (sum_squares / sample_count.max(1) as f64).sqrt();
+    Ok(Loudness {
+        lufs: Some(lufs).filter(|lufs| lufs.is_finite()),
+        peak_dbfs: 20.0 * (peak as f64).log10(),
+        rms_dbfs: 20.0 * rms.log10(),
+    })
+}
@@ -0,0 +1,136 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// decoding opus. symphonia reads the ogg pages of an opus file and finds its length
+// from the granule positions, but it has no decoder for the packets, so this plugs
+// the pure rust one from opus-decoder into it. everything that decodes through
+// `codecs()` gets opus along with symphonia's own codecs.
+
+use std::sync::OnceLock;
+
+use opus_decoder::{OpusError, OpusMultistreamDecoder};
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec};
+use symphonia::core::codecs::{CodecDescriptor, CodecParameters, CodecRegistry, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS};
+use symphonia::core::errors::{decode_error, unsupported_error, Error as SymphoniaError, Result as SymphoniaResult};
+use symphonia::core::formats::Packet;
+
+// opus always decodes at 48kHz, whatever the rate of the sound that was encoded
+pub(crate) const OPUS_SAMPLE_RATE: u32 = 48_000;
+
+// the longest packet opus has, 120ms at 48kHz
+const MAX_PACKET_FRAMES: usize = 5760;
+
+// symphonia's decoders along with the opus one it doesn't have
+pub(crate) fn codecs() -> &'static CodecRegistry {
+    static CODECS: OnceLock<CodecRegistry> = OnceLock::new();
+    CODECS.get_or_init(|| {
+        let mut registry = CodecRegistry::new();
+        symphonia::default::register_enabled_codecs(&mut registry);
+        registry.register_all::<OpusDecoder>();
+        registry
+    })
+}
+
+// the number of frames a track decodes to. the last granule position of an opus
+// stream counts the pre-skip along with the sound, and the decoder drops that.
+pub(crate) fn n_frames(params: &CodecParameters) -> Option<u64> {
+    match params.codec {
+        CODEC_TYPE_OPUS => params.n_frames.map(|n_frames| n_frames.saturating_sub(params.delay.unwrap_or(0) as u64)),
+        _ => params.n_frames,
+    }
+}
+
+// decodes the packets of an ogg opus stream into 48kHz float samples
+pub(crate) struct OpusDecoder {
+    params: CodecParameters,
+    decoder: OpusMultistreamDecoder,
+    channels: usize,
+    interleaved: Vec<f32>, // the samples of the last packet as the decoder gives them
+    buf: AudioBuffer<f32>,
+    skip: usize, // the frames still to drop from the start, the encoder's pre-skip
+}
+
+// the streams and channel mapping for the decoder, from the 'OpusHead' header
+// symphonia keeps as the extra data. a mono or stereo file without a mapping
+// table is one stream.
+fn stream_layout(params: &CodecParameters, channels: usize) -> SymphoniaResult<(usize, usize, Vec<u8>)> {
+    let head = params.extra_data.as_deref().unwrap_or_default();
+    match head.get(18) {
+        Some(0) | None if channels <= 2 => Ok((1, channels - 1, (0..channels as u8).collect())),
+        Some(1) | Some(255) => match head.get(21..21 + channels) {
+            Some(mapping) => Ok((head[19] as usize, head[20] as usize, mapping.to_vec())),
+            None => decode_error("opus: the channel mapping table is cut short"),
+        },
+        _ => unsupported_error("opus: unknown channel mapping"),
+    }
+}
+
+fn opus_error(err: OpusError) -> SymphoniaError {
+    match err {
+        OpusError::InvalidPacket => SymphoniaError::DecodeError("opus: invalid packet"),
+        OpusError::BufferTooSmall => SymphoniaError::DecodeError("opus: packet longer than 120ms"),
+        OpusError::InternalError => SymphoniaError::Unsupported("opus: packet uses an unsupported feature"),
+        OpusError::InvalidArgument(_) => SymphoniaError::Unsupported("opus: unsupported stream layout"),
+    }
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> SymphoniaResult<Self> {
+        let channels = match params.channels {
+            Some(channels) if channels.count() > 0 => channels,
+            _ => return unsupported_error("opus: the number of channels is required"),
+        };
+        let count = channels.count();
+        let (streams, coupled, mapping) = stream_layout(params, count)?;
+        let decoder = OpusMultistreamDecoder::new(OPUS_SAMPLE_RATE, count, streams, coupled, &mapping)
+            .map_err(opus_error)?;
+        Ok(OpusDecoder {
+            params: params.clone(),
+            decoder,
+            channels: count,
+            interleaved: vec![0.0; MAX_PACKET_FRAMES * count],
+            buf: AudioBuffer::new(MAX_PACKET_FRAMES as u64, SignalSpec::new(OPUS_SAMPLE_RATE, channels)),
+            skip: params.delay.unwrap_or(0) as usize,
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[symphonia::core::support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        // a seek lands after the start, so there's nothing left to skip
+        self.decoder.reset();
+        self.skip = 0;
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> SymphoniaResult<AudioBufferRef<'_>> {
+        self.buf.clear();
+        let frames = self.decoder.decode_float(packet.buf(), &mut self.interleaved, false)
+            .map_err(opus_error)?;
+        let skipped = self.skip.min(frames);
+        self.skip -= skipped;
+        self.buf.render_reserved(Some(frames - skipped));
+        for channel in 0..self.channels {
+            let samples = self.interleaved[skipped * self.channels..frames * self.channels].iter()
+                .skip(channel)
+                .step_by(self.channels);
+            for (dest, &sample) in self.buf.chan_mut(channel).iter_mut().zip(samples) {
+                *dest = sample;
+            }
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
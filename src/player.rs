@@ -0,0 +1,1958 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// playing sounds through kira and reading what symphonia can tell about them,
+// like their codec, tags, duration and waveform.
+
+use std::path::{Path, PathBuf};
+
+use kira::sound::static_sound::{PlaybackState, StaticSoundHandle};
+use kira::sound::streaming::{StreamingSoundData, StreamingSoundHandle, StreamingSoundSettings};
+
+use kira::{
+    manager::{AudioManager, AudioManagerSettings},
+    sound::static_sound::{StaticSoundData, StaticSoundSettings},
+    sound::FromFileError,
+    tween::Tween,
+    LoopBehavior,
+};
+
+use symphonia::core::codecs::{CodecType, CODEC_TYPE_ALAC, CODEC_TYPE_FLAC, CODEC_TYPE_MONKEYS_AUDIO, CODEC_TYPE_NULL, CODEC_TYPE_OPUS, CODEC_TYPE_PCM_ALAW, CODEC_TYPE_PCM_MULAW, CODEC_TYPE_TTA, CODEC_TYPE_WAVPACK};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatReader, Track};
+
+use crate::app::{AppState, EntryKind, PlayAllRun, TrackPicker};
+use crate::device::DeviceBackend;
+use crate::error::SpinupError;
+use crate::fsutil::file_stamp;
+use crate::playlist::read_m3u;
+use crate::input::{PANNING_STEP, SEEK_LONG_STEP_SECS, SEEK_STEP_SECS};
+use crate::ui::format_duration;
+
+// volume is stored as an amplitude factor; each keypress moves it by the step
+// and it is clamped to the range of 0% to 200%
+pub(crate) const VOLUME_STEP: f64 = 0.1;
+
+pub(crate) const MAX_VOLUME: f64 = 2.0;
+
+// the playback rate changes by this factor per keypress and is clamped to the range
+pub(crate) const PLAYBACK_RATE_STEP: f64 = 0.05;
+
+pub(crate) const MIN_PLAYBACK_RATE: f64 = 0.25;
+
+pub(crate) const MAX_PLAYBACK_RATE: f64 = 4.0;
+
+// how long the bottom line says a sound finished after it plays to its end
+pub(crate) const FINISHED_INDICATOR_TIME: std::time::Duration = std::time::Duration::from_secs(1);
+
+// the longest fade in allowed when starting playback
+pub(crate) const MAX_START_FADE: std::time::Duration = std::time::Duration::from_secs(5);
+
+// how many min/max pairs a waveform is reduced to, which gets downsampled again
+// to the width of the pane when it's drawn
+pub(crate) const WAVEFORM_RESOLUTION: usize = 2048;
+
+// how many frames of audio go into each min/max pair while decoding a waveform
+pub(crate) const WAVEFORM_BLOCK_FRAMES: usize = 256;
+
+// how many packets are read to estimate the bitrate of files without a duration
+pub(crate) const BITRATE_ESTIMATE_PACKETS: usize = 50;
+
+// the sizes of the sampler info at the start of a wav's 'smpl' chunk and of each
+// loop after it, and the most of the chunk that's read
+const SMPL_HEADER_BYTES: usize = 36;
+
+const SMPL_LOOP_BYTES: usize = 24;
+
+const SMPL_CHUNK_MAX_BYTES: usize = 64 * 1024;
+
+// compressed files up to this size have every packet read to find their bitrate
+// without counting tags and cover art. past it those are too small to matter and
+// the file size is used.
+pub(crate) const ENCODED_BITRATE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+// how long volume, playback rate and panning changes take to ramp on the playing sound so they don't click
+pub(crate) const PARAM_TWEEN_MS: u64 = 50;
+
+// the shortest loop region that can be set between the 'A' and 'B' markers
+pub(crate) const MIN_LOOP_LENGTH: std::time::Duration = std::time::Duration::from_millis(100);
+
+// the most files in a row auto-advance skips over when they can't be played, so a
+// directory full of broken files doesn't keep it busy
+const MAX_ADVANCE_SKIPS: usize = 8;
+
+// how long to wait before trying to open the audio output again when there isn't one
+pub(crate) const AUDIO_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// the audio output sounds are played on, which may be missing on a machine without a
+// device or while the sound server restarts. the file list works without it, and
+// it's opened again the next time something is played.
+pub(crate) struct AudioOutput {
+    pub(crate) manager: Option<AudioManager<DeviceBackend>>,
+    pub(crate) last_attempt: std::time::Instant,
+    pub(crate) device: Option<String>, // the name of the output device picked, or None for the default
+}
+
+impl AudioOutput {
+    // tries to open the audio output on `device`, returning the error if it can't be
+    pub(crate) fn open(device: Option<String>) -> (Self, Option<SpinupError>) {
+        let mut output = AudioOutput { manager: None, last_attempt: std::time::Instant::now(), device };
+        let err = output.connect().err();
+        (output, err)
+    }
+
+    pub(crate) fn connect(&mut self) -> Result<(), SpinupError> {
+        self.last_attempt = std::time::Instant::now();
+        let settings = AudioManagerSettings {
+            backend_settings: self.device.clone(),
+            ..AudioManagerSettings::default()
+        };
+        let manager = AudioManager::<DeviceBackend>::new(settings)
+            .map_err(|err| SpinupError::AudioBackend(err.to_string()))?;
+        self.manager = Some(manager);
+        Ok(())
+    }
+
+    // returns the audio manager to play sounds with, trying to open the output again
+    // if it's missing and the last try wasn't too long ago
+    pub(crate) fn manager(&mut self) -> Result<&mut AudioManager<DeviceBackend>, SpinupError> {
+        if self.manager.is_none() && self.last_attempt.elapsed() >= AUDIO_RETRY_INTERVAL {
+            _ = self.connect();
+        }
+        self.manager.as_mut().ok_or_else(|| SpinupError::AudioBackend("No audio output device available".to_string()))
+    }
+}
+
+// how long a playing sound's position can stay put before the audio output is taken
+// to have stopped, like when its device was unplugged
+pub(crate) const AUDIO_STALL_TIME: std::time::Duration = std::time::Duration::from_secs(3);
+
+// files bigger than this are streamed from disk by default instead of decoded into memory
+pub(crate) const DEFAULT_STREAM_THRESHOLD: u64 = 50 * 1024 * 1024;
+
+// a playing sound, which is either decoded into memory or streamed from its file
+pub(crate) enum SoundHandle {
+    Static(StaticSoundHandle),
+    Streaming(StreamingSoundHandle<FromFileError>),
+}
+
+impl SoundHandle {
+    pub(crate) fn state(&self) -> PlaybackState {
+        match self {
+            SoundHandle::Static(handle) => handle.state(),
+            SoundHandle::Streaming(handle) => handle.state(),
+        }
+    }
+
+    pub(crate) fn position(&self) -> f64 {
+        match self {
+            SoundHandle::Static(handle) => handle.position(),
+            SoundHandle::Streaming(handle) => handle.position(),
+        }
+    }
+
+    pub(crate) fn set_volume(&mut self, volume: f64, tween: Tween) -> Result<(), kira::CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.set_volume(volume, tween),
+            SoundHandle::Streaming(handle) => handle.set_volume(volume, tween),
+        }
+    }
+
+    pub(crate) fn set_playback_rate(&mut self, rate: f64, tween: Tween) -> Result<(), kira::CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.set_playback_rate(rate, tween),
+            SoundHandle::Streaming(handle) => handle.set_playback_rate(rate, tween),
+        }
+    }
+
+    pub(crate) fn set_panning(&mut self, panning: f64, tween: Tween) -> Result<(), kira::CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.set_panning(panning, tween),
+            SoundHandle::Streaming(handle) => handle.set_panning(panning, tween),
+        }
+    }
+
+    pub(crate) fn stop(&mut self, tween: Tween) -> Result<(), kira::CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.stop(tween),
+            SoundHandle::Streaming(handle) => handle.stop(tween),
+        }
+    }
+
+    pub(crate) fn seek_to(&mut self, position: f64) -> Result<(), kira::CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.seek_to(position),
+            SoundHandle::Streaming(handle) => handle.seek_to(position),
+        }
+    }
+}
+
+pub(crate) fn play_selected_file(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError>  {
+    let snd_path = match app_state.selected_file_path() {
+        Some(path) => path,
+        None => return Ok(())
+    };
+    let options = app_state.sound_state.play_options();
+    start_file(app_state, audio, snd_path, options)
+}
+
+// plays the selected file starting `offset` into the sound
+pub(crate) fn play_selected_file_at(app_state: &mut AppState, audio: &mut AudioOutput, offset: std::time::Duration) -> Result<(), SpinupError>  {
+    let snd_path = match app_state.selected_file_path() {
+        Some(path) => path,
+        None => return Ok(())
+    };
+    let options = PlayOptions {
+        start_position: offset,
+        ..app_state.sound_state.play_options()
+    };
+    start_file(app_state, audio, snd_path, options)
+}
+
+// plays the selected file holding on the loop in its 'smpl' chunk, to hear whether
+// the loop is seamless. previews don't cut it short.
+pub(crate) fn play_selected_sample_loop(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    let snd_path = match app_state.selected_file_path() {
+        Some(path) => path,
+        None => return Ok(())
+    };
+    let sample_loop = match read_smpl_loop(&snd_path) {
+        Some(sample_loop) => sample_loop,
+        None => return Err(SpinupError::InvalidPath { path: snd_path, reason: "doesn't have loop points".to_string() }),
+    };
+    let options = PlayOptions {
+        stop_after: None,
+        sample_loop: Some(sample_loop),
+        ..app_state.sound_state.play_options()
+    };
+    start_file(app_state, audio, snd_path, options)
+}
+
+// loads the file and starts playing it as described by `options`. files bigger
+// than the stream threshold are streamed from disk rather than decoded into memory
+// first, as long as their length can be found for the gauge and seeking.
+pub(crate) fn start_file(
+    app_state: &mut AppState, 
+    audio: &mut AudioOutput,
+    snd_path: PathBuf,
+    options: PlayOptions,
+) -> Result<(), SpinupError>  {
+    // a file with more than one audio track asks which one to play the first time,
+    // and the picker starts it again with the same options once one is chosen
+    let track_id = app_state.chosen_tracks.get(&snd_path).copied();
+    if track_id.is_none() {
+        let tracks = list_audio_tracks(&snd_path).unwrap_or_default();
+        if tracks.len() > 1 {
+            app_state.track_picker = Some(TrackPicker::new(snd_path, tracks, options));
+            return Ok(());
+        }
+    }
+    let manager = audio.manager()?;
+    let track_gain = app_state.track_gain_of(&snd_path);
+
+    // a file that was decoded for its waveform or played a moment ago isn't read again.
+    // kira can only stream the first track, so a picked track is always decoded.
+    let cached = app_state.cached_sound(&snd_path);
+    let stamp = file_stamp(&snd_path);
+    let stream_duration = match (&cached, stamp) {
+        (None, Some(stamp)) if track_id.is_none() && options.sample_loop.is_none() && stamp.size > app_state.sound_state.stream_threshold && kira_can_read(&snd_path) => {
+            probe_file_info(&snd_path).ok()
+                .and_then(|(codec_data, _)| codec_data.duration.map(|duration| (duration, codec_data.duration_estimated)))
+        },
+        _ => None,
+    };
+
+    if let Some((duration, duration_estimated)) = stream_duration {
+        check_start_position(options.start_position, duration)?;
+        let sound_settings = app_state.sound_state.streaming_sound_settings()
+            .volume(app_state.sound_state.volume_with_gain(track_gain))
+            .fade_in_tween(options.fade_in)
+            .start_position(options.start_position.as_secs_f64());
+        let sound_data = StreamingSoundData::from_file(&snd_path, sound_settings)?;
+
+        // fade out anything playing right before we start streaming the new file
+        app_state.sound_state.stop_sound(options.fade_out)?;
+        // the audio thread stops taking sounds when its device went away and it
+        // couldn't move to another one, so the output is opened again to play it on
+        let play_handle = match manager.play(sound_data).map_err(SpinupError::from) {
+            Err(SpinupError::AudioBackend(_)) => {
+                reset_audio(app_state, audio)?;
+                audio.manager()?.play(StreamingSoundData::from_file(&snd_path, sound_settings)?)?
+            },
+            result => result?,
+        };
+        app_state.sound_state.started_sound(SoundHandle::Streaming(play_handle), None, duration, options.start_position, snd_path.clone());
+        app_state.sound_state.track_gain = track_gain;
+
+        // a length estimated from the bitrate can be well off for VBR files, so the
+        // packets are counted up in the background to put the real one on the gauge
+        app_state.sound_state.duration_estimated = duration_estimated;
+        if duration_estimated {
+            app_state.start_duration_count(&snd_path);
+        }
+        if app_state.sound_state.mono_check {
+            app_state.last_info_msg = "Streamed files play without the mono check downmix".to_string();
+        }
+    } else {
+        let sound_settings = app_state.sound_state.sound_settings()
+            .volume(app_state.sound_state.volume_with_gain(track_gain))
+            .fade_in_tween(options.fade_in)
+            .start_position(options.start_position.as_secs_f64());
+        let mut sound_data = match cached {
+            Some(sound_data) => StaticSoundData { settings: sound_settings, ..sound_data },
+            None => {
+                let sound_data = load_sound_data(&snd_path, sound_settings, track_id)?;
+                if let Some(stamp) = stamp {
+                    app_state.cache_sound(&snd_path, stamp, sound_data.clone());
+                }
+                sound_data
+            },
+        };
+        if app_state.sound_state.mono_check {
+            downmix_to_mono(&mut sound_data);
+        }
+
+        // a sampler loop plays up to its last frame and then keeps jumping back to
+        // its first, which kira does without a gap
+        if let Some((loop_start, loop_end)) = options.sample_loop {
+            let loop_end = loop_end as usize;
+            if loop_end >= sound_data.frames.len() {
+                return Err(SpinupError::Decode("the loop points are past the end of the file".to_string()));
+            }
+            sound_data.frames = std::sync::Arc::new(sound_data.frames[..=loop_end].to_vec());
+            sound_data.settings.loop_behavior = Some(LoopBehavior {
+                start_position: loop_start as f64 / sound_data.sample_rate as f64,
+            });
+        }
+        check_start_position(options.start_position, sound_data.duration())?;
+
+        // fade out anything playing right before we queue our new file's data
+        app_state.sound_state.stop_sound(options.fade_out)?;
+
+        // start playing
+        let play_handle = match manager.play(sound_data.clone()).map_err(SpinupError::from) {
+            Err(SpinupError::AudioBackend(_)) => {
+                reset_audio(app_state, audio)?;
+                audio.manager()?.play(sound_data.clone())?
+            },
+            result => result?,
+        };
+        let duration = sound_data.duration();
+        app_state.sound_state.started_sound(SoundHandle::Static(play_handle), Some(sound_data), duration, options.start_position, snd_path.clone());
+        app_state.sound_state.track_gain = track_gain;
+        app_state.sound_state.sample_loop = options.sample_loop;
+    }
+    app_state.sound_state.stop_at = options.stop_after.map(|length| options.start_position + length);
+    app_state.playing_file_path = Some(snd_path);
+
+    Ok(())
+}
+
+// loads a whole file to play it. kira only plays the first track of a file, so when
+// that's the picture of a video, or another track was picked, the audio track is
+// decoded here instead.
+fn load_sound_data(snd_path: &Path, settings: StaticSoundSettings, track_id: Option<u32>) -> Result<StaticSoundData, SpinupError> {
+    let decode_track = |track_id| -> Result<StaticSoundData, SpinupError> {
+        let decoded = compute_waveform(snd_path, &std::sync::atomic::AtomicBool::new(false), true, track_id)
+            .and_then(|decoded| decoded.sound_data)
+            .ok_or_else(|| SpinupError::Decode("couldn't decode the audio track".to_string()))?;
+        Ok(StaticSoundData { settings, ..decoded })
+    };
+    if track_id.is_some() {
+        return decode_track(track_id);
+    }
+    match StaticSoundData::from_file(snd_path, settings) {
+        Err(FromFileError::NoDefaultTrack) | Err(FromFileError::SymphoniaError(SymphoniaError::Unsupported(_)))
+            if !kira_can_read(snd_path) => decode_track(None),
+        result => Ok(result?),
+    }
+}
+
+// drops the audio output and opens it again, for when its device went away. the
+// sound that was playing on the old output goes with it.
+pub(crate) fn reset_audio(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    app_state.sound_state.output_lost();
+    app_state.playing_file_path = None;
+    app_state.play_all = None;
+    // the old output lets go of the device before the new one is opened
+    audio.manager = None;
+    audio.connect()?;
+    app_state.last_info_msg = "Audio device reset".to_string();
+    Ok(())
+}
+
+// moves playback to the output device named `device`, or the default one for None.
+// whatever was playing carries on from where it was on the new device, and if the
+// device can't be opened the old one is opened again.
+pub(crate) fn switch_audio_device(app_state: &mut AppState, audio: &mut AudioOutput, device: Option<String>) -> Result<(), SpinupError> {
+    let resume = match &app_state.playing_file_path {
+        Some(path) if app_state.sound_state.is_playing() => Some((path.clone(), app_state.sound_state.play_time)),
+        _ => None,
+    };
+    let play_all = app_state.play_all.take();
+
+    let previous = std::mem::replace(&mut audio.device, device);
+    if let Err(err) = reset_audio(app_state, audio) {
+        audio.device = previous;
+        _ = audio.connect();
+        return Err(err);
+    }
+    if let Some((snd_path, position)) = resume {
+        let options = PlayOptions {
+            fade_out: std::time::Duration::ZERO,
+            start_position: position,
+            sample_loop: app_state.sound_state.sample_loop,
+            ..app_state.sound_state.play_options()
+        };
+        start_file(app_state, audio, snd_path, options)?;
+        app_state.play_all = play_all;
+    }
+    app_state.last_info_msg = format!("Playing on {}", audio.device.as_deref().unwrap_or("the default output device"));
+    Ok(())
+}
+
+// returns an error if `start_position` isn't within a sound `duration` long
+pub(crate) fn check_start_position(start_position: std::time::Duration, duration: std::time::Duration) -> Result<(), SpinupError> {
+    if start_position >= duration {
+        return Err(SpinupError::InvalidRequest(format!(
+            "start offset {} is past the end of the file ({})", 
+            format_duration(start_position), 
+            format_duration(duration))));
+    }
+    Ok(())
+}
+
+// sums the left and right channels together so both sides play the same signal
+pub(crate) fn downmix_to_mono(sound_data: &mut StaticSoundData) {
+    let mono_frames: Vec<kira::dsp::Frame> = sound_data.frames.iter()
+        .map(|frame| kira::dsp::Frame::from_mono((frame.left + frame.right) * 0.5))
+        .collect();
+    sound_data.frames = std::sync::Arc::new(mono_frames);
+}
+
+// toggles the mono downmix, reloading the playing file at its current position
+// so the change can be heard right away
+pub(crate) fn toggle_mono_check(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    app_state.sound_state.mono_check = !app_state.sound_state.mono_check;
+    if !app_state.sound_state.is_playing() {
+        return Ok(());
+    }
+    if let Some(snd_path) = app_state.playing_file_path.clone() {
+        let options = PlayOptions {
+            fade_out: std::time::Duration::ZERO,
+            start_position: app_state.sound_state.play_time,
+            sample_loop: app_state.sound_state.sample_loop,
+            ..app_state.sound_state.play_options()
+        };
+        start_file(app_state, audio, snd_path, options)?;
+    }
+    Ok(())
+}
+
+// plays the file selected in the recently played overlay, closing it
+pub(crate) fn play_selected_recent(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    let snd_path = app_state.selected_recent_path()?;
+    app_state.recent_list = None;
+    let options = app_state.sound_state.play_options();
+    start_file(app_state, audio, snd_path.clone(), options)?;
+    app_state.remember_played(&snd_path);
+    Ok(())
+}
+
+// takes the selected entry out of the queue and plays it
+pub(crate) fn play_selected_queue_item(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    let snd_path = match app_state.take_selected_queue_item() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    app_state.select_file_path(&snd_path);
+    let options = app_state.sound_state.play_options();
+    start_file(app_state, audio, snd_path, options)
+}
+
+// reads the selected playlist and plays its first file, putting the rest of them
+// at the front of the queue so they play before anything that was queued already
+pub(crate) fn play_selected_playlist(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    let playlist_path = match app_state.selected_entry_path() {
+        Some(p) if app_state.is_playlist_selected() => p,
+        _ => return Ok(()),
+    };
+    let playlist = read_m3u(&playlist_path)?;
+    let skipped = playlist.skipped_label();
+    let mut files = playlist.files.into_iter();
+    let first = match files.next() {
+        Some(first) => first,
+        None if skipped.is_empty() => return Err(SpinupError::InvalidPath { path: playlist_path, reason: "is empty".to_string() }),
+        None => return Err(SpinupError::InvalidPath { path: playlist_path, reason: format!("has nothing to play ({})", skipped) }),
+    };
+    let queued = files.len();
+    app_state.queue.splice(0..0, files);
+    app_state.clamp_queue_selection();
+    let options = app_state.sound_state.play_options();
+    start_file(app_state, audio, first, options)?;
+    app_state.last_info_msg = if skipped.is_empty() {
+        format!("Playlist: queued {} more files", queued)
+    } else {
+        format!("Playlist: queued {} more files, {}", queued, skipped)
+    };
+    Ok(())
+}
+
+// plays every supported file in the current directory back to back, starting
+// with the selected file.
+pub(crate) fn start_play_all(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    // play the files in the order they're listed
+    let mut paths: Vec<PathBuf> = app_state.entries.iter()
+        .filter(|e| e.kind == EntryKind::File)
+        .map(|e| e.path.clone())
+        .collect();
+
+    // start at the selected file, or at the first file if a directory is selected
+    if let Some(sel_path) = app_state.selected_file_path() {
+        if let Some(start) = paths.iter().position(|p| *p == sel_path) {
+            paths.drain(..start);
+        }
+    }
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let first_path = paths[0].clone();
+    app_state.play_all = Some(PlayAllRun { paths, next: 1 });
+    app_state.select_file_path(&first_path);
+    let options = app_state.sound_state.play_options();
+    start_file(app_state, audio, first_path, options)
+}
+
+// selects and plays the file after the one that just finished, taking it from the
+// queue first, then the play-all run, then the auto-advance mode.
+pub(crate) fn advance_to_next_file(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    match app_state.next_file_path() {
+        Some(next_path) => start_next_file(app_state, audio, next_path),
+        None => Ok(()),
+    }
+}
+
+// advances to the next file once the playing one finishes on its own. files that
+// can't be read or decoded are skipped, with the reason left in the error log, so
+// one broken file doesn't end a run. anything else, like the audio output going
+// away, stops advancing.
+pub(crate) fn auto_advance(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    let mut failed: Option<PathBuf> = None;
+    for _ in 0..=MAX_ADVANCE_SKIPS {
+        let next_path = match app_state.next_file_path() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        // repeating a file that can't be played would only fail again
+        if failed.as_ref() == Some(&next_path) {
+            return Ok(());
+        }
+        match start_next_file(app_state, audio, next_path.clone()) {
+            Err(err @ (SpinupError::Decode(_) | SpinupError::Io(_) | SpinupError::InvalidPath { .. })) => {
+                let name = next_path.file_name().unwrap_or(next_path.as_os_str()).to_string_lossy();
+                app_state.last_error_msg = format!("Auto-advance Error: skipped {}, {}", name, err);
+                app_state.record_error();
+                // the file after it is found from where it is in the list
+                app_state.playing_file_path = Some(next_path.clone());
+                failed = Some(next_path);
+            },
+            result => return result,
+        }
+    }
+    Ok(())
+}
+
+// selects `next_path` and starts it, crossfading from the sound playing if that's set
+fn start_next_file(app_state: &mut AppState, audio: &mut AudioOutput, next_path: PathBuf) -> Result<(), SpinupError> {
+    app_state.select_file_path(&next_path);
+
+    // with a crossfade the old sound fades out while the new one fades in
+    let crossfade = app_state.sound_state.crossfade;
+    let mut options = app_state.sound_state.play_options();
+    if !crossfade.is_zero() {
+        options.fade_out = crossfade;
+        options.fade_in = Some(Tween {
+            duration: crossfade,
+            ..Default::default()
+        });
+    }
+    start_file(app_state, audio, next_path, options)
+}
+
+// restarts the current sound from the beginning without reloading it, and if
+// nothing is playing this plays the selected file.
+pub(crate) fn restart_sound(app_state: &mut AppState, audio: &mut AudioOutput) -> Result<(), SpinupError> {
+    if app_state.sound_state.is_playing() {
+        app_state.sound_state.seek_to_start()?;
+    } else if app_state.is_file_selected() {
+        play_selected_file(app_state, audio)?;
+    }
+    Ok(())
+}
+
+// the level of one channel over a short stretch of the playing sound
+#[derive(Default, Clone, Copy)]
+pub(crate) struct ChannelLevel {
+    pub(crate) peak: f32, // the largest absolute sample
+    pub(crate) rms: f32, // the root mean square of the samples
+}
+
+pub(crate) struct SoundState {
+    pub(crate) sound: Option<SoundHandle>,  // this may be the handle to the currently playing sound file
+    pub(crate) sound_data: Option<StaticSoundData>, // this may be the data for the sound file playing, when it isn't streamed
+    pub(crate) mono_sound: bool, // set when both channels of `sound_data` are the same, so the level meter shows one
+    pub(crate) playing_path: Option<PathBuf>, // the full path of the sound file playing, for the now playing line
+    pub(crate) fading_sound: Option<SoundHandle>, // a stopped sound that may still be fading out
+    pub(crate) stop_fade: std::time::Duration, // how long sounds take to fade out when stopped
+    pub(crate) start_fade: std::time::Duration, // how long sounds take to fade in when started
+    pub(crate) crossfade: std::time::Duration, // how long auto-advancing crossfades between sounds
+    pub(crate) crossfade_checked: bool, // set once the playing sound has started crossfading to the next
+    pub(crate) play_time: std::time::Duration, // how long the file has been playing
+    pub(crate) play_duration: std::time::Duration, // total duration of the sound
+    pub(crate) volume: f64, // the volume as an amplitude factor, kept for the whole session
+    pub(crate) muted: bool, // when muted, sounds play silently but `volume` is kept to restore later
+    pub(crate) playback_rate: f64, // the speed factor applied to playback, kept for the whole session
+    pub(crate) panning: f64, // 0.0 is hard left, 0.5 is center and 1.0 is hard right, kept for the whole session
+    pub(crate) mono_check: bool, // when set, sounds are downmixed to mono as they are loaded
+    pub(crate) loop_start: Option<std::time::Duration>, // the 'A' point of the loop region
+    pub(crate) loop_end: Option<std::time::Duration>, // the 'B' point of the loop region
+    pub(crate) finished_at: Option<std::time::Instant>, // when the last sound played to its end, for the indicator
+    pub(crate) preview: bool, // when set, sounds only play for `preview_length`
+    pub(crate) preview_length: std::time::Duration,
+    pub(crate) stop_at: Option<std::time::Duration>, // the play time the current sound gets stopped at
+    pub(crate) stream_threshold: u64, // files bigger than this many bytes are streamed instead of loaded into memory
+    pub(crate) stall_check: Option<(f64, std::time::Instant)>, // the position of the playing sound and since when it's been there
+    pub(crate) seek_step: f64, // how many seconds the arrow keys seek
+    pub(crate) seek_long_step: f64, // how many seconds the arrow keys seek with shift
+    pub(crate) replay_gain: bool, // when set, files play at the gain in their ReplayGain or R128 tags
+    pub(crate) track_gain: Option<f64>, // the gain tagged on the playing file in dB, whether it's applied or not
+    pub(crate) sample_loop: Option<(u32, u32)>, // the sampler loop the playing sound keeps repeating, in frames
+    pub(crate) duration_estimated: bool, // set while `play_duration` is only estimated from the bitrate
+}
+
+impl Default for SoundState {
+    fn default() -> Self {
+        Self {
+            sound: None,
+            sound_data: None,
+            mono_sound: false,
+            playing_path: None,
+            fading_sound: None,
+            stop_fade: std::time::Duration::from_millis(250),
+            start_fade: std::time::Duration::ZERO,
+            crossfade: std::time::Duration::ZERO,
+            crossfade_checked: false,
+            play_time: std::time::Duration::ZERO,
+            play_duration: std::time::Duration::ZERO,
+            volume: 1.0,
+            muted: false,
+            playback_rate: 1.0,
+            panning: 0.5,
+            mono_check: false,
+            loop_start: None,
+            loop_end: None,
+            finished_at: None,
+            preview: false,
+            preview_length: std::time::Duration::from_secs(3),
+            stop_at: None,
+            stream_threshold: DEFAULT_STREAM_THRESHOLD,
+            stall_check: None,
+            seek_step: SEEK_STEP_SECS,
+            seek_long_step: SEEK_LONG_STEP_SECS,
+            replay_gain: false,
+            track_gain: None,
+            sample_loop: None,
+            duration_estimated: false,
+        }
+    }
+}
+
+// describes how `start_file` should start a new sound
+pub(crate) struct PlayOptions {
+    pub(crate) fade_out: std::time::Duration, // how long the sound already playing takes to fade out
+    pub(crate) fade_in: Option<Tween>, // an optional fade in from silence for the new sound
+    pub(crate) start_position: std::time::Duration, // how far into the new sound to start playing
+    pub(crate) stop_after: Option<std::time::Duration>, // stops the new sound after playing this long
+    pub(crate) sample_loop: Option<(u32, u32)>, // the first and last frame of a sampler loop to keep playing
+}
+
+#[derive(Default, Clone, Copy)]
+pub(crate) struct SoundFileCodecData {
+    // information about the playing file
+    pub(crate) sample_rate: Option<u32>,
+    pub(crate) bit_depth: Option<u32>,
+    pub(crate) channels: Option<symphonia::core::audio::Channels>,
+    pub(crate) codec: Option<&'static str>, // the short name of the codec, like "mp3", "pcm_s16le" or "pcm_s16be" for aiff
+    pub(crate) codec_kind: Option<CodecKind>,
+    pub(crate) sample_loop: Option<(u32, u32)>, // the first and last frame of the loop in a wav's 'smpl' chunk
+    pub(crate) duration: Option<std::time::Duration>,
+    pub(crate) duration_estimated: bool, // set when the header didn't say how long the file is
+    pub(crate) bitrate: Option<u32>, // the average bitrate in kbps
+    pub(crate) file_size: Option<u64>, // in bytes
+
+}
+
+// how a codec stores the sound, which says whether anything was thrown away
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum CodecKind {
+    Uncompressed,
+    Lossless,
+    Lossy,
+}
+
+impl CodecKind {
+    // sorts a codec by its id. a-law and mu-law are pcm but squeeze the samples into
+    // 8 bits, so they count as lossy along with adpcm.
+    pub(crate) fn of(codec: CodecType) -> Option<Self> {
+        const LOSSLESS_CODECS: [CodecType; 5] = [CODEC_TYPE_FLAC, CODEC_TYPE_WAVPACK, CODEC_TYPE_MONKEYS_AUDIO, CODEC_TYPE_ALAC, CODEC_TYPE_TTA];
+        if codec == CODEC_TYPE_NULL {
+            None
+        } else if LOSSLESS_CODECS.contains(&codec) {
+            Some(CodecKind::Lossless)
+        } else if codec != CODEC_TYPE_PCM_ALAW && codec != CODEC_TYPE_PCM_MULAW
+            && codec_name(codec).is_some_and(|name| name.starts_with("pcm_")) {
+            Some(CodecKind::Uncompressed)
+        } else {
+            Some(CodecKind::Lossy)
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            CodecKind::Uncompressed => "uncompressed",
+            CodecKind::Lossless => "lossless",
+            CodecKind::Lossy => "lossy",
+        }
+    }
+}
+
+// the tags of the selected file, each left out if the file doesn't have it
+#[derive(Default, Clone)]
+pub(crate) struct SoundFileMetadata {
+    pub(crate) title: Option<String>,
+    pub(crate) artist: Option<String>,
+    pub(crate) album: Option<String>,
+    pub(crate) track: Option<String>,
+    pub(crate) year: Option<String>,
+    pub(crate) genre: Option<String>,
+    pub(crate) replay_gain: Option<f64>, // the ReplayGain track gain in dB
+    pub(crate) r128_gain: Option<f64>, // the R128 track gain in dB, moved to the ReplayGain reference level
+}
+
+impl SoundFileMetadata {
+    // fills in the fields from the standard tags in `tags`, skipping empty values
+    pub(crate) fn read_tags(&mut self, tags: &[symphonia::core::meta::Tag]) {
+        use symphonia::core::meta::StandardTagKey;
+        for tag in tags {
+            // the gains are numbers rather than text to show. R128 gains are in 1/256
+            // dB relative to -23 LUFS, which is 5 dB quieter than ReplayGain aims for.
+            if tag.std_key == Some(StandardTagKey::ReplayGainTrackGain) {
+                self.replay_gain = parse_gain_db(&tag.value.to_string()).or(self.replay_gain);
+                continue;
+            }
+            if tag.key.to_ascii_uppercase().ends_with("R128_TRACK_GAIN") {
+                let r128 = tag.value.to_string().trim().parse::<i32>().ok();
+                self.r128_gain = r128.map(|gain| gain as f64 / 256.0 + 5.0).or(self.r128_gain);
+                continue;
+            }
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                Some(StandardTagKey::TrackNumber) => &mut self.track,
+                Some(StandardTagKey::Date) => &mut self.year,
+                Some(StandardTagKey::Genre) => &mut self.genre,
+                _ => continue,
+            };
+            let value = tag.value.to_string().trim().to_string();
+            if !value.is_empty() {
+                *field = Some(value);
+            }
+        }
+    }
+
+    // the tags as label and value pairs, in the order the info pane shows them
+    pub(crate) fn fields(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("Title", &self.title),
+            ("Artist", &self.artist),
+            ("Album", &self.album),
+            ("Track", &self.track),
+            ("Year", &self.year),
+            ("Genre", &self.genre),
+        ]
+        .into_iter()
+        .filter_map(|(label, value)| value.as_deref().map(|v| (label, v)))
+        .collect()
+    }
+
+    // the gain to play the file at, in dB, from ReplayGain before R128
+    pub(crate) fn track_gain(&self) -> Option<f64> {
+        self.replay_gain.or(self.r128_gain)
+    }
+}
+
+// reads a gain like "-7.20 dB"
+fn parse_gain_db(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let number = value.strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .or_else(|| value.strip_suffix("DB"))
+        .unwrap_or(value);
+    number.trim().parse::<f64>().ok().filter(|gain| gain.is_finite())
+}
+
+impl SoundState {
+    // fades out the currently playing sound over `fade` and resets the data structure.
+    // the play time is kept so the gauge can keep showing while the sound fades.
+    pub(crate) fn stop_sound(&mut self, fade: std::time::Duration) -> Result<(), SpinupError> {
+        if let Some(mut current_sound) = self.sound.take() {
+            let tween = Tween {
+                duration: fade,
+                easing: kira::tween::Easing::Linear,
+                ..Default::default()
+            };
+            current_sound.stop(tween)?;
+            self.fading_sound = Some(current_sound);
+            self.sound_data = None;
+            self.playing_path = None;
+            self.clear_loop_region();
+        }
+        Ok(())
+    }
+
+    // drops the fading sound once it has finished fading out
+    pub(crate) fn update_fading_sound(&mut self) {
+        if let Some(fading_sound) = &self.fading_sound {
+            if fading_sound.state() == PlaybackState::Stopped {
+                self.fading_sound = None;
+                if self.sound.is_none() {
+                    self.play_time = std::time::Duration::ZERO;
+                }
+            }
+        }
+    }
+
+    // update the data structure with the sound that just started playing. streamed
+    // sounds don't have their `data` in memory.
+    pub(crate) fn started_sound(
+        &mut self, 
+        handle: SoundHandle, 
+        data: Option<StaticSoundData>,
+        duration: std::time::Duration,
+        start_position: std::time::Duration,
+        path: PathBuf,
+    ) {
+        self.playing_path = Some(path);
+        self.play_duration = duration;
+        self.sound = Some(handle);
+        self.play_time = start_position;
+        self.mono_sound = matches!(&data, Some(data) if data.frames.iter().all(|frame| frame.left == frame.right));
+        self.sound_data = data;
+        self.stall_check = None;
+        self.crossfade_checked = false;
+        self.finished_at = None;
+        self.stop_at = None;
+        self.sample_loop = None;
+        self.duration_estimated = false;
+        self.clear_loop_region();
+    }
+
+    // measures the peak and RMS level of each channel over the `window` of the sound
+    // leading up to the play position, giving a single level for mono sounds
+    pub(crate) fn channel_levels(&self, window: std::time::Duration) -> Vec<ChannelLevel> {
+        let data = match &self.sound_data {
+            Some(data) => data,
+            None => return vec![],
+        };
+        let sample_rate = data.sample_rate as f64;
+        let end = ((self.play_time.as_secs_f64() * sample_rate) as usize).min(data.frames.len());
+        let start = end.saturating_sub((window.as_secs_f64() * sample_rate) as usize);
+        let frames = &data.frames[start..end];
+
+        let channel_count = if self.mono_sound { 1 } else { 2 };
+        let mut levels = vec![ChannelLevel::default(); channel_count];
+        let mut sums = vec![0.0f32; channel_count];
+        for frame in frames {
+            for (ch, sample) in [frame.left, frame.right].iter().take(channel_count).enumerate() {
+                levels[ch].peak = levels[ch].peak.max(sample.abs());
+                sums[ch] += sample * sample;
+            }
+        }
+        if !frames.is_empty() {
+            for (level, sum) in levels.iter_mut().zip(sums) {
+                level.rms = (sum / frames.len() as f32).sqrt();
+            }
+        }
+        levels
+    }
+
+    // describes what the playing sound is doing for the now playing line
+    pub(crate) fn play_state_label(&self) -> &'static str {
+        match self.sound.as_ref().map(|s| s.state()) {
+            Some(PlaybackState::Playing) if self.loop_start.is_some() && self.loop_end.is_some() => "Looping",
+            Some(PlaybackState::Playing) if self.sample_loop.is_some() => "Looping",
+            Some(PlaybackState::Playing) if self.stop_at.is_some() => "Previewing",
+            Some(PlaybackState::Playing) => "Playing",
+            Some(PlaybackState::Pausing) | Some(PlaybackState::Paused) => "Paused",
+            Some(PlaybackState::Stopping) | Some(PlaybackState::Stopped) | None => "Stopped",
+        }
+    }
+
+    // returns true if the playing sound is streamed from its file
+    pub(crate) fn is_streaming(&self) -> bool {
+        matches!(self.sound, Some(SoundHandle::Streaming(_)))
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        if let Some(current_sound) = &self.sound {
+            if current_sound.state() == PlaybackState::Playing {
+                return true;
+            }
+        }
+        false
+    }
+
+    // returns true when the playing sound hasn't moved for AUDIO_STALL_TIME, which
+    // means the audio output stopped running it
+    pub(crate) fn output_stalled(&mut self) -> bool {
+        let position = match &self.sound {
+            Some(sound) if sound.state() == PlaybackState::Playing => sound.position(),
+            _ => {
+                self.stall_check = None;
+                return false;
+            },
+        };
+        match self.stall_check {
+            Some((last, since)) if last == position => since.elapsed() >= AUDIO_STALL_TIME,
+            _ => {
+                self.stall_check = Some((position, std::time::Instant::now()));
+                false
+            },
+        }
+    }
+
+    // forgets the playing and fading sounds after the audio output they were on went
+    // away, so nothing shows as playing anymore
+    pub(crate) fn output_lost(&mut self) {
+        self.sound = None;
+        self.sound_data = None;
+        self.fading_sound = None;
+        self.playing_path = None;
+        self.play_time = std::time::Duration::ZERO;
+        self.play_duration = std::time::Duration::ZERO;
+        self.stall_check = None;
+        self.stop_at = None;
+        self.sample_loop = None;
+        self.duration_estimated = false;
+        self.clear_loop_region();
+    }
+
+    // returns true once when the playing sound reaches its end on its own, releasing
+    // the handle and the sound data. sounds stopped by the user are moved out of
+    // `sound`, so they never count as finished.
+    pub(crate) fn check_finished(&mut self) -> bool {
+        let finished = match &self.sound {
+            Some(current_sound) => current_sound.state() == PlaybackState::Stopped,
+            None => false,
+        };
+        if finished {
+            self.sound = None;
+            self.sound_data = None;
+            self.playing_path = None;
+            self.play_time = std::time::Duration::ZERO;
+            self.play_duration = std::time::Duration::ZERO;
+            self.clear_loop_region();
+            self.finished_at = Some(std::time::Instant::now());
+        }
+        finished
+    }
+
+    // stops the playing sound once it reaches the `stop_at` time, such as at the
+    // end of a preview
+    pub(crate) fn update_stop_at(&mut self) -> Result<(), SpinupError> {
+        if let Some(stop_at) = self.stop_at {
+            if self.sound.is_some() && self.play_time >= stop_at {
+                self.stop_at = None;
+                return self.stop_sound(self.stop_fade);
+            }
+        }
+        Ok(())
+    }
+
+    // returns true for a moment after a sound finished so the user knows it ended
+    pub(crate) fn show_finished(&self) -> bool {
+        matches!(self.finished_at, Some(t) if t.elapsed() < FINISHED_INDICATOR_TIME)
+    }
+
+    // returns true once per sound when it gets within the crossfade time of its end.
+    // sounds shorter than the crossfade or looping a region never crossfade.
+    pub(crate) fn crossfade_due(&mut self) -> bool {
+        if self.crossfade.is_zero() || self.crossfade_checked || !self.is_playing() {
+            return false;
+        }
+        if self.play_duration <= self.crossfade || (self.loop_start.is_some() && self.loop_end.is_some()) || self.sample_loop.is_some() {
+            return false;
+        }
+        let remaining = self.play_duration.saturating_sub(self.play_time).div_f64(self.playback_rate);
+        if remaining <= self.crossfade {
+            self.crossfade_checked = true;
+            return true;
+        }
+        false
+    }
+
+    // returns true if a stopped sound is still fading out and nothing else has started
+    pub(crate) fn is_fading(&self) -> bool {
+        if self.sound.is_some() {
+            return false;
+        }
+        if let Some(fading_sound) = &self.fading_sound {
+            return fading_sound.state() != PlaybackState::Stopped;
+        }
+        false
+    }
+
+    // returns true if there is a sound that can still be heard, either playing or fading out
+    pub(crate) fn is_audible(&self) -> bool {
+        self.is_playing() || self.is_fading()
+    }
+
+    // sets the panning, applying it to the playing sound if there is one
+    pub(crate) fn set_panning(&mut self, panning: f64) -> Result<(), SpinupError> {
+        // round to the nearest step so repeated float additions don't drift
+        let new_panning = (panning / PANNING_STEP).round() * PANNING_STEP;
+        self.panning = new_panning.clamp(0.0, 1.0);
+        if let Some(current_sound) = &mut self.sound {
+            current_sound.set_panning(self.panning, param_tween())?;
+        }
+        Ok(())
+    }
+
+    // describes the panning as a percentage to the left or right, like "L30"
+    pub(crate) fn panning_label(&self) -> String {
+        let percent = ((self.panning - 0.5) * 200.0).round() as i32;
+        match percent {
+            0 => "C".to_string(),
+            p if p < 0 => format!("L{}", -p),
+            p => format!("R{}", p),
+        }
+    }
+
+    // the default way to start a new sound, fading out the current one as if stopped.
+    // the fade in goes from silence up to the volume the sound is loaded with, so it
+    // ends at the chosen volume and keeps muted sounds silent.
+    pub(crate) fn play_options(&self) -> PlayOptions {
+        let fade_in = if self.start_fade.is_zero() {
+            None
+        } else {
+            Some(Tween {
+                duration: self.start_fade,
+                ..Default::default()
+            })
+        };
+        PlayOptions {
+            fade_out: self.stop_fade,
+            fade_in,
+            start_position: std::time::Duration::ZERO,
+            stop_after: if self.preview { Some(self.preview_length) } else { None },
+            sample_loop: None,
+        }
+    }
+
+    // the settings new sounds should be loaded with so they match the current
+    // volume, playback rate and panning
+    pub(crate) fn sound_settings(&self) -> StaticSoundSettings {
+        StaticSoundSettings::new()
+            .volume(self.effective_volume())
+            .playback_rate(self.playback_rate)
+            .panning(self.panning)
+    }
+
+    // the same settings for sounds that are streamed
+    pub(crate) fn streaming_sound_settings(&self) -> StreamingSoundSettings {
+        StreamingSoundSettings::new()
+            .volume(self.effective_volume())
+            .playback_rate(self.playback_rate)
+            .panning(self.panning)
+    }
+
+    // jumps the playing sound back to its beginning
+    pub(crate) fn seek_to_start(&mut self) -> Result<(), SpinupError> {
+        if let Some(current_sound) = &mut self.sound {
+            current_sound.seek_to(0.0)?;
+            self.play_time = std::time::Duration::ZERO;
+        }
+        Ok(())
+    }
+
+    // jumps to `fraction` (0.0 to 1.0) of the way through the playing sound
+    pub(crate) fn seek_to_fraction(&mut self, fraction: f64) -> Result<(), SpinupError> {
+        if let Some(current_sound) = &mut self.sound {
+            let new_time = self.play_duration.mul_f64(fraction.clamp(0.0, 1.0));
+            current_sound.seek_to(new_time.as_secs_f64())?;
+            self.play_time = new_time;
+        }
+        Ok(())
+    }
+
+    // moves the playback position by `amount` seconds, clamping to the start of the
+    // sound. seeking past the end stops the sound instead.
+    pub(crate) fn seek_by(&mut self, amount: f64) -> Result<(), SpinupError> {
+        if let Some(current_sound) = &mut self.sound {
+            let new_time = (self.play_time.as_secs_f64() + amount).max(0.0);
+            if new_time >= self.play_duration.as_secs_f64() {
+                return self.stop_sound(self.stop_fade);
+            }
+            current_sound.seek_to(new_time)?;
+            self.play_time = std::time::Duration::from_secs_f64(new_time);
+        }
+        Ok(())
+    }
+
+    // changes the volume by `delta`, applying it to the playing sound if there is one
+    pub(crate) fn adjust_volume(&mut self, delta: f64) -> Result<(), SpinupError> {
+        // round to the nearest step so repeated float additions don't drift
+        let new_volume = ((self.volume + delta) / VOLUME_STEP).round() * VOLUME_STEP;
+        self.volume = new_volume.clamp(0.0, MAX_VOLUME);
+        let volume = self.effective_volume();
+        if let Some(current_sound) = &mut self.sound {
+            current_sound.set_volume(volume, param_tween())?;
+        }
+        Ok(())
+    }
+
+    // the volume sounds should actually play at, taking muting into account
+    pub(crate) fn effective_volume(&self) -> f64 {
+        self.volume_with_gain(self.track_gain)
+    }
+
+    // the volume a sound tagged with `track_gain` plays at
+    pub(crate) fn volume_with_gain(&self, track_gain: Option<f64>) -> f64 {
+        if self.muted {
+            return 0.0;
+        }
+        match self.applied_gain_of(track_gain) {
+            Some(gain) => self.volume * 10f64.powf(gain / 20.0),
+            None => self.volume,
+        }
+    }
+
+    // the gain applied to the playing sound in dB, if any
+    pub(crate) fn applied_gain(&self) -> Option<f64> {
+        self.applied_gain_of(self.track_gain)
+    }
+
+    fn applied_gain_of(&self, track_gain: Option<f64>) -> Option<f64> {
+        track_gain.filter(|_| self.replay_gain)
+    }
+
+    // turns applying the tagged gain on or off, changing the playing sound's volume
+    pub(crate) fn toggle_replay_gain(&mut self) -> Result<(), SpinupError> {
+        self.replay_gain = !self.replay_gain;
+        let volume = self.effective_volume();
+        if let Some(current_sound) = &mut self.sound {
+            current_sound.set_volume(volume, param_tween())?;
+        }
+        Ok(())
+    }
+
+    // silences the playing sound or restores it to the chosen volume
+    pub(crate) fn toggle_mute(&mut self) -> Result<(), SpinupError> {
+        self.muted = !self.muted;
+        let volume = self.effective_volume();
+        if let Some(current_sound) = &mut self.sound {
+            current_sound.set_volume(volume, Tween::default())?;
+        }
+        Ok(())
+    }
+
+    // sets the playback rate, applying it to the playing sound if there is one
+    pub(crate) fn set_playback_rate(&mut self, rate: f64) -> Result<(), SpinupError> {
+        // round to the nearest step so repeated float additions don't drift
+        let new_rate = (rate / PLAYBACK_RATE_STEP).round() * PLAYBACK_RATE_STEP;
+        self.playback_rate = new_rate.clamp(MIN_PLAYBACK_RATE, MAX_PLAYBACK_RATE);
+        if let Some(current_sound) = &mut self.sound {
+            current_sound.set_playback_rate(self.playback_rate, param_tween())?;
+        }
+        Ok(())
+    }
+
+    // sets the 'A' point of the loop region to the current play time, swapping
+    // the markers if it ends up after the 'B' point.
+    pub(crate) fn set_loop_start(&mut self) -> Result<(), SpinupError> {
+        if self.sound.is_none() {
+            return Ok(());
+        }
+        self.set_loop_points(Some(self.play_time), self.loop_end)
+    }
+
+    // sets the 'B' point of the loop region to the current play time, swapping
+    // the markers if it ends up before the 'A' point.
+    pub(crate) fn set_loop_end(&mut self) -> Result<(), SpinupError> {
+        if self.sound.is_none() {
+            return Ok(());
+        }
+        self.set_loop_points(self.loop_start, Some(self.play_time))
+    }
+
+    // sets the markers, in order. a region shorter than MIN_LOOP_LENGTH would seek
+    // back on every tick, so the markers are left as they were instead.
+    fn set_loop_points(&mut self, a: Option<std::time::Duration>, b: Option<std::time::Duration>) -> Result<(), SpinupError> {
+        if let (Some(a), Some(b)) = (a, b) {
+            let (start, end) = (a.min(b), a.max(b));
+            if end - start < MIN_LOOP_LENGTH {
+                return Err(SpinupError::InvalidRequest("'A' and 'B' are too close together to loop between".to_string()));
+            }
+            self.loop_start = Some(start);
+            self.loop_end = Some(end);
+        } else {
+            self.loop_start = a;
+            self.loop_end = b;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn clear_loop_region(&mut self) {
+        self.loop_start = None;
+        self.loop_end = None;
+    }
+
+    // seeks back to the 'A' point once playback has passed the 'B' point
+    pub(crate) fn update_loop_region(&mut self) -> Result<(), SpinupError> {
+        if let (Some(start), Some(end)) = (self.loop_start, self.loop_end) {
+            if self.play_time >= end {
+                if let Some(current_sound) = &mut self.sound {
+                    current_sound.seek_to(start.as_secs_f64())?;
+                    self.play_time = start;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // returns the text to show on the gauge for the loop region, if any markers are set
+    pub(crate) fn loop_region_label(&self) -> Option<String> {
+        match (self.loop_start, self.loop_end) {
+            (Some(start), Some(end)) => Some(format!("A–B {:.1}s–{:.1}s", start.as_secs_f64(), end.as_secs_f64())),
+            (Some(start), None) => Some(format!("A {:.1}s–", start.as_secs_f64())),
+            (None, Some(end)) => Some(format!("B –{:.1}s", end.as_secs_f64())),
+            (None, None) => None,
+        }
+    }
+
+    // reads the playback position from the playing (or fading) sound. if the handle
+    // doesn't report a usable position, the wall-clock time `t` is added instead.
+    pub(crate) fn update_play_time(&mut self, t: std::time::Duration) {
+        let position = self.sound.as_ref()
+            .or(self.fading_sound.as_ref())
+            .map(|handle| handle.position());
+        match position {
+            Some(secs) if secs.is_finite() && secs >= 0.0 => self.play_time = std::time::Duration::from_secs_f64(secs),
+            _ => {
+                if self.is_audible() {
+                    self.add_playtime(t);
+                }
+            },
+        }
+    }
+
+    // advances the play time by the wall-clock time `t`, scaled by the playback
+    // rate so that it tracks the position within the sound itself.
+    pub(crate) fn add_playtime(&mut self, t: std::time::Duration) {
+        if let Some(new_duration) = self.play_time.checked_add(t.mul_f64(self.playback_rate)) {
+            self.play_time = new_duration;
+        }
+    }
+}
+
+// the short tween used when changing the volume, playback rate or panning of the
+// playing sound so the change doesn't click or zipper
+pub(crate) fn param_tween() -> Tween {
+    Tween {
+        duration: std::time::Duration::from_millis(PARAM_TWEEN_MS),
+        ..Default::default()
+    }
+}
+
+// names a set of channels for the info pane, going by the layouts it matches and
+// otherwise by how many channels there are
+pub(crate) fn channels_label(channels: symphonia::core::audio::Channels) -> String {
+    use symphonia::core::audio::Layout;
+    if channels == Layout::TwoPointOne.into_channels() {
+        return "2.1".to_string();
+    }
+    if channels == Layout::FivePointOne.into_channels() {
+        return "5.1".to_string();
+    }
+    match channels.count() {
+        1 => "Mono".to_string(),
+        2 => "Stereo".to_string(),
+        n => format!("{} channels", n),
+    }
+}
+
+// what decoding a whole file for its waveform produces
+pub(crate) struct DecodedWaveform {
+    pub(crate) peaks: Vec<(f32, f32)>, // the min/max sample of each stretch of the file
+    pub(crate) sound_data: Option<StaticSoundData>, // the sound ready to play, when kira can play it as decoded
+}
+
+// decodes a whole sound file to find the min/max sample of each stretch of it,
+// mixing the channels together. with `keep_frames` set the decoded sound comes back
+// too, ready to play, when it's mono or stereo and decoded without errors. returns
+// None if the file can't be decoded or the `cancel` flag gets set because the
+// selection moved on. `track_id` picks the audio track of a file with more than one,
+// otherwise it's the one `audio_track` finds.
+pub(crate) fn compute_waveform(snd_path: &Path, cancel: &std::sync::atomic::AtomicBool, keep_frames: bool, track_id: Option<u32>) -> Option<DecodedWaveform> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path).ok()?), Default::default());
+    let mut format_reader = probe
+        .format(
+            &Default::default(),
+            mss,
+            &Default::default(),
+            &Default::default(),
+        )
+        .ok()?
+        .format;
+    let track = match track_id {
+        Some(track_id) => format_reader.tracks().iter().find(|track| track.id == track_id)?,
+        None => audio_track(format_reader.as_ref())?,
+    };
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate;
+    let mut decoder = crate::opus::codecs().make(&track.codec_params, &Default::default()).ok()?;
+
+    // kira only plays mono and stereo, and the frames are given up on otherwise
+    let mut frames = sample_rate.filter(|_| keep_frames).map(|_| vec![]);
+    let mut peaks = vec![];
+    let mut block = (f32::MAX, f32::MIN);
+    let mut block_frames = 0;
+    let mut sample_buf: Option<symphonia::core::audio::SampleBuffer<f32>> = None;
+    loop {
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+            return None;
+        }
+        let packet = match format_reader.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(_) => {
+                // as much of the file as could be read is still drawn, but playing
+                // it loads the file again so the error gets reported
+                frames = None;
+                break;
+            },
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(symphonia::core::errors::Error::DecodeError(_)) => {
+                frames = None;
+                continue;
+            },
+            Err(_) => {
+                frames = None;
+                break;
+            },
+        };
+
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        if !matches!(&sample_buf, Some(buf) if buf.capacity() >= decoded.capacity() * channels) {
+            sample_buf = Some(symphonia::core::audio::SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buf = sample_buf.as_mut()?;
+        buf.copy_interleaved_ref(decoded);
+        match (&mut frames, channels) {
+            (Some(frames), 1) => frames.extend(buf.samples().iter().map(|&s| kira::dsp::Frame::from_mono(s))),
+            (Some(frames), 2) => frames.extend(buf.samples().chunks(2).map(|f| kira::dsp::Frame::new(f[0], f[1]))),
+            _ => frames = None,
+        }
+        for frame in buf.samples().chunks(channels) {
+            for &sample in frame {
+                block = (block.0.min(sample), block.1.max(sample));
+            }
+            block_frames += 1;
+            if block_frames == WAVEFORM_BLOCK_FRAMES {
+                peaks.push(block);
+                block = (f32::MAX, f32::MIN);
+                block_frames = 0;
+            }
+        }
+    }
+    if block_frames > 0 {
+        peaks.push(block);
+    }
+    if peaks.is_empty() {
+        return None;
+    }
+
+    // long files are reduced so every waveform in the cache stays small
+    if peaks.len() > WAVEFORM_RESOLUTION {
+        peaks = (0..WAVEFORM_RESOLUTION)
+            .map(|i| {
+                let start = i * peaks.len() / WAVEFORM_RESOLUTION;
+                let end = (i + 1) * peaks.len() / WAVEFORM_RESOLUTION;
+                peaks[start..end].iter()
+                    .fold((f32::MAX, f32::MIN), |(min, max), &(lo, hi)| (min.min(lo), max.max(hi)))
+            })
+            .collect();
+    }
+    let sound_data = frames.zip(sample_rate).map(|(frames, sample_rate)| StaticSoundData {
+        sample_rate,
+        frames: std::sync::Arc::new(frames),
+        settings: StaticSoundSettings::default(),
+    });
+    Some(DecodedWaveform { peaks, sound_data })
+}
+
+// estimates the bitrate of a track in bits per second from the sizes and lengths of
+// its first few packets, for files whose header doesn't say how long they are
+pub(crate) fn estimate_bitrate(format_reader: &mut dyn symphonia::core::formats::FormatReader, track_id: u32, codec_params: &symphonia::core::codecs::CodecParameters) -> Option<f64> {
+    let mut bytes = 0;
+    let mut ticks = 0;
+    let mut packets = 0;
+    while packets < BITRATE_ESTIMATE_PACKETS {
+        let packet = match format_reader.next_packet() {
+            Ok(packet) => packet,
+            Err(_) => break,
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        bytes += packet.buf().len();
+        ticks += packet.dur;
+        packets += 1;
+    }
+
+    // packet lengths are in the track's time base, which is usually one tick per frame
+    let secs = match (codec_params.time_base, codec_params.sample_rate) {
+        (Some(time_base), _) => {
+            let time = time_base.calc_time(ticks);
+            time.seconds as f64 + time.frac
+        },
+        (None, Some(sample_rate)) => ticks as f64 / sample_rate as f64,
+        (None, None) => return None,
+    };
+    if secs <= 0.0 || bytes == 0 {
+        return None;
+    }
+    Some(bytes as f64 * 8.0 / secs)
+}
+
+// the short name of a codec, like "mp3" or "pcm_s16le"
+fn codec_name(codec: CodecType) -> Option<&'static str> {
+    crate::opus::codecs()
+        .get_codec(codec)
+        .map(|descriptor| descriptor.short_name)
+}
+
+// an audio track of a file, as listed by the track picker
+pub(crate) struct AudioTrackInfo {
+    pub(crate) id: u32,
+    pub(crate) codec: Option<&'static str>,
+    pub(crate) channels: Option<usize>,
+    pub(crate) language: Option<String>, // the language tag, when the container has one
+}
+
+// lists the tracks of a file that hold sound, in the order the container has them
+pub(crate) fn list_audio_tracks(snd_path: &Path) -> Result<Vec<AudioTrackInfo>, SpinupError> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path)?), Default::default());
+    let probed = probe.format(&Default::default(), mss, &Default::default(), &Default::default())?;
+    let tracks = probed.format.tracks().iter()
+        .filter(|track| is_audio_track(track))
+        .map(|track| AudioTrackInfo {
+            id: track.id,
+            codec: codec_name(track.codec_params.codec),
+            channels: track.codec_params.channels
+                .or_else(|| track.codec_params.channel_layout.map(|layout| layout.into_channels()))
+                .map(|channels| channels.count()),
+            language: track.language.clone(),
+        })
+        .collect();
+    Ok(tracks)
+}
+
+// the bits per second of a track from the sizes of all of its packets, which leaves
+// out the tags, cover art and any other tracks in the file
+fn encoded_bitrate(format_reader: &mut dyn FormatReader, track_id: u32, duration: std::time::Duration) -> Option<f64> {
+    let mut bytes = 0;
+    while let Ok(packet) = format_reader.next_packet() {
+        if packet.track_id() == track_id {
+            bytes += packet.buf().len() as u64;
+        }
+    }
+    if bytes == 0 || duration.is_zero() {
+        return None;
+    }
+    Some(bytes as f64 * 8.0 / duration.as_secs_f64())
+}
+
+// whether a track holds sound that can be decoded, rather than video or something
+// there's no decoder for
+fn is_audio_track(track: &Track) -> bool {
+    track.codec_params.codec != CODEC_TYPE_NULL && track.codec_params.sample_rate.is_some()
+}
+
+// the track to play in a file: the default one, unless that's the video of a movie
+// with its sound in a later track
+pub(crate) fn audio_track(format_reader: &dyn FormatReader) -> Option<&Track> {
+    format_reader.default_track()
+        .filter(|track| is_audio_track(track))
+        .or_else(|| format_reader.tracks().iter().find(|track| is_audio_track(track)))
+}
+
+// whether kira can read the sound of a file itself. it only plays the first track,
+// and only with symphonia's own decoders, which leaves out opus.
+fn kira_can_read(snd_path: &Path) -> bool {
+    let probe = symphonia::default::get_probe();
+    let file = match std::fs::File::open(snd_path) {
+        Ok(file) => file,
+        Err(_) => return true,
+    };
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(file), Default::default());
+    match probe.format(&Default::default(), mss, &Default::default(), &Default::default()) {
+        Ok(probed) => probed.format.tracks().first().is_some_and(|track| is_audio_track(track) && track.codec_params.codec != CODEC_TYPE_OPUS),
+        // anything that can't be read is left to kira to report
+        Err(_) => true,
+    }
+}
+
+// returns true if the first `max_bytes` of a file probe as something with a track
+// that can be decoded, going by the content alone and not the extension. mp4
+// files can keep their track list at the end, so those are probed whole.
+pub(crate) fn probe_start_is_audio(snd_path: &Path, max_bytes: usize) -> bool {
+    use std::io::Read;
+    let mut file = match std::fs::File::open(snd_path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+    let mut bytes = Vec::new();
+    if (&mut file).take(max_bytes as u64).read_to_end(&mut bytes).is_err() {
+        return false;
+    }
+    let source: Box<dyn symphonia::core::io::MediaSource> = if bytes.get(4..8) == Some(&b"ftyp"[..]) {
+        use std::io::Seek;
+        if file.seek(std::io::SeekFrom::Start(0)).is_err() {
+            return false;
+        }
+        Box::new(file)
+    } else {
+        Box::new(std::io::Cursor::new(bytes))
+    };
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(source, Default::default());
+    match probe.format(&Default::default(), mss, &Default::default(), &Default::default()) {
+        Ok(probed) => probed.format.tracks().iter().any(is_audio_track),
+        Err(_) => false,
+    }
+}
+
+// reads the duration of a sound file from its header, which not every format has
+pub(crate) fn probe_duration(snd_path: &Path) -> Option<std::time::Duration> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path).ok()?), Default::default());
+    let format_reader = probe
+        .format(
+            &Default::default(),
+            mss,
+            &Default::default(),
+            &Default::default(),
+        )
+        .ok()?
+        .format;
+    let codec_params = &audio_track(format_reader.as_ref())?.codec_params;
+    let n_frames = crate::opus::n_frames(codec_params)?;
+    let sample_rate = codec_params.sample_rate?;
+    Some(std::time::Duration::from_secs_f64(n_frames as f64 / sample_rate as f64))
+}
+
+// reads the first loop from the 'smpl' chunk of a wav, as its first and last frame.
+// files that aren't wavs, and chunks that don't make sense, have no loop.
+pub(crate) fn read_smpl_loop(snd_path: &Path) -> Option<(u32, u32)> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = std::fs::File::open(snd_path).ok()?;
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return None;
+    }
+    loop {
+        let mut chunk_header = [0u8; 8];
+        file.read_exact(&mut chunk_header).ok()?;
+        let size = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]);
+        if &chunk_header[0..4] != b"smpl" {
+            // chunks are padded out to an even length
+            file.seek(SeekFrom::Current(size as i64 + (size & 1) as i64)).ok()?;
+            continue;
+        }
+
+        // the loop count is 28 bytes in, and the start and end of the first loop
+        // are 8 and 12 bytes into it
+        if !(SMPL_HEADER_BYTES + SMPL_LOOP_BYTES..=SMPL_CHUNK_MAX_BYTES).contains(&(size as usize)) {
+            return None;
+        }
+        let mut chunk = vec![0u8; size as usize];
+        file.read_exact(&mut chunk).ok()?;
+        let word = |offset: usize| {
+            chunk.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        if word(28)? == 0 {
+            return None;
+        }
+        let start = word(SMPL_HEADER_BYTES + 8)?;
+        let end = word(SMPL_HEADER_BYTES + 12)?;
+        return (start < end).then_some((start, end));
+    }
+}
+
+// tags can come before the container, like ID3v2 in front of an mp3, or inside
+// it, and the ones inside win when both have a value
+fn read_probed_tags(probed: &mut symphonia::core::probe::ProbeResult, metadata: &mut SoundFileMetadata) {
+    if let Some(outer) = probed.metadata.get() {
+        if let Some(revision) = outer.current() {
+            metadata.read_tags(revision.tags());
+        }
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        metadata.read_tags(revision.tags());
+    }
+}
+
+// reads only the tags of a sound file, for when the rest of its info isn't needed
+pub(crate) fn probe_tags(snd_path: &Path) -> Option<SoundFileMetadata> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path).ok()?), Default::default());
+    let mut probed = probe
+        .format(
+            &Default::default(),
+            mss,
+            &Default::default(),
+            &Default::default(),
+        )
+        .ok()?;
+    let mut metadata = SoundFileMetadata::default();
+    read_probed_tags(&mut probed, &mut metadata);
+    Some(metadata)
+}
+
+// finds the exact length of a file whose header doesn't have it by adding up the
+// lengths of all of its packets, which doesn't need them decoded. returns None if
+// the `cancel` flag gets set because something else started playing.
+pub(crate) fn count_duration(snd_path: &Path, cancel: &std::sync::atomic::AtomicBool) -> Option<std::time::Duration> {
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path).ok()?), Default::default());
+    let mut format_reader = probe
+        .format(
+            &Default::default(),
+            mss,
+            &Default::default(),
+            &Default::default(),
+        )
+        .ok()?
+        .format;
+    let track = audio_track(format_reader.as_ref())?;
+    let track_id = track.id;
+    let time_base = track.codec_params.time_base;
+    let sample_rate = track.codec_params.sample_rate;
+
+    let mut ticks = 0;
+    loop {
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+            return None;
+        }
+        match format_reader.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => ticks += packet.dur,
+            Ok(_) => {},
+            Err(_) => break,
+        }
+    }
+    let secs = match (time_base, sample_rate) {
+        (Some(time_base), _) => {
+            let time = time_base.calc_time(ticks);
+            time.seconds as f64 + time.frac
+        },
+        (None, Some(sample_rate)) => ticks as f64 / sample_rate as f64,
+        (None, None) => return None,
+    };
+    (secs > 0.0).then(|| std::time::Duration::from_secs_f64(secs))
+}
+
+// pulls up the codec data and tags of a sound file for the info pane
+pub(crate) fn probe_file_info(snd_path: &Path) -> Result<(SoundFileCodecData, SoundFileMetadata), SpinupError> {
+    let mut codec_data = SoundFileCodecData::default();
+    let mut metadata = SoundFileMetadata::default();
+
+    let probe = symphonia::default::get_probe();
+    let mss = symphonia::core::io::MediaSourceStream::new(Box::new(std::fs::File::open(snd_path)?), Default::default());
+    let mut probed = probe
+        .format(
+            &Default::default(),
+            mss,
+            &Default::default(),
+            &Default::default(),
+        )?;
+
+    read_probed_tags(&mut probed, &mut metadata);
+
+    let mut format_reader = probed.format;
+    let track = audio_track(format_reader.as_ref()).ok_or(FromFileError::NoDefaultTrack)?;
+    let track_id = track.id;
+    let codec_params = track.codec_params.clone();
+
+    codec_data.sample_rate = codec_params.sample_rate;
+    codec_data.bit_depth = codec_params.bits_per_sample;
+    // plenty of files only have the channels and not a named layout
+    codec_data.channels = codec_params.channels
+        .or_else(|| codec_params.channel_layout.map(|layout| layout.into_channels()));
+    codec_data.codec = codec_name(codec_params.codec);
+    codec_data.codec_kind = CodecKind::of(codec_params.codec);
+    codec_data.sample_loop = read_smpl_loop(snd_path);
+    codec_data.file_size = std::fs::metadata(snd_path).ok().map(|m| m.len());
+
+    // the duration is exact when the header has the number of frames. otherwise
+    // the bitrate of the first packets is used to estimate it from the file size.
+    let file_bits = codec_data.file_size.map(|size| size as f64 * 8.0);
+    if let (Some(n_frames), Some(sample_rate)) = (crate::opus::n_frames(&codec_params), codec_params.sample_rate) {
+        let duration = std::time::Duration::from_secs_f64(n_frames as f64 / sample_rate as f64);
+        codec_data.duration = Some(duration);
+
+        // uncompressed sound has its bitrate in the format, and compressed sound is
+        // measured by what its packets add up to rather than the whole file
+        let channels = codec_data.channels.map(|channels| channels.count() as u32);
+        let bits_per_sec = match (codec_data.codec_kind, codec_params.bits_per_sample, channels) {
+            (Some(CodecKind::Uncompressed), Some(bits), Some(channels)) => Some(sample_rate as f64 * bits as f64 * channels as f64),
+            _ if duration.is_zero() => None,
+            _ if codec_data.file_size.is_some_and(|size| size <= ENCODED_BITRATE_MAX_BYTES) => {
+                encoded_bitrate(format_reader.as_mut(), track_id, duration)
+                    .or_else(|| file_bits.map(|bits| bits / duration.as_secs_f64()))
+            },
+            _ => file_bits.map(|bits| bits / duration.as_secs_f64()),
+        };
+        codec_data.bitrate = bits_per_sec.map(|bits_per_sec| (bits_per_sec / 1000.0).round() as u32);
+    } else if let Some(bits_per_sec) = estimate_bitrate(format_reader.as_mut(), track_id, &codec_params) {
+        codec_data.bitrate = Some((bits_per_sec / 1000.0).round() as u32);
+        if let Some(bits) = file_bits {
+            codec_data.duration = Some(std::time::Duration::from_secs_f64(bits / bits_per_sec));
+            codec_data.duration_estimated = true;
+        }
+    }
+
+    Ok((codec_data, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kira::manager::backend::mock::{MockBackend, MockBackendSettings};
+
+    // an audio manager that only renders when `render` is called, for playing
+    // sounds without a device
+    fn mock_manager() -> AudioManager<MockBackend> {
+        let settings = AudioManagerSettings {
+            backend_settings: MockBackendSettings { sample_rate: 10 },
+            ..AudioManagerSettings::default()
+        };
+        AudioManager::new(settings).unwrap()
+    }
+
+    fn render(manager: &mut AudioManager<MockBackend>, frames: usize) {
+        for _ in 0..frames {
+            manager.backend_mut().on_start_processing();
+            manager.backend_mut().process();
+        }
+    }
+
+    // writes a mono 16 bit aiff at 44.1kHz with `frames` frames of a rising sawtooth
+    fn write_aiff(path: &Path, frames: u32) {
+        let mut comm = vec![];
+        comm.extend(1u16.to_be_bytes());
+        comm.extend(frames.to_be_bytes());
+        comm.extend(16u16.to_be_bytes());
+        // 44100 as an 80 bit extended float
+        comm.extend([0x40, 0x0e, 0xac, 0x44, 0, 0, 0, 0, 0, 0]);
+        let mut ssnd = vec![0u8; 8];
+        for frame in 0..frames {
+            ssnd.extend(((frame % 4096) as i16 * 8).to_be_bytes());
+        }
+        let mut form = b"AIFF".to_vec();
+        for (id, chunk) in [(b"COMM", comm), (b"SSND", ssnd)] {
+            form.extend(id);
+            form.extend((chunk.len() as u32).to_be_bytes());
+            form.extend(chunk);
+        }
+        let mut file = b"FORM".to_vec();
+        file.extend((form.len() as u32).to_be_bytes());
+        file.extend(form);
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn aiff_files_are_listed_probed_and_decoded() {
+        let dir = tempfile::tempdir().unwrap();
+        write_aiff(&dir.path().join("ramp.aif"), 4410);
+        write_aiff(&dir.path().join("Ramp2.AIFF"), 4410);
+        let mut files = crate::fsutil::get_supported_files_in_dir(dir.path(), false, None, true).unwrap();
+        files.sort();
+        assert_eq!(files, [dir.path().join("Ramp2.AIFF"), dir.path().join("ramp.aif")]);
+
+        let (codec_data, _) = probe_file_info(&files[1]).unwrap();
+        assert_eq!(codec_data.sample_rate, Some(44100));
+        assert_eq!(codec_data.bit_depth, Some(16));
+        assert_eq!(codec_data.codec, Some("pcm_s16be"));
+        assert_eq!(codec_data.codec_kind, Some(CodecKind::Uncompressed));
+        // symphonia counts the frames from the size of the sound data chunk, which
+        // includes its 8 byte header, so it comes out 4 frames longer
+        let duration = codec_data.duration.unwrap();
+        assert!(duration.abs_diff(std::time::Duration::from_millis(100)) < std::time::Duration::from_millis(1));
+
+        let sound_data = load_sound_data(&files[1], StaticSoundSettings::default(), None).unwrap();
+        assert_eq!(sound_data.sample_rate, 44100);
+        assert_eq!(sound_data.frames.len(), 4410);
+        assert_eq!(sound_data.frames[1].left, 8.0 / 32768.0);
+    }
+
+    // writes an ogg opus file of `packets` silent 20ms mono packets after a 312 frame pre-skip
+    fn write_opus(path: &Path, packets: u64) {
+        use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+        let mut head = b"OpusHead".to_vec();
+        head.extend([1, 1]);
+        head.extend(312u16.to_le_bytes());
+        head.extend(44100u32.to_le_bytes());
+        head.extend([0, 0, 0]);
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend(4u32.to_le_bytes());
+        tags.extend(b"test");
+        tags.extend(0u32.to_le_bytes());
+        let mut writer = PacketWriter::new(std::fs::File::create(path).unwrap());
+        writer.write_packet(head.into(), 1, PacketWriteEndInfo::EndPage, 0).unwrap();
+        writer.write_packet(tags.into(), 1, PacketWriteEndInfo::EndPage, 0).unwrap();
+        for packet in 1..=packets {
+            // a celt fullband 20ms frame that decodes to silence
+            let end = if packet == packets { PacketWriteEndInfo::EndStream } else { PacketWriteEndInfo::NormalPacket };
+            writer.write_packet(vec![0xf8, 0xff, 0xfe].into(), 1, end, packet * 960).unwrap();
+        }
+    }
+
+    #[test]
+    fn opus_files_are_listed_probed_and_decoded() {
+        let dir = tempfile::tempdir().unwrap();
+        write_opus(&dir.path().join("silence.opus"), 50);
+        let files = crate::fsutil::get_supported_files_in_dir(dir.path(), false, None, true).unwrap();
+        assert_eq!(files, [dir.path().join("silence.opus")]);
+
+        let (codec_data, _) = probe_file_info(&files[0]).unwrap();
+        assert_eq!(codec_data.sample_rate, Some(48000));
+        assert_eq!(codec_data.codec, Some("opus"));
+        // the last granule position is 50 packets of 960 frames, less the pre-skip
+        assert_eq!(codec_data.duration, Some(std::time::Duration::from_secs_f64(47688.0 / 48000.0)));
+        assert_eq!(probe_duration(&files[0]), codec_data.duration);
+
+        let sound_data = load_sound_data(&files[0], StaticSoundSettings::default(), None).unwrap();
+        assert_eq!(sound_data.sample_rate, 48000);
+        assert_eq!(sound_data.frames.len(), 47688);
+        assert!(sound_data.frames.iter().all(|frame| frame.left.abs() < 1e-3));
+    }
+
+    // starts a sound `frames` long at 10 frames a second
+    fn start_sound(manager: &mut AudioManager<MockBackend>, sound_state: &mut SoundState, frames: usize) {
+        let data = StaticSoundData {
+            sample_rate: 10,
+            frames: std::sync::Arc::new(vec![kira::dsp::Frame::from_mono(0.5); frames]),
+            settings: StaticSoundSettings::default(),
+        };
+        let handle = manager.play(data.clone()).unwrap();
+        let duration = data.duration();
+        sound_state.started_sound(SoundHandle::Static(handle), Some(data), duration, std::time::Duration::ZERO, PathBuf::from("test.wav"));
+        render(manager, 1);
+    }
+
+    #[test]
+    fn a_sound_playing_to_its_end_is_released() {
+        let mut manager = mock_manager();
+        let mut sound_state = SoundState::default();
+        start_sound(&mut manager, &mut sound_state, 5);
+        assert!(sound_state.is_playing());
+        assert!(!sound_state.check_finished());
+
+        render(&mut manager, 20);
+        assert!(!sound_state.is_playing());
+        assert!(sound_state.check_finished());
+        assert!(sound_state.sound.is_none());
+        assert!(sound_state.sound_data.is_none());
+        assert!(sound_state.playing_path.is_none());
+        assert_eq!(sound_state.play_time, std::time::Duration::ZERO);
+        assert_eq!(sound_state.play_duration, std::time::Duration::ZERO);
+        assert!(sound_state.show_finished());
+        // it only counts as finished once
+        assert!(!sound_state.check_finished());
+    }
+
+    #[test]
+    fn a_stopped_sound_fades_out_without_finishing() {
+        let mut manager = mock_manager();
+        let mut sound_state = SoundState::default();
+        start_sound(&mut manager, &mut sound_state, 50);
+        sound_state.stop_sound(std::time::Duration::ZERO).unwrap();
+        assert!(!sound_state.is_playing());
+        assert!(sound_state.sound.is_none());
+        assert!(sound_state.fading_sound.is_some());
+
+        render(&mut manager, 5);
+        sound_state.update_fading_sound();
+        assert!(sound_state.fading_sound.is_none());
+        assert!(!sound_state.check_finished());
+        assert!(!sound_state.show_finished());
+        assert_eq!(sound_state.play_time, std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn playing_again_after_finishing_clears_the_indicator() {
+        let mut manager = mock_manager();
+        let mut sound_state = SoundState::default();
+        start_sound(&mut manager, &mut sound_state, 5);
+        render(&mut manager, 20);
+        assert!(sound_state.check_finished());
+        start_sound(&mut manager, &mut sound_state, 50);
+        assert!(sound_state.is_playing());
+        assert!(!sound_state.show_finished());
+    }
+
+    fn secs(secs: f64) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn loop_points_are_put_in_order() {
+        let mut sound_state = SoundState::default();
+        sound_state.set_loop_points(Some(secs(4.0)), Some(secs(1.0))).unwrap();
+        assert_eq!(sound_state.loop_start, Some(secs(1.0)));
+        assert_eq!(sound_state.loop_end, Some(secs(4.0)));
+    }
+
+    #[test]
+    fn loop_points_at_the_same_time_are_rejected() {
+        let mut sound_state = SoundState::default();
+        sound_state.set_loop_points(Some(secs(2.0)), None).unwrap();
+        assert!(sound_state.set_loop_points(Some(secs(2.0)), Some(secs(2.0))).is_err());
+        assert!(sound_state.set_loop_points(Some(secs(2.0)), Some(secs(2.05))).is_err());
+        // the marker that was set already is kept
+        assert_eq!(sound_state.loop_start, Some(secs(2.0)));
+        assert_eq!(sound_state.loop_end, None);
+    }
+
+    #[test]
+    fn loop_end_can_be_set_before_the_start() {
+        let mut sound_state = SoundState::default();
+        sound_state.set_loop_points(None, Some(secs(3.0))).unwrap();
+        assert_eq!(sound_state.loop_end, Some(secs(3.0)));
+        sound_state.set_loop_points(Some(secs(5.0)), Some(secs(3.0))).unwrap();
+        assert_eq!(sound_state.loop_start, Some(secs(3.0)));
+        assert_eq!(sound_state.loop_end, Some(secs(5.0)));
+    }
+}
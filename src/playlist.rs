@@ -0,0 +1,121 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// reading m3u playlists into the files they list, which get played through the
+// queue like any other files, and writing the queue or marked files out as one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::SpinupError;
+
+// the extensions of the playlists that are listed next to the sound files
+pub(crate) const PLAYLIST_EXTS: [&str; 2] = ["m3u", "m3u8"];
+
+// the files a playlist lists, along with the entries that were left out of them
+#[derive(Default)]
+pub(crate) struct Playlist {
+    pub(crate) files: Vec<PathBuf>, // full paths to the files that could be found, in order
+    pub(crate) missing: usize, // entries pointing at files that don't exist
+    pub(crate) urls: usize, // entries that are urls, which can't be played
+}
+
+impl Playlist {
+    // describes what was left out of the playlist, like "3 entries missing", or an
+    // empty string when nothing was
+    pub(crate) fn skipped_label(&self) -> String {
+        let mut skipped = vec![];
+        if self.missing > 0 {
+            skipped.push(format!("{} {} missing", self.missing, if self.missing == 1 { "entry" } else { "entries" }));
+        }
+        if self.urls > 0 {
+            skipped.push(format!("{} {} skipped", self.urls, if self.urls == 1 { "url" } else { "urls" }));
+        }
+        skipped.join(", ")
+    }
+}
+
+// returns true if the file has one of the playlist extensions, ignoring case
+pub(crate) fn is_playlist(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => PLAYLIST_EXTS.iter().any(|playlist_ext| ext.eq_ignore_ascii_case(playlist_ext)),
+        None => false,
+    }
+}
+
+// reads the m3u playlist at `path`. comments and the extended m3u '#EXTINF' lines
+// are skipped, and relative paths are taken to be relative to the playlist's
+// directory. anything that isn't valid UTF-8 is replaced rather than failing the
+// whole playlist.
+pub(crate) fn read_m3u(path: &Path) -> Result<Playlist, SpinupError> {
+    let bytes = fs::read(path)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut playlist = Playlist::default();
+    for line in text.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // streams would need a network client to play
+        if line.contains("://") {
+            playlist.urls += 1;
+            continue;
+        }
+        let file_path = base_dir.join(line);
+        if file_path.is_file() {
+            playlist.files.push(file_path);
+        } else {
+            playlist.missing += 1;
+        }
+    }
+    Ok(playlist)
+}
+
+// writes `files` to `path` as an extended m3u playlist in UTF-8, with the duration of
+// each file where it's known. files are written relative to the playlist's directory
+// when they can be, so the playlist can be moved along with them.
+pub(crate) fn write_m3u(path: &Path, files: &[(PathBuf, Option<std::time::Duration>)]) -> Result<(), SpinupError> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    // the files are full canonical paths, so the directory has to be one too
+    let base_dir = base_dir.canonicalize().unwrap_or_else(|_| base_dir.to_path_buf());
+    let mut text = String::from("#EXTM3U\n");
+    for (file_path, duration) in files {
+        let seconds = duration.map(|d| d.as_secs_f64().round() as i64).unwrap_or(-1);
+        let title = file_path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+        let mut entry = relative_path(&base_dir, file_path)
+            .unwrap_or_else(|| file_path.clone())
+            .display()
+            .to_string();
+        // a name starting with '#' would be read back as a comment
+        if entry.starts_with('#') {
+            entry = format!("./{}", entry);
+        }
+        text.push_str(&format!("#EXTINF:{},{}\n{}\n", seconds, title, entry));
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+// the path to `file_path` from `base_dir`, going up through '..' as far as needed, or
+// None when they don't share a root
+fn relative_path(base_dir: &Path, file_path: &Path) -> Option<PathBuf> {
+    if !base_dir.is_absolute() || !file_path.is_absolute() {
+        return None;
+    }
+    let base: Vec<_> = base_dir.components().collect();
+    let file: Vec<_> = file_path.components().collect();
+    let common = base.iter().zip(file.iter()).take_while(|(a, b)| a == b).count();
+    // only the root is shared, like different drives on windows
+    if common <= 1 {
+        return None;
+    }
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &file[common..] {
+        relative.push(component);
+    }
+    Some(relative)
+}
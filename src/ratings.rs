@@ -0,0 +1,166 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// star ratings for files, kept in one file in the data directory or, with
+// `ratings_in_dirs`, in a file in each directory so they travel with the samples.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::fsutil::app_data_dir;
+
+// the file in the data directory that ratings are saved to, one rated file per line
+pub(crate) const RATINGS_FILE_NAME: &str = "ratings";
+
+// the file in each directory that the ratings of its files are saved to with `ratings_in_dirs`
+pub(crate) const DIR_RATINGS_FILE_NAME: &str = ".spinup-ratings";
+
+// the most stars a file can get
+pub(crate) const MAX_RATING: u8 = 5;
+
+// draws a rating as filled and empty stars, like "★★★☆☆"
+pub(crate) fn stars(rating: u8) -> String {
+    let rating = rating.min(MAX_RATING) as usize;
+    format!("{}{}", "★".repeat(rating), "☆".repeat(MAX_RATING as usize - rating))
+}
+
+// the ratings of files by full path
+#[derive(Default)]
+pub(crate) struct Ratings {
+    pub(crate) in_dirs: bool, // saved next to the files instead of in the data directory
+    ratings: HashMap<PathBuf, u8>,
+    loaded_dirs: HashSet<PathBuf>, // the directories whose ratings file has been read, with `in_dirs`
+}
+
+impl Ratings {
+    // reads the ratings from the data directory. with `in_dirs` they're read from
+    // each directory as its files are listed instead.
+    pub(crate) fn load(in_dirs: bool) -> io::Result<Self> {
+        let mut ratings = Ratings { in_dirs, ..Default::default() };
+        if in_dirs {
+            return Ok(ratings);
+        }
+        let ratings_path = match app_data_dir() {
+            Some(dir) => dir.join(RATINGS_FILE_NAME),
+            None => return Ok(ratings),
+        };
+        ratings.ratings = read_ratings_file(&ratings_path, |path| Some(PathBuf::from(path)))?;
+        Ok(ratings)
+    }
+
+    // reads the ratings file in `dir` the first time a file in it needs its rating,
+    // when they're kept in each directory
+    pub(crate) fn load_dir(&mut self, dir: &Path) -> io::Result<()> {
+        if !self.in_dirs || self.loaded_dirs.contains(dir) {
+            return Ok(());
+        }
+        self.loaded_dirs.insert(dir.to_path_buf());
+        // only plain file names are taken, so a ratings file can't rate files elsewhere
+        let dir_ratings = read_ratings_file(&dir.join(DIR_RATINGS_FILE_NAME), |name| {
+            let name = Path::new(name);
+            match name.file_name() {
+                Some(file_name) if file_name == name.as_os_str() => Some(dir.join(name)),
+                _ => None,
+            }
+        })?;
+        self.ratings.extend(dir_ratings);
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, path: &Path) -> Option<u8> {
+        self.ratings.get(path).copied()
+    }
+
+    // rates the file at `path`, or takes its rating away with None, and saves the ratings
+    pub(crate) fn set(&mut self, path: &Path, rating: Option<u8>) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            self.load_dir(dir)?;
+        }
+        if let Some(rating) = rating {
+            self.ratings.insert(path.to_path_buf(), rating.min(MAX_RATING));
+        } else {
+            self.ratings.remove(path);
+        }
+        self.save(path)
+    }
+
+    // carries the rating of a file that was moved over to where it went
+    pub(crate) fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        for dir in [from.parent(), to.parent()].into_iter().flatten() {
+            self.load_dir(dir)?;
+        }
+        let rating = match self.ratings.remove(from) {
+            Some(rating) => rating,
+            None => return Ok(()),
+        };
+        self.ratings.insert(to.to_path_buf(), rating);
+        self.save(from)?;
+        self.save(to)
+    }
+
+    // drops the ratings of the files in `dir` that are gone, so ratings don't pile up
+    // for files deleted or renamed outside of spinup
+    pub(crate) fn forget_missing_in(&mut self, dir: &Path) -> io::Result<()> {
+        let missing: Vec<PathBuf> = self.ratings.keys()
+            .filter(|path| path.parent() == Some(dir) && !path.exists())
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        for path in &missing {
+            self.ratings.remove(path);
+        }
+        self.save(&missing[0])
+    }
+
+    // writes out the ratings file that the rating of `path` belongs in
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if self.in_dirs {
+            let dir = match path.parent() {
+                Some(dir) => dir,
+                None => return Ok(()),
+            };
+            let text: String = self.ratings.iter()
+                .filter(|(rated, _)| rated.parent() == Some(dir))
+                .filter_map(|(rated, rating)| Some(format!("{}\t{}\n", rating, rated.file_name()?.to_string_lossy())))
+                .collect();
+            let ratings_path = dir.join(DIR_RATINGS_FILE_NAME);
+            // a directory that had its last rating taken away doesn't keep an empty file
+            if text.is_empty() {
+                return match fs::remove_file(ratings_path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                };
+            }
+            return fs::write(ratings_path, text);
+        }
+        let dir = app_data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "couldn't find a data directory"))?;
+        fs::create_dir_all(&dir)?;
+        let text: String = self.ratings.iter()
+            .map(|(rated, rating)| format!("{}\t{}\n", rating, rated.display()))
+            .collect();
+        fs::write(dir.join(RATINGS_FILE_NAME), text)
+    }
+}
+
+// reads a ratings file, where each line is the rating and then the file, which
+// `to_path` turns into a full path. a line that doesn't make sense is skipped.
+fn read_ratings_file(ratings_path: &Path, to_path: impl Fn(&str) -> Option<PathBuf>) -> io::Result<HashMap<PathBuf, u8>> {
+    let text = match fs::read_to_string(ratings_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    let parse_line = |line: &str| -> Option<(PathBuf, u8)> {
+        let (rating, file) = line.split_once('\t')?;
+        let rating: u8 = rating.parse().ok()?;
+        if rating == 0 || rating > MAX_RATING {
+            return None;
+        }
+        Some((to_path(file)?, rating))
+    };
+    Ok(text.lines().filter_map(parse_line).collect())
+}
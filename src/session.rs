@@ -0,0 +1,193 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// remembering where spinup was left between runs: the directory, the selected
+// entry, the sort order, the volume and the toggles. it's saved when quitting and,
+// as well as it can be, when spinup panics.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::app::{AdvanceMode, AppState, EntryKind, InfoPaneMode, SortMode};
+use crate::theme::ThemeKind;
+
+// the file in the state directory the session is saved to
+pub(crate) const SESSION_FILE_NAME: &str = "session.toml";
+
+// the last session taken while running, for the panic hook to save since it can't
+// get to the app state itself
+static PANIC_SESSION: Mutex<Option<String>> = Mutex::new(None);
+
+// returns the directory the session is saved in, following the XDG base directory
+// spec and falling back to ~/.local/state
+pub(crate) fn app_state_dir() -> Option<PathBuf> {
+    let state_home = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(&std::env::var_os("HOME")?).join(".local").join("state"),
+    };
+    Some(state_home.join("spinup"))
+}
+
+// what's saved of a run
+pub(crate) struct Session {
+    pub(crate) dir: PathBuf,
+    pub(crate) selected: Option<(EntryKind, OsString)>,
+    pub(crate) sort_mode: SortMode,
+    pub(crate) volume: f64,
+    pub(crate) muted: bool,
+    pub(crate) show_hidden: bool,
+    pub(crate) recursive: bool,
+    pub(crate) advance_mode: AdvanceMode,
+    pub(crate) shuffle: bool,
+    pub(crate) preview: bool,
+    pub(crate) mono_check: bool,
+    pub(crate) replay_gain: bool,
+    pub(crate) info_pane_mode: InfoPaneMode,
+    pub(crate) theme_kind: ThemeKind,
+}
+
+impl Session {
+    pub(crate) fn capture(app_state: &AppState) -> Self {
+        Session {
+            dir: app_state.current_directory_path.clone(),
+            selected: app_state.selected_entry_key(),
+            sort_mode: app_state.sort_mode,
+            volume: app_state.sound_state.volume,
+            muted: app_state.sound_state.muted,
+            show_hidden: app_state.show_hidden,
+            recursive: app_state.recursive,
+            advance_mode: app_state.advance_mode,
+            shuffle: app_state.shuffle,
+            preview: app_state.sound_state.preview,
+            mono_check: app_state.sound_state.mono_check,
+            replay_gain: app_state.sound_state.replay_gain,
+            info_pane_mode: app_state.info_pane_mode,
+            theme_kind: app_state.theme_kind,
+        }
+    }
+
+    // sets everything but the directory and the selection, which have to wait until
+    // the directory is listed
+    pub(crate) fn apply_toggles(&self, app_state: &mut AppState) {
+        app_state.sort_mode = self.sort_mode;
+        app_state.sound_state.volume = self.volume;
+        app_state.sound_state.muted = self.muted;
+        app_state.show_hidden = self.show_hidden;
+        app_state.recursive = self.recursive;
+        app_state.advance_mode = self.advance_mode;
+        app_state.shuffle = self.shuffle;
+        app_state.sound_state.preview = self.preview;
+        app_state.sound_state.mono_check = self.mono_check;
+        app_state.sound_state.replay_gain = self.replay_gain;
+        app_state.info_pane_mode = self.info_pane_mode;
+        app_state.theme_kind = self.theme_kind;
+    }
+
+    pub(crate) fn to_text(&self) -> String {
+        let mut table = toml::map::Map::new();
+        table.insert("dir".to_string(), toml::Value::String(self.dir.display().to_string()));
+        if let Some((kind, name)) = &self.selected {
+            let kind = match kind {
+                EntryKind::ParentDir => "parent",
+                EntryKind::Dir => "dir",
+                EntryKind::Playlist => "playlist",
+                EntryKind::File => "file",
+            };
+            table.insert("selected_kind".to_string(), toml::Value::String(kind.to_string()));
+            table.insert("selected".to_string(), toml::Value::String(name.to_string_lossy().into_owned()));
+        }
+        table.insert("sort".to_string(), toml::Value::String(self.sort_mode.name().to_string()));
+        table.insert("volume".to_string(), toml::Value::Float(self.volume));
+        table.insert("muted".to_string(), toml::Value::Boolean(self.muted));
+        table.insert("show_hidden".to_string(), toml::Value::Boolean(self.show_hidden));
+        table.insert("recursive".to_string(), toml::Value::Boolean(self.recursive));
+        table.insert("advance".to_string(), toml::Value::String(self.advance_mode.name().to_string()));
+        table.insert("shuffle".to_string(), toml::Value::Boolean(self.shuffle));
+        table.insert("preview".to_string(), toml::Value::Boolean(self.preview));
+        table.insert("mono_check".to_string(), toml::Value::Boolean(self.mono_check));
+        table.insert("replay_gain".to_string(), toml::Value::Boolean(self.replay_gain));
+        table.insert("info_pane".to_string(), toml::Value::String(self.info_pane_mode.name().to_string()));
+        table.insert("theme".to_string(), toml::Value::String(self.theme_kind.name().to_string()));
+        toml::Value::Table(table).to_string()
+    }
+
+    // reads a saved session. anything missing or unreadable is left at its default,
+    // since the file is only ever written by spinup and a newer or older one may
+    // not have all of it.
+    pub(crate) fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let table = text.parse::<toml::Value>()?;
+        let string = |key: &str| table.get(key).and_then(|value| value.as_str());
+        let boolean = |key: &str| table.get(key).and_then(|value| value.as_bool()).unwrap_or(false);
+        let dir = string("dir").ok_or("the session doesn't have a directory")?;
+        let selected_kind = match string("selected_kind") {
+            Some("parent") => Some(EntryKind::ParentDir),
+            Some("dir") => Some(EntryKind::Dir),
+            Some("playlist") => Some(EntryKind::Playlist),
+            Some("file") => Some(EntryKind::File),
+            _ => None,
+        };
+        Ok(Session {
+            dir: PathBuf::from(dir),
+            selected: selected_kind.zip(string("selected").map(OsString::from)),
+            sort_mode: string("sort").and_then(SortMode::from_name).unwrap_or_default(),
+            volume: table.get("volume").and_then(|value| value.as_float()).unwrap_or(1.0),
+            muted: boolean("muted"),
+            show_hidden: boolean("show_hidden"),
+            recursive: boolean("recursive"),
+            advance_mode: string("advance").and_then(AdvanceMode::from_name).unwrap_or_default(),
+            shuffle: boolean("shuffle"),
+            preview: boolean("preview"),
+            mono_check: boolean("mono_check"),
+            replay_gain: boolean("replay_gain"),
+            info_pane_mode: string("info_pane").and_then(InfoPaneMode::from_name).unwrap_or_default(),
+            theme_kind: string("theme").and_then(|name| name.parse().ok()).unwrap_or_default(),
+        })
+    }
+}
+
+// reads the session saved by the last run, returning None if there isn't one
+pub(crate) fn load_session() -> Result<Option<Session>, Box<dyn Error>> {
+    let session_path = match app_state_dir() {
+        Some(dir) => dir.join(SESSION_FILE_NAME),
+        None => return Ok(None),
+    };
+    match fs::read_to_string(&session_path) {
+        Ok(text) => Session::parse(&text)
+            .map(Some)
+            .map_err(|err| format!("{}: {}", session_path.display(), err).into()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("{}: {}", session_path.display(), err).into()),
+    }
+}
+
+pub(crate) fn save_session_text(text: &str) -> io::Result<()> {
+    let dir = app_state_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "couldn't find a state directory"))?;
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(SESSION_FILE_NAME), text)
+}
+
+// keeps the session for the panic hook to save if spinup panics before quitting
+pub(crate) fn remember_session(app_state: &AppState) {
+    if let Ok(mut session) = PANIC_SESSION.lock() {
+        *session = Some(Session::capture(app_state).to_text());
+    }
+}
+
+// saves the last remembered session when spinup panics, before the panic is
+// reported the usual way
+pub(crate) fn install_panic_save() {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // the lock may be what panicked, so this doesn't wait on it
+        if let Ok(session) = PANIC_SESSION.try_lock() {
+            if let Some(text) = session.as_deref() {
+                _ = save_session_text(text);
+            }
+        }
+        previous_hook(info);
+    }));
+}
@@ -0,0 +1,175 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the color schemes the interface can be drawn with. everything `ui()` draws
+// takes its style from a Theme so none of the colors are hardcoded there.
+
+use tui::style::{Color, Modifier, Style};
+
+/// The built-in themes, selectable with `--theme` and cycled at runtime.
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum ThemeKind {
+    #[default]
+    Dark,
+    Light,
+    Monochrome,
+}
+
+impl ThemeKind {
+    /// Every built-in theme in the order they're cycled through.
+    pub const ALL: [ThemeKind; 3] = [ThemeKind::Dark, ThemeKind::Light, ThemeKind::Monochrome];
+
+    /// The theme after this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            ThemeKind::Dark => ThemeKind::Light,
+            ThemeKind::Light => ThemeKind::Monochrome,
+            ThemeKind::Monochrome => ThemeKind::Dark,
+        }
+    }
+
+    /// The name the theme goes by on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemeKind::Dark => "dark",
+            ThemeKind::Light => "light",
+            ThemeKind::Monochrome => "monochrome",
+        }
+    }
+
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeKind::Dark => Theme::dark(),
+            ThemeKind::Light => Theme::light(),
+            ThemeKind::Monochrome => Theme::monochrome(),
+        }
+    }
+}
+
+impl std::str::FromStr for ThemeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ThemeKind::ALL.iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("unknown theme '{}', expected dark, light or monochrome", s))
+    }
+}
+
+/// The styles for every part of the interface.
+pub struct Theme {
+    /// Cleared for themes that only use modifiers, where the progress gauge has
+    /// to be drawn with line characters since it's otherwise filled in with color.
+    pub uses_color: bool,
+    /// The highlighted row of a list.
+    pub selection: Style,
+    pub directory: Style,
+    /// The m3u playlists listed next to the sound files.
+    pub playlist: Style,
+    pub hidden: Style,
+    pub marked: Style,
+    /// Secondary text like the list columns and placeholders.
+    pub dim: Style,
+    pub error: Style,
+    /// The title bar and the playback status on it.
+    pub title: Style,
+    /// Prompts, text being typed and key names in the help.
+    pub prompt: Style,
+    /// Text that matched a filter or fuzzy find query.
+    pub filter_match: Style,
+    /// The now playing label and the finished indicator.
+    pub playing: Style,
+    pub gauge: Style,
+    /// The gauge while an A-B loop region is set.
+    pub loop_gauge: Style,
+    pub waveform: Style,
+    pub playhead: Style,
+    /// The level meter below the last 6 dB, in the last 6 dB, and when clipping.
+    pub level_low: Style,
+    pub level_high: Style,
+    pub level_clip: Style,
+    /// The codec of a file that was lossy compressed.
+    pub lossy: Style,
+}
+
+impl Theme {
+    /// The original colors, made for terminals with a dark background.
+    pub fn dark() -> Self {
+        Theme {
+            uses_color: true,
+            selection: Style::default().bg(Color::LightGreen).add_modifier(Modifier::BOLD),
+            directory: Style::default().fg(Color::Blue),
+            playlist: Style::default().fg(Color::Cyan),
+            hidden: Style::default().fg(Color::DarkGray),
+            marked: Style::default().fg(Color::Magenta),
+            dim: Style::default().fg(Color::DarkGray),
+            error: Style::default().fg(Color::Red),
+            title: Style::default().add_modifier(Modifier::BOLD),
+            prompt: Style::default().fg(Color::Yellow),
+            filter_match: Style::default().fg(Color::Yellow).add_modifier(Modifier::UNDERLINED),
+            playing: Style::default().fg(Color::LightGreen),
+            gauge: Style::default().fg(Color::LightGreen).bg(Color::Black),
+            loop_gauge: Style::default().fg(Color::LightYellow).bg(Color::Black),
+            waveform: Style::default().fg(Color::LightGreen),
+            playhead: Style::default().fg(Color::Black).bg(Color::LightYellow),
+            level_low: Style::default().fg(Color::LightGreen),
+            level_high: Style::default().fg(Color::Yellow),
+            level_clip: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            lossy: Style::default().fg(Color::Yellow),
+        }
+    }
+
+    /// Darker colors that stay readable on a light background.
+    pub fn light() -> Self {
+        Theme {
+            uses_color: true,
+            selection: Style::default().fg(Color::White).bg(Color::Blue).add_modifier(Modifier::BOLD),
+            directory: Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            playlist: Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            hidden: Style::default().fg(Color::Gray),
+            marked: Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            dim: Style::default().fg(Color::Gray),
+            error: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            title: Style::default().add_modifier(Modifier::BOLD),
+            prompt: Style::default().fg(Color::Magenta),
+            filter_match: Style::default().fg(Color::Red).add_modifier(Modifier::UNDERLINED),
+            playing: Style::default().fg(Color::Green),
+            gauge: Style::default().fg(Color::Green).bg(Color::White),
+            loop_gauge: Style::default().fg(Color::Magenta).bg(Color::White),
+            waveform: Style::default().fg(Color::Green),
+            playhead: Style::default().fg(Color::White).bg(Color::Red),
+            level_low: Style::default().fg(Color::Green),
+            level_high: Style::default().fg(Color::Yellow),
+            level_clip: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            lossy: Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// No colors at all, only bold, underlined and reversed text, for terminals
+    /// that can't show colors.
+    pub fn monochrome() -> Self {
+        Theme {
+            uses_color: false,
+            selection: Style::default().add_modifier(Modifier::REVERSED),
+            directory: Style::default().add_modifier(Modifier::BOLD),
+            playlist: Style::default().add_modifier(Modifier::ITALIC),
+            hidden: Style::default().add_modifier(Modifier::DIM),
+            marked: Style::default().add_modifier(Modifier::BOLD | Modifier::ITALIC),
+            dim: Style::default().add_modifier(Modifier::DIM),
+            error: Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            title: Style::default().add_modifier(Modifier::BOLD),
+            prompt: Style::default().add_modifier(Modifier::BOLD),
+            filter_match: Style::default().add_modifier(Modifier::UNDERLINED),
+            playing: Style::default().add_modifier(Modifier::BOLD),
+            gauge: Style::default().add_modifier(Modifier::BOLD),
+            loop_gauge: Style::default().add_modifier(Modifier::BOLD | Modifier::ITALIC),
+            waveform: Style::default(),
+            playhead: Style::default().add_modifier(Modifier::REVERSED),
+            level_low: Style::default(),
+            level_high: Style::default().add_modifier(Modifier::BOLD),
+            level_clip: Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            lossy: Style::default().add_modifier(Modifier::ITALIC),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// the directory tree shown to the left of the file list when the terminal is wide
+// enough. a directory's subdirectories are only read the first time it's expanded,
+// so a big library opens as quickly as a small one.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use tui::widgets::ListState;
+
+use crate::fsutil::get_directories_in_dir;
+
+// a directory showing in the tree
+pub(crate) struct TreeRow {
+    pub(crate) path: PathBuf,
+    pub(crate) depth: usize, // how far below the root it is, with the root at 0
+    pub(crate) expanded: bool,
+    pub(crate) is_parent: bool, // the '..' row above the root, for going further up
+}
+
+impl TreeRow {
+    // the name shown for the row: the whole path for the root and the last part for
+    // everything below it
+    pub(crate) fn label(&self) -> String {
+        if self.is_parent {
+            return "..".to_string();
+        }
+        if self.depth == 0 {
+            return self.path.display().to_string();
+        }
+        self.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct DirTree {
+    pub(crate) root: PathBuf,
+    pub(crate) rows: Vec<TreeRow>, // the directories showing, in the order they're drawn
+    pub(crate) list_state: ListState, // the selection is an index into `rows`
+    pub(crate) offset: usize, // the index into `rows` of the top row drawn
+    pub(crate) shown_dir: PathBuf, // the directory the tree was last moved to, which the file list shows
+    pub(crate) show_hidden: bool, // when set, directories starting with a '.' are shown too
+    subdirs: HashMap<PathBuf, Vec<PathBuf>>, // the sorted subdirectories of each directory read so far
+}
+
+impl DirTree {
+    pub(crate) fn new(root: &Path, show_hidden: bool) -> Self {
+        let mut tree = DirTree { show_hidden, ..Default::default() };
+        tree.set_root(root);
+        tree
+    }
+
+    // starts the tree over at `root`, expanded, with the root selected
+    pub(crate) fn set_root(&mut self, root: &Path) {
+        self.root = root.to_path_buf();
+        self.shown_dir = root.to_path_buf();
+        self.rows.clear();
+        if let Some(parent) = root.parent() {
+            self.rows.push(TreeRow { path: parent.to_path_buf(), depth: 0, expanded: false, is_parent: true });
+        }
+        self.rows.push(TreeRow { path: root.to_path_buf(), depth: 0, expanded: false, is_parent: false });
+        let root_row = self.rows.len() - 1;
+        self.expand(root_row);
+        self.list_state.select(Some(root_row));
+    }
+
+    // forgets the directories read so far, so they're read again as they're shown,
+    // keeping the same directories expanded where they still exist
+    pub(crate) fn reload(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+        self.subdirs.clear();
+        let expanded: Vec<PathBuf> = self.rows.iter()
+            .filter(|row| row.expanded)
+            .map(|row| row.path.clone())
+            .collect();
+        let shown = self.shown_dir.clone();
+        let root = self.root.clone();
+        self.set_root(&root);
+        for dir in expanded {
+            if let Some(row) = self.rows.iter().position(|row| !row.is_parent && row.path == dir) {
+                if !self.rows[row].expanded {
+                    self.expand(row);
+                }
+            }
+        }
+        self.reveal(&shown);
+    }
+
+    // the selected row, unless it's the '..' row, which isn't listed until it's entered
+    pub(crate) fn selected_dir(&self) -> Option<&Path> {
+        let row = self.rows.get(self.list_state.selected()?)?;
+        if row.is_parent { None } else { Some(row.path.as_path()) }
+    }
+
+    // moves the selection by `delta` rows, stopping at either end
+    pub(crate) fn move_selection(&mut self, delta: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let selected = self.list_state.selected().unwrap_or(0) as isize;
+        let row = selected.saturating_add(delta).clamp(0, self.rows.len() as isize - 1);
+        self.list_state.select(Some(row as usize));
+    }
+
+    // expands the selected directory or collapses it if it's expanded. on the '..'
+    // row the tree starts over a directory further up instead, still showing `current`.
+    pub(crate) fn toggle_selected(&mut self, current: &Path) {
+        let row = match self.list_state.selected() {
+            Some(row) if row < self.rows.len() => row,
+            _ => return,
+        };
+        if self.rows[row].is_parent {
+            let parent = self.rows[row].path.clone();
+            self.set_root(&parent);
+            self.reveal(current);
+        } else if self.rows[row].expanded {
+            self.collapse(row);
+        } else {
+            self.expand(row);
+        }
+    }
+
+    // expands the directories down to `dir` and selects it, starting the tree over
+    // at `dir` if it isn't under the root
+    pub(crate) fn reveal(&mut self, dir: &Path) {
+        if !dir.starts_with(&self.root) {
+            self.set_root(dir);
+            return;
+        }
+        self.shown_dir = dir.to_path_buf();
+        let mut ancestors: Vec<&Path> = dir.ancestors().take_while(|a| a.starts_with(&self.root)).collect();
+        ancestors.reverse();
+        let mut found = None;
+        for ancestor in ancestors {
+            let row = match self.rows.iter().position(|row| !row.is_parent && row.path == ancestor) {
+                Some(row) => row,
+                // a directory that isn't there any more, or is hidden
+                None => break,
+            };
+            found = Some(row);
+            if ancestor != dir && !self.rows[row].expanded {
+                self.expand(row);
+            }
+        }
+        if let Some(row) = found {
+            self.list_state.select(Some(row));
+        }
+    }
+
+    fn expand(&mut self, row: usize) {
+        let dir = self.rows[row].path.clone();
+        let depth = self.rows[row].depth;
+        let children: Vec<TreeRow> = self.subdirs_of(&dir).iter()
+            .map(|path| TreeRow { path: path.clone(), depth: depth + 1, expanded: false, is_parent: false })
+            .collect();
+        self.rows[row].expanded = true;
+        self.rows.splice(row + 1..row + 1, children);
+    }
+
+    fn collapse(&mut self, row: usize) {
+        let depth = self.rows[row].depth;
+        let end = self.rows[row + 1..].iter()
+            .position(|r| r.depth <= depth)
+            .map_or(self.rows.len(), |offset| row + 1 + offset);
+        self.rows.drain(row + 1..end);
+        self.rows[row].expanded = false;
+    }
+
+    // the subdirectories of `dir`, read the first time they're needed. a directory
+    // that can't be read has none.
+    fn subdirs_of(&mut self, dir: &Path) -> &[PathBuf] {
+        let show_hidden = self.show_hidden;
+        self.subdirs.entry(dir.to_path_buf()).or_insert_with(|| {
+            let mut subdirs = get_directories_in_dir(dir, show_hidden).unwrap_or_default();
+            subdirs.sort_by_key(|path| path.file_name().map(|n| n.to_string_lossy().to_lowercase()));
+            subdirs
+        })
+    }
+}
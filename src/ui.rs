@@ -0,0 +1,1224 @@
+// Copyright 2022 by Timothy Bogdala <tdb@animal-machine.com
+// Source code is released under the GPL v3 license or greater, see 'LICENSE' for more details.
+
+// draws the interface from the AppState with tui. nothing in here changes
+// anything but the bits of layout that input handling needs to know about.
+
+use tui::layout::Rect;
+use tui::style::Style;
+use tui::text::{Span, Spans};
+use tui::widgets::{Borders, Block, Clear, Gauge, LineGauge, List, ListItem, ListState, Paragraph};
+
+use crate::keymap;
+use crate::loudness::format_db;
+use crate::theme;
+use crate::app::{AdvanceMode, AppState, BrowserEntry, EntryKind, Focus, InfoPaneMode, Prompt, find_case_insensitive};
+use crate::player::{ChannelLevel, CodecKind, channels_label};
+use crate::ratings::stars;
+
+// this is the prefix used in the listitems for directories
+pub(crate) const DIR_LISTITEM_PREFIX: &str = "<DIR> ";
+
+// the most results the fuzzy finder lists at once
+pub(crate) const FINDER_MAX_RESULTS: usize = 200;
+
+// shown in front of the names of marked files
+pub(crate) const MARK_PREFIX: &str = "* ";
+
+// shown in front of the names of files whose content didn't probe as audio
+pub(crate) const PROBE_FAILED_PREFIX: &str = "⚠ ";
+
+// the narrowest the file list gets before the info pane is hidden to make room
+pub(crate) const MIN_FILE_LIST_WIDTH: u16 = 30;
+
+// below this size only a note that the terminal is too small gets drawn
+pub(crate) const MIN_FRAME_WIDTH: u16 = 20;
+
+pub(crate) const MIN_FRAME_HEIGHT: u16 = 6;
+
+// on terminals shorter than this the title bar and info pane are dropped to leave
+// the rows to the list
+pub(crate) const MIN_HEIGHT_FOR_TITLE: u16 = 10;
+
+// how wide the terminal has to be for the directory tree to go beside the file
+// list, and how wide the tree gets: a quarter of the width, within these limits
+pub(crate) const TREE_LAYOUT_MIN_WIDTH: u16 = 100;
+
+pub(crate) const TREE_PANE_MIN_WIDTH: u16 = 24;
+
+pub(crate) const TREE_PANE_MAX_WIDTH: u16 = 40;
+
+// what goes in front of a directory in the tree when it's expanded or collapsed
+pub(crate) const TREE_EXPANDED_PREFIX: &str = "▾ ";
+
+pub(crate) const TREE_COLLAPSED_PREFIX: &str = "▸ ";
+
+// the range of widths the info pane can be resized to, how wide it starts out and
+// how much each key press resizes it by
+pub(crate) const MIN_INFO_PANE_WIDTH: u16 = 20;
+
+pub(crate) const MAX_INFO_PANE_WIDTH: u16 = 50;
+
+pub(crate) const DEFAULT_INFO_PANE_WIDTH: u16 = 25;
+
+pub(crate) const INFO_PANE_WIDTH_STEP: u16 = 2;
+
+// the height of the waveform pane, borders included, and how tall the file list
+// has to be before it gives up the room for it
+pub(crate) const WAVEFORM_PANE_HEIGHT: u16 = 6;
+
+pub(crate) const MIN_LIST_HEIGHT_FOR_WAVEFORM: u16 = 16;
+
+// how much of the sound just played the level meter measures
+pub(crate) const LEVEL_METER_WINDOW: std::time::Duration = std::time::Duration::from_millis(66);
+
+// the quietest level the meter shows, in dBFS, and where it puts tick marks
+pub(crate) const LEVEL_METER_MIN_DB: f32 = -60.0;
+
+pub(crate) const LEVEL_METER_TICKS_DB: [f32; 5] = [-48.0, -36.0, -24.0, -12.0, -6.0];
+
+// the narrowest the names in the file list get before the columns after them are dropped
+pub(crate) const MIN_NAME_COLUMN_WIDTH: usize = 20;
+
+// the narrowest the progress gauge gets before the time next to it is dropped
+pub(crate) const MIN_GAUGE_WIDTH: u16 = 10;
+
+pub(crate) fn ui<B: tui::backend::Backend>(app_state: &mut AppState, f: &mut tui::Frame<B>) {
+    let whole_frame = f.size();
+    let theme = app_state.theme_kind.theme();
+
+    // only set again if the gauge gets drawn, for mouse clicks to seek with
+    app_state.gauge_area = None;
+
+    // a terminal too small to lay anything out in only gets told so, with nothing
+    // on screen to click
+    if whole_frame.width < MIN_FRAME_WIDTH || whole_frame.height < MIN_FRAME_HEIGHT {
+        app_state.list_area = Rect::default();
+        app_state.tree_area = Rect::default();
+        if whole_frame.area() > 0 {
+            let too_small = Paragraph::new(format!("terminal too small (need ≥ {}x{})", MIN_FRAME_WIDTH, MIN_FRAME_HEIGHT))
+                .wrap(tui::widgets::Wrap {trim: true})
+                .style(theme.error);
+            f.render_widget(too_small, whole_frame);
+        }
+        return;
+    }
+    let show_title = whole_frame.height >= MIN_HEIGHT_FOR_TITLE;
+    let list_y = if show_title { 1 } else { 0 };
+
+    // file list by default takes up the whole width and the info pane disabled
+    let mut file_list_width = whole_frame.width;
+
+    // the directory tree takes the left side when the terminal is wide enough for it
+    let tree_width = if app_state.tree_shown {
+        (whole_frame.width / 4).clamp(TREE_PANE_MIN_WIDTH, TREE_PANE_MAX_WIDTH).min(whole_frame.width.saturating_sub(MIN_FILE_LIST_WIDTH))
+    } else {
+        0
+    };
+    file_list_width -= tree_width;
+
+    // decide if we're going to show the info pane -- by default it shows for files,
+    // but it can be forced on or off. either way it's hidden when it would squeeze
+    // the file list below its minimum width.
+    let info_pane_width = app_state.info_pane_width;
+    let show_info_pane = match app_state.info_pane_mode {
+        InfoPaneMode::Auto => app_state.is_file_selected(),
+        InfoPaneMode::On => true,
+        InfoPaneMode::Off => false,
+    } && show_title && file_list_width >= info_pane_width + MIN_FILE_LIST_WIDTH;
+    if show_info_pane {
+        file_list_width -= info_pane_width;
+    }
+
+    let mut chunks: Vec<Rect> = vec![
+        // top menu line, only drawn when `show_title` is set
+        Rect {x: 0, y: 0, width:whole_frame.width, height: 1},
+
+        // main file list
+        Rect {x: tree_width, y: list_y, width: file_list_width, height: whole_frame.height - list_y - 1},
+
+        // error message / progress bar
+        Rect {x: 0, y: whole_frame.height - 1, width: whole_frame.width, height: 1},
+    ];
+
+    // the now playing line takes the bottom row of the list while something plays,
+    // so it stays visible whatever directory is being browsed
+    let mut now_playing_area = None;
+    if app_state.sound_state.playing_path.is_some() && chunks[1].height > 3 {
+        chunks[1].height -= 1;
+        now_playing_area = Some(Rect {x: 0, y: chunks[1].y + chunks[1].height, width: whole_frame.width, height: 1});
+    }
+
+    // the level meter takes a row per channel above the now playing line
+    let mut meter_area = None;
+    let mut levels = vec![];
+    if app_state.sound_state.is_playing() {
+        levels = app_state.sound_state.channel_levels(LEVEL_METER_WINDOW);
+    }
+    let meter_rows = levels.len() as u16;
+    if meter_rows > 0 && chunks[1].height > 3 + meter_rows {
+        chunks[1].height -= meter_rows;
+        meter_area = Some(Rect {x: 0, y: chunks[1].y + chunks[1].height, width: whole_frame.width, height: meter_rows});
+    }
+
+    // the waveform of the selected file goes under the list when there's room for it
+    let mut waveform_area = None;
+    if app_state.is_file_selected() && chunks[1].height >= MIN_LIST_HEIGHT_FOR_WAVEFORM {
+        chunks[1].height -= WAVEFORM_PANE_HEIGHT;
+        waveform_area = Some(Rect {x: 0, y: chunks[1].y + chunks[1].height, width: whole_frame.width, height: WAVEFORM_PANE_HEIGHT});
+    }
+
+    // the 4th chunk will be present if the info pane is used. it grows past its usual
+    // height when the tags of the file need more room.
+    let mut info_text = vec![];
+    if app_state.info_pending.is_some() {
+        info_text.push(Spans::from(Span::styled("probing…", theme.dim)));
+    }
+    if let Some(codec) = app_state.select_file_info.codec {
+        // lossy files stand out when picking which copy of a sound to keep
+        match app_state.select_file_info.codec_kind {
+            Some(kind) => {
+                let style = if kind == CodecKind::Lossy { theme.lossy } else { Style::default() };
+                info_text.push(Spans::from(vec![
+                    Span::raw("Codec: "),
+                    Span::styled(format!("{} ({})", codec, kind.label()), style),
+                ]));
+            },
+            None => info_text.push(Spans::from(format!("Codec: {}", codec))),
+        }
+    }
+    if let Some(duration) = app_state.select_file_info.duration {
+        let estimate = if app_state.select_file_info.duration_estimated { "~" } else { "" };
+        info_text.push(Spans::from(format!("Duration: {}{}", estimate, format_duration(duration))));
+    }
+    if let Some(bitrate) = app_state.select_file_info.bitrate {
+        // an estimated duration means the bitrate came from the first few packets
+        let estimate = if app_state.select_file_info.duration_estimated { "~" } else { "" };
+        info_text.push(Spans::from(format!("Bitrate: {}{} kbps", estimate, bitrate)));
+    }
+    if let Some(size) = app_state.select_file_info.file_size {
+        info_text.push(Spans::from(format!("Size: {}", format_size(size))));
+    }
+    if let Some(sr) = app_state.select_file_info.sample_rate {
+        info_text.push(Spans::from(format!("Sample Rate: {}", sr)));
+    }
+    if let Some(bd) = app_state.select_file_info.bit_depth {
+        info_text.push(Spans::from(format!("Bit Depth: {}", bd)));
+    }
+    if let Some(channels) = app_state.select_file_info.channels {
+        info_text.push(Spans::from(format!("Layout: {}", channels_label(channels))));
+    }
+    if let Some((start, end)) = app_state.select_file_info.sample_loop {
+        info_text.push(Spans::from(format!("Loop: {}–{} smp", start, end)));
+    }   
+    for (label, value) in app_state.select_file_metadata.fields() {
+        info_text.push(Spans::from(format!("{}: {}", label, value)));
+    }
+    match (app_state.select_file_metadata.replay_gain, app_state.select_file_metadata.r128_gain) {
+        (Some(gain), _) => info_text.push(Spans::from(format!("ReplayGain: {} dB", format_gain(gain)))),
+        (None, Some(gain)) => info_text.push(Spans::from(format!("ReplayGain: {} dB (R128)", format_gain(gain)))),
+        (None, None) => {},
+    }
+    if let Some(loudness) = app_state.selected_loudness() {
+        info_text.push(Spans::from(loudness.label()));
+        info_text.push(Spans::from(format!("RMS: {} dBFS", format_db(loudness.rms_dbfs))));
+    } else if matches!(&app_state.loudness_job, Some(job) if app_state.selected_file_path().as_ref() == Some(&job.path)) {
+        info_text.push(Spans::from(Span::styled("measuring loudness…", theme.dim)));
+    }
+    let rating = app_state.selected_entry()
+        .filter(|_| app_state.is_file_selected())
+        .and_then(|entry| app_state.entries[entry].rating);
+    if let Some(rating) = rating {
+        info_text.push(Spans::from(format!("Rating: {}", stars(rating))));
+    }
+    let cover_index = info_text.len();
+    info_text.push(Spans::from(format!("Pan: {}", app_state.sound_state.panning_label())));
+    if app_state.sound_state.sound.is_some() {
+        let mode = if app_state.sound_state.is_streaming() { "streaming" } else { "in memory" };
+        info_text.push(Spans::from(format!("Playback: {}", mode)));
+    }
+    if app_state.sound_state.mono_check {
+        info_text.push(Spans::from(Span::styled("MONO CHECK", theme.prompt)));
+    }
+    if show_info_pane {
+        let inner_width = info_pane_width.saturating_sub(2).max(1) as usize;
+        let count_rows = |lines: &[Spans]| -> usize {
+            lines.iter()
+                .map(|line| line.width().div_ceil(inner_width).max(1))
+                .sum()
+        };
+
+        // the cover art goes after the tags when there's color and room to draw it,
+        // and otherwise it's only described
+        let cover = app_state.selected_file_path()
+            .and_then(|path| app_state.covers.get(&path))
+            .and_then(|cover| cover.as_ref());
+        if let Some(cover) = cover {
+            let mut cover_lines = if theme.uses_color { cover.thumbnail_lines(inner_width as u16) } else { vec![] };
+            let needed = count_rows(&info_text) + cover_lines.len() + 2;
+            if cover_lines.is_empty() || needed > chunks[1].height as usize {
+                cover_lines = vec![Spans::from(Span::styled(cover.label(), theme.dim))];
+            }
+            info_text.splice(cover_index..cover_index, cover_lines);
+        }
+        let wrapped_lines = count_rows(&info_text);
+        let info_height = (wrapped_lines as u16 + 2).max(7).min(chunks[1].height);
+        chunks.push(Rect {x: tree_width + file_list_width, y: list_y, width: info_pane_width, height: info_height});
+    }
+
+    // remember how many rows of the list fit inside the borders for paging
+    app_state.list_viewport_height = chunks[1].height.saturating_sub(2).max(1) as usize;
+    app_state.list_area = Block::default().borders(Borders::ALL).inner(chunks[1]);
+
+    // the list is scrolled here rather than by tui so mouse clicks can be mapped
+    // back to entries, and only the rows that fit get built
+    let selected_row = app_state.file_list_state.selected();
+    app_state.list_offset = scroll_offset(app_state.list_offset, selected_row, app_state.list_viewport_height, app_state.visible_entries.len());
+    let shown_end = (app_state.list_offset + app_state.list_viewport_height).min(app_state.visible_entries.len());
+    let mut shown_list_state = ListState::default();
+    shown_list_state.select(selected_row.map(|row| row.saturating_sub(app_state.list_offset)));
+
+    // the size, duration, rating and modified time columns are dropped from the right
+    // when the list is too narrow to leave room for the names. the rating only gets a
+    // column once something is rated or being rated.
+    let row_width = (chunks[1].width as usize).saturating_sub(2 + ">> ".len());
+    let mut columns = vec![ListColumn::Size, ListColumn::Duration];
+    if app_state.rating_mode || app_state.entries.iter().any(|e| e.rating.is_some()) {
+        columns.push(ListColumn::Rating);
+    }
+    columns.push(ListColumn::Modified);
+    while !columns.is_empty() && row_width < MIN_NAME_COLUMN_WIDTH + columns.iter().map(|c| c.width()).sum::<usize>() {
+        columns.pop();
+    }
+    let name_width = row_width.saturating_sub(columns.iter().map(|c| c.width()).sum::<usize>());
+
+    // build the file list widget out of the entries that pass the filter,
+    // highlighting the part of the name that matched
+    // marked files get a '*' in front of their names
+    let shown_entries: Vec<(&BrowserEntry, usize, String, bool)> = app_state.visible_entries[app_state.list_offset..shown_end].iter()
+        .map(|&entry| {
+            let info = &app_state.entries[entry];
+            let prefix = if matches!(info.kind, EntryKind::ParentDir | EntryKind::Dir) { DIR_LISTITEM_PREFIX } else { "" };
+            let marked = !app_state.marked.is_empty() && app_state.marked.contains(&info.path);
+            let mark = if marked { MARK_PREFIX } else { "" };
+            let warning = if info.probe_failed { PROBE_FAILED_PREFIX } else { "" };
+            let name_start = mark.len() + warning.len() + prefix.len();
+            let name = format!("{:<width$}", truncate_right(&format!("{}{}{}{}", mark, warning, prefix, info.display_name()), name_width), width = name_width);
+            (info, name_start, name, marked)
+        })
+        .collect();
+    let file_list_items: Vec<ListItem> = shown_entries.iter()
+        .map(|(info, name_start, name, marked)| {
+            // a very narrow list can cut into the directory prefix
+            let search_start = if name.is_char_boundary(*name_start) { *name_start } else { name.len() };
+            let mut row = highlight_filter_match(name, search_start, &app_state.filter, theme.filter_match);
+            let column_text: String = columns.iter()
+                .map(|c| format!("{:>width$}", c.text(info), width = c.width()))
+                .collect();
+            row.0.push(Span::styled(column_text, theme.dim));
+            let new_li = ListItem::new(row);
+            if *marked {
+                new_li.style(theme.marked)
+            } else if info.is_hidden() {
+                new_li.style(theme.hidden)
+            } else if info.kind == EntryKind::Playlist {
+                new_li.style(theme.playlist)
+            } else if info.is_dir() {
+                new_li.style(theme.directory)
+            } else {
+                new_li.style(Style::default())
+            }
+        })
+        .collect();
+
+    let mut list_title = format!("Dir: {} [{}]", app_state.current_directory_path.display(), app_state.sort_mode.label());
+    if app_state.recursive {
+        match &app_state.scan {
+            Some(scan) => list_title.push_str(&format!(" [recursive: scanning, {} files]", scan.files_found)),
+            None if app_state.scan_truncated => list_title.push_str(&format!(" [recursive: first {} files]", app_state.file_count())),
+            None => list_title.push_str(" [recursive]"),
+        }
+    }
+    if let Some(ext) = &app_state.ext_filter {
+        list_title.push_str(&format!(" [*.{}]", ext));
+    }
+    if let Some(min_rating) = app_state.min_rating {
+        list_title.push_str(&format!(" [★{}+]", min_rating));
+    }
+    if app_state.rating_mode {
+        list_title.push_str(" [rating: 1-5 to rate, 0 to clear, esc to stop]");
+    }
+    if app_state.dir_changed {
+        list_title.push_str(" [changed on disk, refresh to see]");
+    }
+    if !app_state.filter.is_empty() {
+        list_title.push_str(&format!(" [filter: {}]", app_state.filter));
+    }
+    let list_block = Block::default()
+        .title(list_title)
+        .borders(Borders::ALL);
+    let list_widget = List::new(file_list_items)
+        .block(list_block)
+        .highlight_style(theme.selection)
+        .highlight_symbol(">> ");
+    
+    
+    // the queue pane takes the place of the file list when it has the focus
+    if app_state.focus == Focus::Queue {
+        let queue_items: Vec<ListItem> = app_state.queue.iter()
+            .map(|p| ListItem::new(p.display().to_string()))
+            .collect();
+        let queue_block = Block::default()
+            .title(format!("Queue: {} (tab to return)", app_state.queue.len()))
+            .borders(Borders::ALL);
+        let queue_widget = List::new(queue_items)
+            .block(queue_block)
+            .highlight_style(theme.selection)
+            .highlight_symbol(">> ");
+        f.render_stateful_widget(queue_widget, chunks[1], &mut app_state.queue_list_state);
+    } else {
+        f.render_stateful_widget(list_widget, chunks[1], &mut shown_list_state);
+        // an empty box would look like the list is broken or still loading
+        if app_state.visible_entries.is_empty() && app_state.scan.is_none() && app_state.list_area.height > 0 {
+            let placeholder = if app_state.filter.is_empty() && app_state.ext_filter.is_none() {
+                if app_state.tree_shown { "No playable files" } else { "No playable files or directories" }
+            } else {
+                "Nothing matches the filter"
+            };
+            let area = app_state.list_area;
+            let placeholder_area = Rect::new(area.x, area.y + area.height / 2, area.width, 1);
+            let placeholder_widget = Paragraph::new(placeholder)
+                .alignment(tui::layout::Alignment::Center)
+                .style(theme.dim);
+            f.render_widget(placeholder_widget, placeholder_area);
+        }
+    }
+
+    if tree_width > 0 {
+        let tree_pane = Rect {x: 0, y: list_y, width: tree_width, height: chunks[1].height};
+        draw_tree(app_state, f, tree_pane);
+    }
+
+    // put a title bar at the top
+    let mut title_spans = vec![Span::raw("spinup:  (?)help | (space)play or enter dir | (bksp)stop | (q)quit")];
+    if let Some((done, total)) = app_state.duration_probe_progress() {
+        title_spans.push(Span::styled(format!("  (scanning {}/{})", done, total), theme.dim));
+    }
+    let title_widget = Paragraph::new(Spans::from(title_spans))
+        .alignment(tui::layout::Alignment::Left)
+        .style(theme.title);
+    if show_title {
+        f.render_widget(title_widget, chunks[0]);
+    }
+
+    // the playback status readout sits on the right side of the title bar, after
+    // the count or 'g' being typed
+    let mut status_spans = vec![];
+    if app_state.pending_count.is_some() || app_state.pending_g {
+        let count = app_state.pending_count.map(|c| c.to_string()).unwrap_or_default();
+        let g = if app_state.pending_g { "g" } else { "" };
+        status_spans.push(Span::styled(format!(" {}{} ", count, g), theme.prompt));
+        status_spans.push(Span::raw("|"));
+    }
+    match app_state.advance_mode {
+        AdvanceMode::Off => {},
+        AdvanceMode::StopAtEnd => status_spans.push(Span::raw(" AUTO |")),
+        AdvanceMode::Wrap => status_spans.push(Span::raw(" AUTO+WRAP |")),
+        AdvanceMode::RepeatOne => status_spans.push(Span::raw(" REPEAT |")),
+    }
+    if app_state.shuffle {
+        status_spans.push(Span::raw(" SHUFFLE |"));
+    }
+    if app_state.sound_state.preview {
+        status_spans.push(Span::raw(" PREVIEW |"));
+    }
+    if !app_state.queue.is_empty() {
+        status_spans.push(Span::raw(format!(" Queue: {} |", app_state.queue.len())));
+    }
+    if !app_state.marked.is_empty() {
+        status_spans.push(Span::styled(format!(" Marked: {} ", app_state.marked.len()), theme.marked));
+        status_spans.push(Span::raw("|"));
+    }
+    if let Some(op) = &app_state.file_operation {
+        let verb = if op.moving { "MOVING" } else { "COPYING" };
+        match op.progress {
+            Some(p) if p.bytes_total > 0 => status_spans.push(Span::raw(format!(" {} {}/{} {}% |", verb, p.file + 1, p.file_count, p.bytes_done * 100 / p.bytes_total))),
+            Some(p) => status_spans.push(Span::raw(format!(" {} {}/{} |", verb, p.file + 1, p.file_count))),
+            None => status_spans.push(Span::raw(format!(" {} |", verb))),
+        }
+    }
+    if let Some(job) = &app_state.export_job {
+        let percent = job.progress.load(std::sync::atomic::Ordering::Relaxed);
+        status_spans.push(Span::raw(format!(" {} {}% |", if job.converting { "CONVERTING" } else { "TRIMMING" }, percent)));
+    }
+    if let Some(run) = &app_state.play_all {
+        status_spans.push(Span::raw(format!(" PLAY ALL {}/{} |", run.next, run.paths.len())));
+    }
+    if app_state.sound_state.muted {
+        status_spans.push(Span::styled(" MUTED ", theme.error));
+    }
+    if (app_state.sound_state.playback_rate - 1.0).abs() > f64::EPSILON {
+        status_spans.push(Span::raw(format!(" {:.2}x |", app_state.sound_state.playback_rate)));
+    }
+    if app_state.sound_state.replay_gain {
+        match app_state.sound_state.applied_gain() {
+            Some(gain) => status_spans.push(Span::raw(format!(" RG {} dB |", format_gain(gain)))),
+            None => status_spans.push(Span::raw(" RG |")),
+        }
+    }
+    status_spans.push(Span::raw(format!(" Vol: {}% ", (app_state.sound_state.volume * 100.0).round())));
+    let status_widget = Paragraph::new(Spans::from(status_spans))
+        .alignment(tui::layout::Alignment::Right)
+        .style(theme.title);
+    if show_title {
+        f.render_widget(status_widget, chunks[0]);
+    }
+
+    if let (Some(area), Some(playing_path)) = (now_playing_area, &app_state.sound_state.playing_path) {
+        let state_label = format!("{}: ", app_state.sound_state.play_state_label());
+        let path_width = (area.width as usize).saturating_sub(state_label.chars().count());
+        let now_playing_widget = Paragraph::new(Spans::from(vec![
+            Span::styled(state_label, theme.playing.add_modifier(tui::style::Modifier::BOLD)),
+            Span::raw(truncate_path_left(&playing_path.display().to_string(), path_width)),
+        ]));
+        f.render_widget(now_playing_widget, area);
+    }
+
+    // a pending prompt shows over everything else, then text being entered, then errors if we have any
+    if let Some(prompt) = &app_state.prompt {
+        // the files about to be deleted are listed over the file list
+        if let Prompt::Delete { paths, .. } = prompt {
+            let list_inner = app_state.list_area;
+            let height = (paths.len() as u16 + 2).min(list_inner.height);
+            let area = Rect {
+                x: list_inner.x + 2,
+                y: list_inner.y + (list_inner.height - height) / 2,
+                width: list_inner.width.saturating_sub(4),
+                height,
+            };
+            let delete_items: Vec<ListItem> = paths.iter()
+                .map(|p| ListItem::new(p.display().to_string()))
+                .collect();
+            let delete_widget = List::new(delete_items)
+                .block(Block::default().title(prompt.message()).borders(Borders::ALL))
+                .style(theme.error);
+            f.render_widget(Clear, area);
+            f.render_widget(delete_widget, area);
+        }
+        let prompt_widget = Paragraph::new(prompt.message())
+            .style(theme.prompt.add_modifier(tui::style::Modifier::BOLD));
+        f.render_widget(prompt_widget, chunks[2]);
+    } else if let Some(input) = &app_state.text_input {
+        let input_line = format!("{}: {}", input.kind.label(), input.text);
+        let cursor_x = chunks[2].x + (input_line.chars().count() as u16).min(chunks[2].width.saturating_sub(1));
+        // errors show after the input since the input stays open to fix them
+        let mut input_spans = vec![Span::styled(input_line, theme.prompt)];
+        if !app_state.last_error_msg.is_empty() {
+            input_spans.push(Span::styled(format!("  {}", app_state.last_error_msg), theme.error));
+        }
+        let input_widget = Paragraph::new(Spans::from(input_spans));
+        f.render_widget(input_widget, chunks[2]);
+        f.set_cursor(cursor_x, chunks[2].y);
+    } else if !app_state.last_error_msg.is_empty() {
+        let err_widget = Paragraph::new(app_state.last_error_msg.as_ref())
+            .style(theme.error);
+        f.render_widget(err_widget, chunks[2]);
+    } else if !app_state.last_info_msg.is_empty() {
+        let info_widget = Paragraph::new(app_state.last_info_msg.as_ref())
+            .style(theme.prompt);
+        f.render_widget(info_widget, chunks[2]);
+    } else if app_state.sound_state.show_finished() {
+        let finished_widget = Paragraph::new("Finished")
+            .style(theme.playing);
+        f.render_widget(finished_widget, chunks[2]);
+    } else if app_state.sound_state.is_audible() {
+        let cur_ms = app_state.sound_state.play_time.as_millis();
+        let total_ms = app_state.sound_state.play_duration.as_millis();
+        let pct: f64 = cur_ms as f64 / total_ms as f64;
+        // the play time can run a tick past the end, or past a length that was only
+        // estimated, so a full bar is clamped and a sound without a length shows an
+        // empty one rather than the bar going away
+        let pct = if pct.is_finite() { pct } else { 0.0 };
+        // the elapsed and total time go to the right of the gauge, unless the
+        // terminal is too narrow to fit both. an estimated total gets a '~'
+        // until its real length has been counted up.
+        let estimate = if app_state.sound_state.duration_estimated { "~" } else { "" };
+        let elapsed = if app_state.sound_state.duration_estimated {
+            app_state.sound_state.play_time
+        } else {
+            app_state.sound_state.play_time.min(app_state.sound_state.play_duration)
+        };
+        let time_text = format!(" {} / {}{}",
+            format_duration(elapsed),
+            estimate,
+            format_duration(app_state.sound_state.play_duration));
+        let time_width = time_text.chars().count() as u16;
+        let mut gauge_area = chunks[2];
+        if gauge_area.width >= time_width + MIN_GAUGE_WIDTH {
+            gauge_area.width -= time_width;
+            let time_area = Rect {x: gauge_area.x + gauge_area.width, width: time_width, ..chunks[2]};
+            f.render_widget(Paragraph::new(time_text), time_area);
+        }
+
+        // an A-B loop region gets labeled on the gauge and drawn in a different style
+        let loop_label = app_state.sound_state.loop_region_label();
+        let gauge_style = if loop_label.is_some() { theme.loop_gauge } else { theme.gauge };
+        let ratio = pct.clamp(0.0, 1.0);
+        if theme.uses_color {
+            let mut progress = Gauge::default().gauge_style(gauge_style).ratio(ratio);
+            if let Some(loop_label) = loop_label {
+                progress = progress.label(loop_label);
+            }
+            f.render_widget(progress, gauge_area);
+        } else {
+            // a block gauge is filled in with colors alone, so without them the
+            // progress gets drawn as a line instead
+            let mut progress = LineGauge::default()
+                .gauge_style(gauge_style)
+                .line_set(tui::symbols::line::THICK)
+                .ratio(ratio);
+            if let Some(loop_label) = loop_label {
+                progress = progress.label(loop_label);
+            }
+            f.render_widget(progress, gauge_area);
+        }
+        app_state.gauge_area = Some(gauge_area);
+    }
+    
+    // build the file info widget if it is used
+    if show_info_pane && chunks.len() > 3 {
+        let info_block = Block::default()
+            .title("File Information")
+            .borders(Borders::ALL);
+        let info_para = Paragraph::new(info_text)
+            .block(info_block)
+            .wrap(tui::widgets::Wrap {trim:true});
+        f.render_widget(info_para, chunks[3]);
+    }
+
+    if let Some(area) = meter_area {
+        let labels: &[&str] = if levels.len() == 1 { &["M"] } else { &["L", "R"] };
+        let meter_lines: Vec<Spans> = levels.iter()
+            .zip(labels)
+            .map(|(level, label)| level_meter_line(label, level, area.width as usize, &theme))
+            .collect();
+        f.render_widget(Paragraph::new(meter_lines), area);
+    }
+
+    if let Some(area) = waveform_area {
+        let waveform_block = Block::default()
+            .title("Waveform")
+            .borders(Borders::ALL);
+        let inner = waveform_block.inner(area);
+        f.render_widget(waveform_block, area);
+
+        let selected_path = app_state.selected_file_path();
+        let waveform_widget = match selected_path.as_ref().and_then(|p| app_state.waveforms.get(p)) {
+            Some(Some(peaks)) => {
+                // the playhead only shows on the waveform of the file that's playing
+                let mut playhead = None;
+                if app_state.sound_state.is_audible() && app_state.sound_state.playing_path == selected_path {
+                    let fraction = app_state.sound_state.play_time.as_secs_f64() / app_state.sound_state.play_duration.as_secs_f64();
+                    if fraction.is_finite() {
+                        playhead = Some(((fraction.clamp(0.0, 1.0) * inner.width as f64) as usize).min((inner.width as usize).saturating_sub(1)));
+                    }
+                }
+                Paragraph::new(waveform_lines(peaks, inner.width as usize, inner.height as usize, playhead, &theme))
+            },
+            Some(None) => Paragraph::new("no preview").style(theme.dim),
+            None => Paragraph::new("reading waveform...").style(theme.dim),
+        };
+        f.render_widget(waveform_widget, inner);
+    }
+
+    // the bookmarks draw on top of everything else, with directories that are
+    // gone shown in red
+    if let Some(list_state) = &mut app_state.bookmark_list {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let bookmarks_block = Block::default()
+            .title("Bookmarks (1-9 or enter to jump, x to remove, esc to close)")
+            .borders(Borders::ALL);
+        if app_state.bookmarks.is_empty() {
+            let empty_widget = Paragraph::new("No bookmarks yet, press 'b' to bookmark the current directory")
+                .block(bookmarks_block);
+            f.render_widget(empty_widget, area);
+        } else {
+            let bookmark_items: Vec<ListItem> = app_state.bookmarks.iter()
+                .enumerate()
+                .map(|(i, dir)| {
+                    let number = if i < 9 { format!("{}", i + 1) } else { " ".to_string() };
+                    let item = ListItem::new(format!("{}  {}", number, dir.display()));
+                    if dir.is_dir() {
+                        item
+                    } else {
+                        item.style(theme.error)
+                    }
+                })
+                .collect();
+            let bookmarks_widget = List::new(bookmark_items)
+                .block(bookmarks_block)
+                .highlight_style(theme.selection)
+                .highlight_symbol(">> ");
+            f.render_stateful_widget(bookmarks_widget, area, list_state);
+        }
+    }
+
+    // the recently played files draw on top of everything else as well, with files
+    // that are gone dimmed
+    if let Some(list_state) = &mut app_state.recent_list {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let recent_block = Block::default()
+            .title("Recently Played (enter to go to, space to play, esc to close)")
+            .borders(Borders::ALL);
+        if app_state.recent.is_empty() {
+            let empty_widget = Paragraph::new("Nothing has been played yet")
+                .block(recent_block);
+            f.render_widget(empty_widget, area);
+        } else {
+            let recent_items: Vec<ListItem> = app_state.recent.iter()
+                .map(|play| {
+                    let played_at = chrono::DateTime::<chrono::Local>::from(play.played_at).format("%Y-%m-%d %H:%M");
+                    let item = ListItem::new(format!("{}  {}", played_at, play.path.display()));
+                    if play.path.is_file() {
+                        item
+                    } else {
+                        item.style(theme.dim)
+                    }
+                })
+                .collect();
+            let recent_widget = List::new(recent_items)
+                .block(recent_block)
+                .highlight_style(theme.selection)
+                .highlight_symbol(">> ");
+            f.render_stateful_widget(recent_widget, area, list_state);
+        }
+    }
+
+    // the duplicates draw on top of everything else as well, numbered by their group
+    // with the marked files standing out, or how far the hashing has got
+    if let Some(list) = &mut app_state.duplicates {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let mode = if list.deep { "decoded sound" } else { "bytes" };
+        let duplicates_block = Block::default()
+            .title(format!("Duplicates by {} (space to mark, a to mark copies, d to delete marked, D to switch, esc to close)", mode))
+            .borders(Borders::ALL);
+        if let Some(scan) = &list.scan {
+            let done = scan.done.load(std::sync::atomic::Ordering::Relaxed);
+            let total = scan.total.load(std::sync::atomic::Ordering::Relaxed);
+            let hashing_widget = Paragraph::new(format!("Hashing {}/{} files…", done, total))
+                .block(duplicates_block);
+            f.render_widget(hashing_widget, area);
+        } else if list.groups.is_empty() {
+            let empty_widget = Paragraph::new("No duplicates among the listed files")
+                .block(duplicates_block);
+            f.render_widget(empty_widget, area);
+        } else {
+            let current_dir = &app_state.current_directory_path;
+            let duplicate_items: Vec<ListItem> = list.rows()
+                .map(|(group, path)| {
+                    let name = path.strip_prefix(current_dir).unwrap_or(path).display();
+                    let item = ListItem::new(format!("{:>3}  {}", group + 1, name));
+                    if app_state.marked.contains(path) {
+                        item.style(theme.marked)
+                    } else {
+                        item
+                    }
+                })
+                .collect();
+            let duplicates_widget = List::new(duplicate_items)
+                .block(duplicates_block)
+                .highlight_style(theme.selection)
+                .highlight_symbol(">> ");
+            f.render_stateful_widget(duplicates_widget, area, &mut list.list_state);
+        }
+    }
+
+    // the output devices draw on top of everything else too, with the default device
+    // as the first row
+    if let Some(picker) = &mut app_state.device_picker {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let devices_block = Block::default()
+            .title("Output Devices (enter to play on, esc to close)")
+            .borders(Borders::ALL);
+        let default_label = match &picker.default_name {
+            Some(name) => format!("default output device ({})", name),
+            None => "default output device".to_string(),
+        };
+        let mut device_items = vec![ListItem::new(default_label)];
+        device_items.extend(picker.names.iter().map(|name| ListItem::new(name.as_str())));
+        let devices_widget = List::new(device_items)
+            .block(devices_block)
+            .highlight_style(theme.selection)
+            .highlight_symbol(">> ");
+        f.render_stateful_widget(devices_widget, area, &mut picker.list_state);
+    }
+
+    // the audio tracks of a file draw on top of everything else as well, numbered
+    // the way the container numbers them
+    if let Some(picker) = &mut app_state.track_picker {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let name = picker.path.file_name().unwrap_or(picker.path.as_os_str()).to_string_lossy();
+        let tracks_block = Block::default()
+            .title(format!("Audio Tracks of {} (enter to play, esc to close)", name))
+            .borders(Borders::ALL);
+        let track_items: Vec<ListItem> = picker.tracks.iter()
+            .map(|track| {
+                let channels = match track.channels {
+                    Some(1) => "mono".to_string(),
+                    Some(2) => "stereo".to_string(),
+                    Some(count) => format!("{} channels", count),
+                    None => "unknown channels".to_string(),
+                };
+                let language = track.language.as_deref().unwrap_or("no language");
+                ListItem::new(format!("#{}  {}  {}  {}", track.id, track.codec.unwrap_or("unknown codec"), channels, language))
+            })
+            .collect();
+        let tracks_widget = List::new(track_items)
+            .block(tracks_block)
+            .highlight_style(theme.selection)
+            .highlight_symbol(">> ");
+        f.render_stateful_widget(tracks_widget, area, &mut picker.list_state);
+    }
+
+    // the help lists every binding in the keymap by category, scrolling when the
+    // terminal is too small to fit it all
+    if let Some(scroll) = &mut app_state.help_scroll {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let keys_width = app_state.keymap.bindings().iter()
+            .map(|binding| binding.keys_label().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut help_lines: Vec<Spans> = vec![];
+        for category in keymap::Category::ALL {
+            if !help_lines.is_empty() {
+                help_lines.push(Spans::from(""));
+            }
+            help_lines.push(Spans::from(Span::styled(category.title(), theme.title)));
+            for binding in app_state.keymap.bindings().iter().filter(|b| b.category == category) {
+                help_lines.push(Spans::from(vec![
+                    Span::styled(format!("  {:<width$}  ", binding.keys_label(), width = keys_width), theme.prompt),
+                    Span::raw(binding.description),
+                ]));
+            }
+        }
+        let help_block = Block::default()
+            .title("Keys (up/down to scroll, any other key to close)")
+            .borders(Borders::ALL);
+        let max_scroll = (help_lines.len() as u16).saturating_sub(help_block.inner(area).height);
+        *scroll = (*scroll).min(max_scroll);
+        let help_widget = Paragraph::new(help_lines)
+            .block(help_block)
+            .scroll((*scroll, 0));
+        f.render_widget(help_widget, area);
+    }
+
+    // the error log lists the newest errors first
+    if let Some(scroll) = &mut app_state.error_log_scroll {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let error_lines: Vec<Spans> = if app_state.error_log.is_empty() {
+            vec![Spans::from(Span::styled("No errors yet", theme.dim))]
+        } else {
+            app_state.error_log.iter()
+                .rev()
+                .map(|logged| Spans::from(vec![
+                    Span::styled(format!("{}  ", logged.time.format("%H:%M:%S")), theme.dim),
+                    Span::styled(logged.message.clone(), theme.error),
+                ]))
+                .collect()
+        };
+        let error_block = Block::default()
+            .title(format!("Errors: {} (up/down to scroll, any other key to close)", app_state.error_log.len()))
+            .borders(Borders::ALL);
+        let max_scroll = (error_lines.len() as u16).saturating_sub(error_block.inner(area).height);
+        *scroll = (*scroll).min(max_scroll);
+        let error_widget = Paragraph::new(error_lines)
+            .block(error_block)
+            .wrap(tui::widgets::Wrap {trim: false})
+            .scroll((*scroll, 0));
+        f.render_widget(error_widget, area);
+    }
+
+    // the fuzzy finder draws on top of everything else
+    if let Some(finder) = &mut app_state.finder {
+        let area = overlay_area(whole_frame);
+        f.render_widget(Clear, area);
+        let finder_block = Block::default()
+            .title(format!("Find ({} matches, esc to cancel)", finder.results.len()))
+            .borders(Borders::ALL);
+        let inner = finder_block.inner(area);
+        f.render_widget(finder_block, area);
+        if inner.height < 2 {
+            return;
+        }
+
+        let query_line = format!("> {}", finder.query);
+        let cursor_x = inner.x + (query_line.chars().count() as u16).min(inner.width.saturating_sub(1));
+        f.render_widget(Paragraph::new(query_line), Rect {height: 1, ..inner});
+        f.set_cursor(cursor_x, inner.y);
+
+        // matched characters are highlighted in each result
+        let match_style = theme.filter_match.add_modifier(tui::style::Modifier::BOLD);
+        let result_items: Vec<ListItem> = finder.results.iter()
+            .take(FINDER_MAX_RESULTS)
+            .map(|result| {
+                let mut spans = vec![];
+                if result.is_dir {
+                    spans.push(Span::styled(DIR_LISTITEM_PREFIX, theme.directory));
+                }
+                for (i, c) in result.name.chars().enumerate() {
+                    if result.positions.contains(&i) {
+                        spans.push(Span::styled(c.to_string(), match_style));
+                    } else {
+                        spans.push(Span::raw(c.to_string()));
+                    }
+                }
+                ListItem::new(Spans::from(spans))
+            })
+            .collect();
+        let results_widget = List::new(result_items)
+            .highlight_style(theme.selection)
+            .highlight_symbol(">> ");
+        let results_area = Rect {y: inner.y + 1, height: inner.height - 1, ..inner};
+        f.render_stateful_widget(results_widget, results_area, &mut finder.list_state);
+    }
+}
+
+// draws the directory tree in `area`, scrolled the same way as the file list so
+// clicks can be mapped back to its rows
+fn draw_tree<B: tui::backend::Backend>(app_state: &mut AppState, f: &mut tui::Frame<B>, area: Rect) {
+    let theme = app_state.theme_kind.theme();
+    let block = Block::default()
+        .title(if app_state.focus == Focus::Tree { "Dirs (tab to files)" } else { "Dirs" })
+        .borders(Borders::ALL);
+    app_state.tree_area = block.inner(area);
+    let tree = match &mut app_state.tree {
+        Some(tree) => tree,
+        None => return,
+    };
+    let height = (app_state.tree_area.height as usize).max(1);
+    let selected_row = tree.list_state.selected();
+    tree.offset = scroll_offset(tree.offset, selected_row, height, tree.rows.len());
+    let shown_end = (tree.offset + height).min(tree.rows.len());
+    let name_width = (app_state.tree_area.width as usize).saturating_sub(">> ".len());
+    let tree_items: Vec<ListItem> = tree.rows[tree.offset..shown_end].iter()
+        .map(|row| {
+            let prefix = match (row.is_parent, row.expanded) {
+                (true, _) => "",
+                (false, true) => TREE_EXPANDED_PREFIX,
+                (false, false) => TREE_COLLAPSED_PREFIX,
+            };
+            let label = row.label();
+            let name = truncate_right(&format!("{}{}{}", "  ".repeat(row.depth), prefix, label), name_width);
+            let style = if row.depth > 0 && label.starts_with('.') { theme.hidden } else { theme.directory };
+            ListItem::new(name).style(style)
+        })
+        .collect();
+    let mut shown_list_state = ListState::default();
+    shown_list_state.select(selected_row.map(|row| row.saturating_sub(tree.offset)));
+    let tree_widget = List::new(tree_items)
+        .block(block)
+        .highlight_style(theme.selection)
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(tree_widget, area, &mut shown_list_state);
+}
+
+// keeps the selected row inside a list `height` rows tall, scrolling from `offset`
+// as little as possible the same way tui's own lists do
+pub(crate) fn scroll_offset(offset: usize, selected: Option<usize>, height: usize, len: usize) -> usize {
+    let mut offset = offset.min(len.saturating_sub(height));
+    if let Some(selected) = selected.filter(|&selected| selected < len) {
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + height {
+            offset = selected + 1 - height;
+        }
+    }
+    offset
+}
+
+// returns the area in the middle of the screen that overlays are drawn in
+pub(crate) fn overlay_area(whole_frame: Rect) -> Rect {
+    let width = (whole_frame.width * 3 / 4).max(20).min(whole_frame.width);
+    let height = (whole_frame.height * 3 / 4).max(5).min(whole_frame.height);
+    Rect {
+        x: (whole_frame.width - width) / 2, 
+        y: (whole_frame.height - height) / 2, 
+        width, 
+        height,
+    }
+}
+
+// the columns shown after the names in the file list
+pub(crate) enum ListColumn {
+    Size,
+    Duration,
+    Rating,
+    Modified,
+}
+
+impl ListColumn {
+    // the width of the column including the space separating it from the one before
+    pub(crate) fn width(&self) -> usize {
+        match self {
+            ListColumn::Size => 10,
+            ListColumn::Duration => 10,
+            ListColumn::Rating => 7,
+            ListColumn::Modified => 18,
+        }
+    }
+
+    // the text of the column for an entry. directories only have a modified time.
+    pub(crate) fn text(&self, info: &BrowserEntry) -> String {
+        match self {
+            ListColumn::Size if !info.is_dir() => format_size(info.size),
+            ListColumn::Duration if !info.is_dir() => info.duration.map(format_duration).unwrap_or_default(),
+            ListColumn::Rating if !info.is_dir() => info.rating.map(stars).unwrap_or_default(),
+            ListColumn::Modified => info.modified
+                .map(|m| chrono::DateTime::<chrono::Local>::from(m).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+// splits `text` into spans so the first case-insensitive match of `filter` at or
+// after byte `search_start` stands out
+pub(crate) fn highlight_filter_match<'a>(text: &'a str, search_start: usize, filter: &str, match_style: Style) -> Spans<'a> {
+    if let Some((start, end)) = find_case_insensitive(&text[search_start..], filter) {
+        let (start, end) = (start + search_start, end + search_start);
+        return Spans::from(vec![
+            Span::raw(&text[..start]),
+            Span::styled(&text[start..end], match_style),
+            Span::raw(&text[end..]),
+        ]);
+    }
+    Spans::from(text)
+}
+
+// shortens text to at most `max_chars` by cutting off the end, marking the cut with '…'
+pub(crate) fn truncate_right(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+// formats a gain in dB with its sign and a proper minus, like "−7.2" or "+1.5"
+pub(crate) fn format_gain(gain: f64) -> String {
+    format!("{:+.1}", gain).replace('-', "\u{2212}")
+}
+
+// formats a file size in bytes with binary units, like "1.5 MB"
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// shortens a path to at most `max_chars` by cutting it from the left, starting at a
+// separator where possible, like "…/drums/kick_03.wav"
+pub(crate) fn truncate_path_left(path: &str, max_chars: usize) -> String {
+    let char_count = path.chars().count();
+    if char_count <= max_chars {
+        return path.to_string();
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+    let tail: String = path.chars().skip(char_count - (max_chars - 1)).collect();
+    let tail = match tail.find(std::path::is_separator) {
+        Some(sep) => &tail[sep..],
+        None => &tail[..],
+    };
+    format!("…{}", tail)
+}
+
+// formats a duration as mm:ss, or h:mm:ss for long durations
+pub(crate) fn format_duration(d: std::time::Duration) -> String {
+    let total_secs = d.as_secs();
+    let (hours, mins, secs) = (total_secs / 3600, (total_secs / 60) % 60, total_secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, mins, secs)
+    } else {
+        format!("{:02}:{:02}", mins, secs)
+    }
+}
+
+// converts a linear amplitude to dBFS, clamped to the bottom of the level meter
+pub(crate) fn amplitude_to_db(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return LEVEL_METER_MIN_DB;
+    }
+    (20.0 * amplitude.log10()).max(LEVEL_METER_MIN_DB)
+}
+
+// draws one channel of the level meter: the RMS level as a bar, the peak as a
+// marker past it and the peak in dB at the end, which reads CLIP at full scale
+pub(crate) fn level_meter_line(label: &str, level: &ChannelLevel, width: usize, theme: &theme::Theme) -> Spans<'static> {
+    let peak_text = if level.peak >= 1.0 {
+        "     CLIP".to_string()
+    } else if level.peak <= 0.0 {
+        "  -inf dB".to_string()
+    } else {
+        format!("{:>6.1} dB", amplitude_to_db(level.peak))
+    };
+    let bar_width = width.saturating_sub(label.len() + 1 + peak_text.len() + 1);
+    let to_col = |db: f32| (((db - LEVEL_METER_MIN_DB) / -LEVEL_METER_MIN_DB * bar_width as f32) as usize).min(bar_width);
+    let rms_cols = to_col(amplitude_to_db(level.rms));
+    let peak_col = if level.peak > 0.0 { Some(to_col(amplitude_to_db(level.peak)).min(bar_width.saturating_sub(1))) } else { None };
+    let tick_cols: Vec<usize> = LEVEL_METER_TICKS_DB.iter().map(|db| to_col(*db)).collect();
+
+    // the bar turns yellow in the last 6 dB and red when it clips
+    let level_style = |col: usize| {
+        if level.peak >= 1.0 && col + 1 >= bar_width {
+            theme.level_clip
+        } else if col >= to_col(-6.0) {
+            theme.level_high
+        } else {
+            theme.level_low
+        }
+    };
+    let mut spans = vec![Span::styled(format!("{} ", label), theme.title)];
+    for col in 0..bar_width {
+        let span = if col < rms_cols {
+            Span::styled("█", level_style(col))
+        } else if peak_col == Some(col) {
+            Span::styled("▌", level_style(col))
+        } else if tick_cols.contains(&col) {
+            Span::styled("┊", theme.dim)
+        } else {
+            Span::styled("·", theme.dim)
+        };
+        spans.push(span);
+    }
+    let peak_style = if level.peak >= 1.0 { theme.level_clip } else { Style::default() };
+    spans.push(Span::styled(format!(" {}", peak_text), peak_style));
+    Spans::from(spans)
+}
+
+// draws the min/max pairs of a waveform as `height` lines of block characters,
+// using half blocks so each line holds two steps of the amplitude. the pairs
+// are resampled to one per column, and the playhead column is highlighted.
+pub(crate) fn waveform_lines(peaks: &[(f32, f32)], width: usize, height: usize, playhead: Option<usize>, theme: &theme::Theme) -> Vec<Spans<'static>> {
+    if peaks.is_empty() || width == 0 || height == 0 {
+        return vec![];
+    }
+
+    // the range of half rows each column covers, from the top
+    let half_rows = height * 2;
+    let columns: Vec<(usize, usize)> = (0..width)
+        .map(|col| {
+            let start = col * peaks.len() / width;
+            let end = ((col + 1) * peaks.len() / width).max(start + 1);
+            let (min, max) = peaks[start..end].iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), &(lo, hi)| (min.min(lo), max.max(hi)));
+            let to_half_row = |v: f32| (((1.0 - v.clamp(-1.0, 1.0)) / 2.0 * half_rows as f32) as usize).min(half_rows - 1);
+            (to_half_row(max), to_half_row(min))
+        })
+        .collect();
+
+    (0..height)
+        .map(|row| {
+            let spans: Vec<Span> = columns.iter()
+                .enumerate()
+                .map(|(col, &(top, bottom))| {
+                    let upper = (top..=bottom).contains(&(row * 2));
+                    let lower = (top..=bottom).contains(&(row * 2 + 1));
+                    let c = match (upper, lower) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    };
+                    if playhead == Some(col) {
+                        Span::styled(c.to_string(), theme.playhead)
+                    } else {
+                        Span::styled(c.to_string(), theme.waveform)
+                    }
+                })
+                .collect();
+            Spans::from(spans)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::backend::TestBackend;
+
+    // lists a directory with a couple of files and a subdirectory
+    fn listed_fixture() -> (tempfile::TempDir, AppState) {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["kick.wav", "snare.wav"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("loops")).unwrap();
+        let mut app_state = AppState::default();
+        app_state.set_current_directory(dir.path());
+        app_state.update_file_names();
+        app_state.select_list_item(2);
+        (dir, app_state)
+    }
+
+    // draws the app on a terminal of the given size, returning its rows
+    fn draw(app_state: &mut AppState, width: u16, height: u16) -> Vec<String> {
+        app_state.update_tree_layout(width);
+
+        let mut terminal = tui::Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| ui(app_state, f)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| (0..width).map(|x| buffer.get(x, y).symbol.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn tiny_terminals_only_say_they_are_too_small() {
+        let (_dir, mut app_state) = listed_fixture();
+        assert!(draw(&mut app_state, 0, 0).is_empty());
+        assert_eq!(draw(&mut app_state, 1, 1), ["t"]);
+        for (width, height) in [(MIN_FRAME_WIDTH - 1, MIN_FRAME_HEIGHT), (MIN_FRAME_WIDTH, MIN_FRAME_HEIGHT - 1), (100, 5)] {
+            let rows = draw(&mut app_state, width, height);
+            assert!(rows[0].starts_with("terminal too small"), "{}x{}: {:?}", width, height, rows);
+        }
+    }
+
+    #[test]
+    fn the_smallest_terminal_draws_the_list() {
+        let (_dir, mut app_state) = listed_fixture();
+        let rows = draw(&mut app_state, MIN_FRAME_WIDTH, MIN_FRAME_HEIGHT);
+        assert!(!rows.concat().contains("too small"));
+        assert!(rows.concat().contains("loops"), "{:?}", rows);
+    }
+
+    #[test]
+    fn terminals_of_every_size_near_the_minimum_draw() {
+        let (_dir, mut app_state) = listed_fixture();
+        for width in 0..=TREE_LAYOUT_MIN_WIDTH + 2 {
+            for height in 0..=MIN_LIST_HEIGHT_FOR_WAVEFORM + 2 {
+                draw(&mut app_state, width, height);
+            }
+        }
+    }
+}